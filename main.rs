@@ -10,7 +10,7 @@ extern crate core;
 /// A fast compressor/decompressor
 ///
 /// Snappy is a compression/decompression library. It does not aim for maximum compression, or compatibility with any other compression library; instead, it aims for very high speeds and reasonable compression.
-mod snappy;
+extern crate snappy;
 
 use libc::{printf, fprintf, open, close, fread, fwrite};
 use libc::{strcmp, strcat};
@@ -0,0 +1,141 @@
+//! Link logic of snappy-sys
+//!
+//! With feature `vendored`, the bundled snappy sources in `snappy/` are compiled and linked statically,
+//! otherwise the system libsnappy is found with pkg-config (vcpkg for MSVC) and linked.
+//!
+//! Features `static` and `dynamic` choose the linkage of system libsnappy, the default is what pkg-config finds.
+//!
+//! `snappy_compress_level` is compiled from `shim/level.cc` when the headers have `CompressionOptions`, emitting
+//! cfg `snappy_level`. Support is found by compiling the shim rather than from the version: snapshots between
+//! 1.1.10 and 1.2.0 have it and still say 1.1.10.
+
+use std::env;
+use std::path::PathBuf;
+#[cfg(feature = "vendored")]
+use std::fs;
+
+/// Bundled snappy version, see `snappy/NEWS` upstream
+#[cfg(feature = "vendored")]
+const SNAPPY_VERSION: (u32, u32, u32) = (1, 1, 10);
+
+fn main() {
+  println!("cargo:rustc-check-cfg=cfg(snappy_level)");
+  println!("cargo:rerun-if-changed=shim/level.cc");
+
+  #[cfg(feature = "vendored")]
+  build_vendored();
+
+  #[cfg(not(feature = "vendored"))]
+  link_system();
+}
+
+/// Find and link system libsnappy
+#[cfg(not(feature = "vendored"))]
+fn link_system() {
+  let (statik, dynamic) = (cfg!(feature = "static"), cfg!(feature = "dynamic"));
+  if statik && dynamic { panic!("features `static` and `dynamic` of snappy-sys are mutually exclusive") }
+
+  if env::var("CARGO_CFG_TARGET_ENV").unwrap() == "msvc" {
+    // vcpkg picks linkage by its triplet and VCPKGRS_DYNAMIC
+    if let Ok(library) = vcpkg::Config::new().cargo_metadata(false).find_package("snappy") {
+      build_level_shim(&library.include_paths);
+      vcpkg::Config::new().emit_includes(true).find_package("snappy").unwrap();
+      return
+    }
+  } else {
+    let mut config = pkg_config::Config::new();
+    if statik || dynamic { config.statik(statik); }
+
+    // the shim goes before libsnappy on the link line, so it is probed without emitting anything first
+    if let Ok(library) = config.clone().cargo_metadata(false).probe("snappy") {
+      build_level_shim(&library.include_paths);
+      config.probe("snappy").unwrap();
+      if statik { link_cpp_stdlib() }
+      return
+    }
+  }
+
+  // not found, hope it is in default library paths
+  build_level_shim(&[]);
+  let kind = if statik { "static=" } else if dynamic { "dylib=" } else { "" };
+  println!("cargo:rustc-link-lib={}snappy", kind);
+  if statik { link_cpp_stdlib() }
+}
+
+/// Static libsnappy needs the C++ standard library linked too
+#[cfg(not(feature = "vendored"))]
+fn link_cpp_stdlib() {
+  let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
+
+  match target_os.as_str() {
+    "macos" | "ios" | "freebsd" | "openbsd" => println!("cargo:rustc-link-lib=c++"),
+    "windows" if env::var("CARGO_CFG_TARGET_ENV").unwrap() == "msvc" => (),
+    _ => println!("cargo:rustc-link-lib=stdc++"),
+  }
+}
+
+/// Compile bundled snappy with the `cc` crate
+#[cfg(feature = "vendored")]
+fn build_vendored() {
+  let src = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap()).join("snappy");
+  let include = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("include");
+  fs::create_dir_all(&include).unwrap();
+
+  let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
+  let target_env = env::var("CARGO_CFG_TARGET_ENV").unwrap();
+  let big_endian = env::var("CARGO_CFG_TARGET_ENDIAN").unwrap() == "big";
+
+  // snappy-stubs-public.h is generated by CMake upstream
+  let (major, minor, patch) = SNAPPY_VERSION;
+  let have_sys_uio = if target_os == "windows" { "0" } else { "1" };
+  let stubs = fs::read_to_string(src.join("snappy-stubs-public.h.in")).unwrap()
+    .replace("${HAVE_SYS_UIO_H_01}", have_sys_uio)
+    .replace("${PROJECT_VERSION_MAJOR}", &major.to_string())
+    .replace("${PROJECT_VERSION_MINOR}", &minor.to_string())
+    .replace("${PROJECT_VERSION_PATCH}", &patch.to_string());
+  fs::write(include.join("snappy-stubs-public.h"), stubs).unwrap();
+
+  let mut build = cc::Build::new();
+  build.cpp(true)
+    .include(&include)
+    .include(&src)
+    .define("SNAPPY_IS_BIG_ENDIAN", if big_endian { "1" } else { "0" })
+    .warnings(false);
+
+  if target_env == "msvc" {
+    build.flag("/std:c++14").flag("/EHsc");
+  } else {
+    build.flag("-std=c++11")
+      .define("HAVE_BUILTIN_EXPECT", "1")
+      .define("HAVE_BUILTIN_CTZ", "1");
+  }
+  if env::var("PROFILE").map(|p| p == "release").unwrap_or(false) {
+    build.define("NDEBUG", None);
+  }
+
+  for file in &["snappy.cc", "snappy-sinksource.cc", "snappy-stubs-internal.cc", "snappy-c.cc"] {
+    let path = src.join(file);
+    println!("cargo:rerun-if-changed={}", path.display());
+    build.file(path);
+  }
+  // the bundled sources have CompressionOptions
+  build.file("shim/level.cc");
+  build.compile("snappy");
+  println!("cargo:rustc-cfg=snappy_level");
+
+  for header in &["snappy.h", "snappy-c.h", "snappy-sinksource.h"] {
+    fs::copy(src.join(header), include.join(header)).unwrap();
+  }
+  println!("cargo:root={}", include.parent().unwrap().display());
+  println!("cargo:include={}", include.display());
+}
+
+/// Compile `snappy_compress_level` against the system headers in `include_paths`, if they have `CompressionOptions`
+#[cfg(not(feature = "vendored"))]
+fn build_level_shim(include_paths: &[PathBuf]) {
+  let mut build = cc::Build::new();
+  build.cpp(true).file("shim/level.cc").includes(include_paths).warnings(false).cargo_warnings(false);
+  if env::var("CARGO_CFG_TARGET_ENV").unwrap() == "msvc" { build.flag("/std:c++14"); } else { build.flag("-std=c++11"); }
+
+  if build.try_compile("snappy_level").is_ok() { println!("cargo:rustc-cfg=snappy_level") }
+}
@@ -0,0 +1,16 @@
+// snappy_compress with a compression level, for libsnappy with CompressionOptions (1.2 and later snapshots)
+//
+// Only compiled when the headers found have CompressionOptions, see build.rs.
+
+#include "snappy.h"
+#include "snappy-c.h"
+
+extern "C" snappy_status snappy_compress_level(const char* input, size_t input_length, char* compressed,
+                                               size_t* compressed_length, int level) {
+  if (*compressed_length < snappy::MaxCompressedLength(input_length)) return SNAPPY_BUFFER_TOO_SMALL;
+  if (level < snappy::CompressionOptions::MinCompressionLevel()) level = snappy::CompressionOptions::MinCompressionLevel();
+  if (level > snappy::CompressionOptions::MaxCompressionLevel()) level = snappy::CompressionOptions::MaxCompressionLevel();
+
+  snappy::RawCompress(input, input_length, compressed, compressed_length, snappy::CompressionOptions(level));
+  return SNAPPY_OK;
+}
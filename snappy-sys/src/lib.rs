@@ -7,98 +7,39 @@
 // copies of the Software, and to permit persons to whom the Software is
 // furnished to do so, subject to the following conditions:
 
-//! Snappy is a compression/decompression library.
+//! Raw FFI declarations for [google/snappy](https://github.com/google/snappy)
 //!
-//! It does not aim for maximum compression, or compatibility with any other compression library
+//! Only `core` types are used in this crate, so it does not depend on `std` or `libc`.
 //!
-//! instead, it aims for very high speeds and reasonable compression.
+//! The system libsnappy is linked by default, found with pkg-config (vcpkg for MSVC),
+//! features `static` and `dynamic` choose its linkage.
+//! Enable feature `vendored` to build the bundled copy instead.
 //!
-//! GitHub repository see [google/snappy](https://github.com/google/snappy)
-//!
-//! Simple Rust binding by duangsuse
-
-#![crate_type = "rlib"]
-#![crate_name = "snappy"]
-
-#![doc(html_logo_url = "https://www.rust-lang.org/logos/rust-logo-128x128-blk-v2.png",
-  html_favicon_url = "https://doc.rust-lang.org/favicon.ico")]
-
-#![allow(unused_attributes)]
-#![feature(libc)]
+//! Safe, idiomatic binding see the `snappy` crate.
 
-extern crate libc;
-extern crate core;
+#![no_std]
 
-use core::fmt;
+use core::ffi::c_int;
 
-use libc::size_t;
-use libc::malloc;
+/// Operation succeed, no exception
+pub const SNAPPY_OK: c_int = 0;
+/// Bad input buffer given
+pub const SNAPPY_INVALID_INPUT: c_int = 1;
+/// Allocated buffer too small
+pub const SNAPPY_BUFFER_TOO_SMALL: c_int = 2;
 
-/// Return values for snappy operations
-///
-/// See the documentation for each function to know what each can return.
-#[repr(C)]
-pub enum SnappyResult {
-  /// Operation succeed, no exception
-  Ok = 0,
-  /// Bad input buffer given
-  InvalidInput = 1,
-  /// Allocated buffer too small
-  InsufficientBuffer = 2
-}
+/// Highest level `snappy_compress_level` tells apart: 2 when libsnappy has `CompressionOptions`, else 1
+#[cfg(snappy_level)]
+pub const SNAPPY_MAX_LEVEL: c_int = 2;
+/// Highest level `snappy_compress_level` tells apart: 2 when libsnappy has `CompressionOptions`, else 1
+#[cfg(not(snappy_level))]
+pub const SNAPPY_MAX_LEVEL: c_int = 1;
 
-/// `Display` implementation for `SnappyResult`
-///
-/// + SnappyResult::Ok => "Ok"
-/// + SnappyResult::InvalidInput => "Invalid Input"
-/// + SnappyResult::InsufficientBuffer => "Insufficient Buffer"
-impl fmt::Display for SnappyResult {
-  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    match self {
-      SnappyResult::Ok => f.write_str("Ok"),
-      SnappyResult::InvalidInput => f.write_str("Invalid Input"),
-      SnappyResult::InsufficientBuffer => f.write_str("Insufficient Buffer"),
-    }
-  }
-}
-
-/// Result type checker methods
-///
-/// Check if result is ok, or error kind of this result enumeration
-///
-impl SnappyResult {
-  pub fn is_ok(&self) -> bool { match self { SnappyResult::Ok => true, _ => false } }
-  pub fn not_ok(&self) -> bool { !self.is_ok() }
-  pub fn bad_input(&self) -> bool { match self { SnappyResult::InvalidInput => true, _ => false } }
-  pub fn insuff_buf(&self) -> bool { match self { SnappyResult::InsufficientBuffer => true, _ => false } }
-}
-
-/// Deflates(compress) a byte slice
-pub unsafe extern "C" fn deflate(input: *const u8, length: size_t, buffer_ptr: *mut u8) -> SnappyResult {
-  let output_len = snappy_max_compressed_length(length);
-  let buffer_ptr = malloc(output_len) as *mut u8;
-
-  snappy_compress(input, length, buffer_ptr, output_len)
-}
+/// C `size_t`, same as `usize` on every platform Rust supports
+#[allow(non_camel_case_types)]
+pub type size_t = usize;
 
-/// Inflates(uncompress) a byte slice
-pub unsafe extern "C" fn inflate(input: *const u8, length: size_t, output: *mut u8) -> SnappyResult {
-  let output_len: *mut usize = &mut 0usize;
-  let check = snappy_uncompressed_length(input, length, output_len);
-
-  if check.not_ok() { return SnappyResult::InvalidInput }
-
-  snappy_uncompress(input, length, output, *output_len)
-}
-
-
-/// Validate a byte slice
-pub unsafe extern "C" fn validate(input: *const u8, length: size_t) -> bool {
-  snappy_validate_compressed_buffer(input, length).is_ok()
-}
-
-#[link(name = "snappy")]
-extern {
+extern "C" {
   /// Takes the data stored in "input[0..input_length-1]" and stores
   /// it in the array pointed to by "compressed".
   ///
@@ -118,7 +59,7 @@ extern {
   ///   }
   ///   free(output);
   ///   ```
-  pub fn snappy_compress(input: *const u8, length: size_t, compressed: *mut u8, compressed_length: size_t) -> SnappyResult;
+  pub fn snappy_compress(input: *const u8, length: size_t, compressed: *mut u8, compressed_length: *mut size_t) -> c_int;
 
   /// Given data in "compressed[0..compressed_length-1]" generated by
   /// calling the snappy_compress routine, this routine stores
@@ -148,8 +89,7 @@ extern {
   ///   free(output);
   ///   ```
   ///
-  pub fn snappy_uncompress(input: *const u8, compressed_length: size_t, uncompressed: *mut u8, uncompressed_length: size_t) -> SnappyResult;
-
+  pub fn snappy_uncompress(input: *const u8, compressed_length: size_t, uncompressed: *mut u8, uncompressed_length: *mut size_t) -> c_int;
 
   /// Returns the maximal size of the compressed representation of
   /// input data that is "source_length" bytes in length.
@@ -161,7 +101,7 @@ extern {
   /// *result normally. Returns SNAPPY_INVALID_INPUT on parsing error.
   /// This operation takes O(1) time.
   ///
-  pub fn snappy_uncompressed_length(compressed: *const u8, compressed_length: size_t, result: *mut size_t) -> SnappyResult;
+  pub fn snappy_uncompressed_length(compressed: *const u8, compressed_length: size_t, result: *mut size_t) -> c_int;
 
   /// Check if the contents of "compressed\[\]" can be uncompressed successfully.
   /// Does not return the uncompressed data; if so, returns SNAPPY_OK,
@@ -169,6 +109,23 @@ extern {
   /// Takes time proportional to compressed_length, but is usually at least a
   /// factor of four faster than actual decompression.
   ///
-  pub fn snappy_validate_compressed_buffer(compressed: *const u8, compressed_length: size_t) -> SnappyResult;
+  pub fn snappy_validate_compressed_buffer(compressed: *const u8, compressed_length: size_t) -> c_int;
+}
+
+#[cfg(snappy_level)]
+extern "C" {
+  /// Same as `snappy_compress`, at compression `level`: 1 is the fastest, 2 a little slower and denser
+  ///
+  /// Levels out of range are clamped. Not part of libsnappy's C API, compiled from `shim/level.cc`.
+  pub fn snappy_compress_level(input: *const u8, length: size_t, compressed: *mut u8, compressed_length: *mut size_t, level: c_int) -> c_int;
 }
 
+/// Same as `snappy_compress`, `level` is ignored: this libsnappy has level 1 only
+///
+/// # Safety
+///
+/// Same as `snappy_compress`.
+#[cfg(not(snappy_level))]
+pub unsafe fn snappy_compress_level(input: *const u8, length: size_t, compressed: *mut u8, compressed_length: *mut size_t, _level: c_int) -> c_int {
+  snappy_compress(input, length, compressed, compressed_length)
+}
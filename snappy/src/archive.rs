@@ -0,0 +1,26 @@
+//! tar archives of directory trees in framed streams, `.tar.sz` files, with the `tar` feature
+
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::read::SnappyReader;
+use crate::write::SnappyWriter;
+
+/// Write a compressed tar archive of the tree at `path` to `writer`, then flush it and give it back
+///
+/// Entries are relative to `path`; symbolic links are archived as links, not followed.
+pub fn archive_dir<P: AsRef<Path>, W: Write>(path: P, writer: W) -> io::Result<W> {
+  let mut builder = tar::Builder::new(SnappyWriter::new(writer));
+  builder.follow_symlinks(false);
+  builder.append_dir_all(".", path)?;
+  let mut writer = builder.into_inner()?.finish()?;
+  writer.flush()?;
+  Ok(writer)
+}
+
+/// Extract a compressed tar archive from `reader` into the directory `path`, created if missing
+///
+/// Entries reaching outside of `path`, e.g. through `..`, are skipped, see `tar::Archive::unpack`.
+pub fn extract_archive<R: Read, P: AsRef<Path>>(reader: R, path: P) -> io::Result<()> {
+  tar::Archive::new(SnappyReader::new(reader)).unpack(path)
+}
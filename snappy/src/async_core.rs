@@ -0,0 +1,109 @@
+//! State machines of the async adapters, shared by `async_tokio` and `async_futures`
+//!
+//! Inner readers and writers are polled through closures, so each runtime module only maps its own
+//! `AsyncRead`/`AsyncWrite` on them.
+
+use std::io;
+use std::task::{ready, Context, Poll};
+
+use crate::frame::{DecoderOptions, EncoderOptions, FrameEncoder, PushDecoder};
+use crate::SnappyError;
+
+/// Encoding side of `AsyncFrameEncoder`: input buffered up to a block, encoded data waiting for the inner writer
+pub(crate) struct EncoderState {
+  /// Encodes into its `Vec`, drained to the inner writer
+  encoder: FrameEncoder<Vec<u8>>,
+  /// Length of encoded data already written
+  written: usize,
+  buffer: Vec<u8>,
+}
+
+impl EncoderState {
+  /// State with `options`, which are validated first
+  pub(crate) fn new(options: EncoderOptions) -> Result<Self, SnappyError> {
+    let encoder = FrameEncoder::with_options(Vec::new(), options)?;
+    let buffer = Vec::with_capacity(encoder.block_size());
+    Ok(EncoderState { encoder, written: 0, buffer })
+  }
+
+  /// `poll_write`: drain encoded data, then take as much of `buf` as fits in the current block
+  pub(crate) fn poll_write<P>(&mut self, cx: &mut Context<'_>, write: P, buf: &[u8]) -> Poll<io::Result<usize>>
+  where P: FnMut(&mut Context<'_>, &[u8]) -> Poll<io::Result<usize>> {
+    ready!(self.poll_drain(cx, write))?;
+
+    let block_size = self.encoder.block_size();
+    let len = buf.len().min(block_size - self.buffer.len());
+    self.buffer.extend_from_slice(&buf[..len]);
+    if self.buffer.len() == block_size { self.write_buffer()? }
+    Poll::Ready(Ok(len))
+  }
+
+  /// `poll_flush` up to the inner writer: end the current block and drain all encoded data
+  pub(crate) fn poll_flush<P>(&mut self, cx: &mut Context<'_>, write: P) -> Poll<io::Result<()>>
+  where P: FnMut(&mut Context<'_>, &[u8]) -> Poll<io::Result<usize>> {
+    self.write_buffer()?;
+    self.encoder.sync_flush()?;
+    self.poll_drain(cx, write)
+  }
+
+  /// Compress buffered input as a chunk
+  fn write_buffer(&mut self) -> io::Result<()> {
+    if !self.buffer.is_empty() {
+      self.encoder.write_data(&self.buffer)?;
+      self.buffer.clear();
+    }
+    Ok(())
+  }
+
+  /// Write all encoded data with `write`, handling partial writes
+  fn poll_drain<P>(&mut self, cx: &mut Context<'_>, mut write: P) -> Poll<io::Result<()>>
+  where P: FnMut(&mut Context<'_>, &[u8]) -> Poll<io::Result<usize>> {
+    let encoded = self.encoder.get_mut();
+    while self.written < encoded.len() {
+      let n = ready!(write(cx, &encoded[self.written..]))?;
+      if n == 0 { return Poll::Ready(Err(io::ErrorKind::WriteZero.into())) }
+      self.written += n;
+    }
+    encoded.clear();
+    self.written = 0;
+    Poll::Ready(Ok(()))
+  }
+}
+
+/// Decoding side of `AsyncFrameDecoder`, over a `PushDecoder`
+pub(crate) struct DecoderState {
+  decoder: PushDecoder,
+  /// Scratch space for reads of the inner reader
+  buffer: Vec<u8>,
+  /// Position in the current block
+  pos: usize,
+}
+
+impl DecoderState {
+  pub(crate) fn new(options: DecoderOptions) -> Self {
+    DecoderState { decoder: PushDecoder::with_options(options), buffer: vec![0; 8192], pos: 0 }
+  }
+
+  /// Decoded data not consumed yet, reading with `read` until some is there; empty at the end of stream
+  pub(crate) fn poll_fill_buf<P>(&mut self, cx: &mut Context<'_>, mut read: P) -> Poll<io::Result<&[u8]>>
+  where P: FnMut(&mut Context<'_>, &mut [u8]) -> Poll<io::Result<usize>> {
+    loop {
+      if self.pos < self.decoder.current_block().len() { return Poll::Ready(Ok(&self.decoder.current_block()[self.pos..])) }
+
+      if self.decoder.next_block()?.is_some() {
+        self.pos = 0;
+        continue
+      }
+
+      let n = ready!(read(cx, &mut self.buffer))?;
+      if n == 0 {
+        self.decoder.finish()?;
+        return Poll::Ready(Ok(&[]))
+      }
+      self.decoder.push(&self.buffer[..n]);
+    }
+  }
+
+  /// Mark `len` bytes of `poll_fill_buf` as read
+  pub(crate) fn consume(&mut self, len: usize) { self.pos += len }
+}
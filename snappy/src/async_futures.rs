@@ -0,0 +1,86 @@
+//! Framed stream adapters over futures `AsyncRead`/`AsyncWrite`, with the `futures-io` feature
+//!
+//! Runtime-agnostic counterparts of `async_tokio`, for async-std, smol and other executors.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_io::{AsyncRead, AsyncWrite};
+
+use crate::async_core::{DecoderState, EncoderState};
+use crate::frame::{DecoderOptions, EncoderOptions};
+use crate::SnappyError;
+
+/// Compressing `AsyncWrite`, output is a framed stream
+///
+/// Input is buffered until a block is full, `poll_flush` ends the current block early;
+/// `poll_close` writes what is left, then closes the inner writer.
+pub struct AsyncFrameEncoder<W> {
+  inner: W,
+  state: EncoderState,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncFrameEncoder<W> {
+  pub fn new(inner: W) -> Self { AsyncFrameEncoder::with_options(inner, EncoderOptions::new()).unwrap() }
+
+  /// Encoder with `options`, which are validated first
+  pub fn with_options(inner: W, options: EncoderOptions) -> Result<Self, SnappyError> {
+    Ok(AsyncFrameEncoder { inner, state: EncoderState::new(options)? })
+  }
+
+  pub fn get_ref(&self) -> &W { &self.inner }
+  pub fn get_mut(&mut self) -> &mut W { &mut self.inner }
+
+  /// Get back the inner writer, data not written by `poll_close` or `poll_flush` is lost
+  pub fn into_inner(self) -> W { self.inner }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncFrameEncoder<W> {
+  fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+    let this = self.get_mut();
+    let inner = &mut this.inner;
+    this.state.poll_write(cx, |cx, buf| Pin::new(&mut *inner).poll_write(cx, buf), buf)
+  }
+
+  fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    let this = self.get_mut();
+    let inner = &mut this.inner;
+    ready!(this.state.poll_flush(cx, |cx, buf| Pin::new(&mut *inner).poll_write(cx, buf)))?;
+    Pin::new(inner).poll_flush(cx)
+  }
+
+  fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    ready!(self.as_mut().poll_flush(cx))?;
+    Pin::new(&mut self.get_mut().inner).poll_close(cx)
+  }
+}
+
+/// Decompressing `AsyncRead` of a framed stream, over a `PushDecoder`
+pub struct AsyncFrameDecoder<R> {
+  inner: R,
+  state: DecoderState,
+}
+
+impl<R: AsyncRead + Unpin> AsyncFrameDecoder<R> {
+  pub fn new(inner: R) -> Self { AsyncFrameDecoder::with_options(inner, DecoderOptions::new()) }
+
+  /// Decoder with `options`
+  pub fn with_options(inner: R, options: DecoderOptions) -> Self { AsyncFrameDecoder { inner, state: DecoderState::new(options) } }
+
+  pub fn get_ref(&self) -> &R { &self.inner }
+  pub fn get_mut(&mut self) -> &mut R { &mut self.inner }
+  pub fn into_inner(self) -> R { self.inner }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncFrameDecoder<R> {
+  fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+    let this = self.get_mut();
+    let inner = &mut this.inner;
+    let block = ready!(this.state.poll_fill_buf(cx, |cx, scratch| Pin::new(&mut *inner).poll_read(cx, scratch)))?;
+    let len = block.len().min(buf.len());
+    buf[..len].copy_from_slice(&block[..len]);
+    this.state.consume(len);
+    Poll::Ready(Ok(len))
+  }
+}
@@ -0,0 +1,90 @@
+//! Framed stream adapters over tokio `AsyncRead`/`AsyncWrite`, with the `tokio` feature
+//!
+//! Compression runs inline on the polling task, one block at a time, like the blocking adapters.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::async_core::{DecoderState, EncoderState};
+use crate::frame::{DecoderOptions, EncoderOptions};
+use crate::SnappyError;
+
+/// Compressing `AsyncWrite`, output is a framed stream
+///
+/// Input is buffered until a block is full, `poll_flush` ends the current block early;
+/// `poll_shutdown` writes what is left, then shuts down the inner writer.
+pub struct AsyncFrameEncoder<W> {
+  inner: W,
+  state: EncoderState,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncFrameEncoder<W> {
+  pub fn new(inner: W) -> Self { AsyncFrameEncoder::with_options(inner, EncoderOptions::new()).unwrap() }
+
+  /// Encoder with `options`, which are validated first
+  pub fn with_options(inner: W, options: EncoderOptions) -> Result<Self, SnappyError> {
+    Ok(AsyncFrameEncoder { inner, state: EncoderState::new(options)? })
+  }
+
+  pub fn get_ref(&self) -> &W { &self.inner }
+  pub fn get_mut(&mut self) -> &mut W { &mut self.inner }
+
+  /// Get back the inner writer, data not written by `poll_shutdown` or `poll_flush` is lost
+  pub fn into_inner(self) -> W { self.inner }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncFrameEncoder<W> {
+  fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+    let this = self.get_mut();
+    let inner = &mut this.inner;
+    this.state.poll_write(cx, |cx, buf| Pin::new(&mut *inner).poll_write(cx, buf), buf)
+  }
+
+  fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    let this = self.get_mut();
+    let inner = &mut this.inner;
+    ready!(this.state.poll_flush(cx, |cx, buf| Pin::new(&mut *inner).poll_write(cx, buf)))?;
+    Pin::new(inner).poll_flush(cx)
+  }
+
+  fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    ready!(self.as_mut().poll_flush(cx))?;
+    Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+  }
+}
+
+/// Decompressing `AsyncRead` of a framed stream, over a `PushDecoder`
+pub struct AsyncFrameDecoder<R> {
+  inner: R,
+  state: DecoderState,
+}
+
+impl<R: AsyncRead + Unpin> AsyncFrameDecoder<R> {
+  pub fn new(inner: R) -> Self { AsyncFrameDecoder::with_options(inner, DecoderOptions::new()) }
+
+  /// Decoder with `options`
+  pub fn with_options(inner: R, options: DecoderOptions) -> Self { AsyncFrameDecoder { inner, state: DecoderState::new(options) } }
+
+  pub fn get_ref(&self) -> &R { &self.inner }
+  pub fn get_mut(&mut self) -> &mut R { &mut self.inner }
+  pub fn into_inner(self) -> R { self.inner }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncFrameDecoder<R> {
+  fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+    let this = self.get_mut();
+    let inner = &mut this.inner;
+    let block = ready!(this.state.poll_fill_buf(cx, |cx, scratch| {
+      let mut read = ReadBuf::new(scratch);
+      ready!(Pin::new(&mut *inner).poll_read(cx, &mut read))?;
+      Poll::Ready(Ok(read.filled().len()))
+    }))?;
+    let len = block.len().min(buf.remaining());
+    buf.put_slice(&block[..len]);
+    this.state.consume(len);
+    Poll::Ready(Ok(()))
+  }
+}
@@ -0,0 +1,38 @@
+//! Blocks of the Avro `snappy` codec: a raw block followed by the big-endian CRC-32 of the uncompressed data
+//!
+//! Avro object container files written by Java tooling compress each data block this way; the CRC is the common
+//! IEEE one of zlib, not the CRC-32C of the framing format.
+
+use crate::crc32c::make_table;
+use crate::{compress_to_spare, decompress, SnappyError};
+
+/// Reversed IEEE polynomial
+const POLY: u32 = 0xedb8_8320;
+
+/// Byte-wise lookup table
+const TABLE: [u32; 256] = make_table(POLY);
+
+/// CRC-32 of `data`, as zlib and Java's `java.util.zip.CRC32` compute it
+pub fn crc32(data: &[u8]) -> u32 {
+  !data.iter().fold(!0, |crc, &b| TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+/// Compress `input` into an Avro snappy block
+pub fn avro_compress_block(input: &[u8]) -> Vec<u8> {
+  let mut output = Vec::new();
+  compress_to_spare(input, &mut output);
+  output.extend_from_slice(&crc32(input).to_be_bytes());
+  output
+}
+
+/// Decompress an Avro snappy block, checking its CRC
+///
+/// A mismatch gives `ChecksumMismatch` of chunk index 0, a block too short for the CRC `TruncatedStream`.
+pub fn avro_decompress_block(input: &[u8]) -> Result<Vec<u8>, SnappyError> {
+  if input.len() < 4 { return Err(SnappyError::TruncatedStream { missing: (4 - input.len()) as u64 }) }
+  let (block, crc) = input.split_at(input.len() - 4);
+  let data = decompress(block)?;
+  let (expected, actual) = (u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]), crc32(&data));
+  if expected != actual { return Err(SnappyError::ChecksumMismatch { chunk_index: 0, expected, actual }) }
+  Ok(data)
+}
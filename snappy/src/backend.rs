@@ -0,0 +1,110 @@
+//! Raw snappy implementations behind one trait, to pick one per instance at runtime
+//!
+//! The crate functions use the backend chosen at compile time, `default_backend`; `Compressor` and `Decompressor`
+//! take any other, e.g. `PureRust` when libsnappy is unfit.
+
+use core::fmt;
+use core::mem::MaybeUninit;
+
+use alloc::vec::Vec;
+
+use crate::SnappyError;
+
+/// Raw block format compression and decompression
+///
+/// Output buffers are `MaybeUninit`, implementations must only write into them.
+///
+/// # Safety
+///
+/// The length returned by `compress` and `decompress` must be at most `output.len()`, and the first that many bytes
+/// of `output` must be initialized: callers set it as the length of a `Vec` without checking.
+pub unsafe trait Backend: fmt::Debug + Send + Sync {
+  /// Max length of the compressed form of `len` bytes
+  fn max_compressed_len(&self, len: usize) -> usize;
+
+  /// Compress `input` into `output`, which must hold `max_compressed_len` bytes, returns the compressed length
+  fn compress(&self, input: &[u8], output: &mut [MaybeUninit<u8>]) -> Result<usize, SnappyError>;
+
+  /// Uncompressed length stored in the header of compressed `input`
+  fn uncompressed_len(&self, input: &[u8]) -> Result<usize, SnappyError>;
+
+  /// Decompress `input` into `output`, which must hold `uncompressed_len` bytes, returns the uncompressed length
+  fn decompress(&self, input: &[u8], output: &mut [MaybeUninit<u8>]) -> Result<usize, SnappyError>;
+
+  /// Whether `input` decompresses without error
+  fn validate(&self, input: &[u8]) -> bool;
+
+  /// Compress `input` into a new `Vec`
+  fn compress_vec(&self, input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(self.max_compressed_len(input.len()));
+    let len = self.compress(input, output.spare_capacity_mut()).expect("buffer of max compressed length is always enough");
+    unsafe { output.set_len(len) }
+    output
+  }
+
+  /// Decompress `input` into a new `Vec`
+  fn decompress_vec(&self, input: &[u8]) -> Result<Vec<u8>, SnappyError> {
+    let mut output = Vec::with_capacity(self.uncompressed_len(input)?);
+    let len = self.decompress(input, output.spare_capacity_mut())?;
+    unsafe { output.set_len(len) }
+    Ok(output)
+  }
+}
+
+/// Backend of the crate functions: `PureRust` with the `pure-rust` feature or on wasm32, else `LibSnappy`
+pub fn default_backend() -> &'static dyn Backend {
+  #[cfg(any(feature = "pure-rust", target_arch = "wasm32"))]
+  return &PureRust;
+  #[cfg(not(any(feature = "pure-rust", target_arch = "wasm32")))]
+  return &LibSnappy;
+}
+
+/// libsnappy linked at build time, with the `libsnappy` feature except on wasm32
+#[cfg(all(feature = "libsnappy", not(target_arch = "wasm32")))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LibSnappy;
+
+#[cfg(all(feature = "libsnappy", not(target_arch = "wasm32")))]
+// libsnappy writes `output_len` bytes and errs when they do not fit
+unsafe impl Backend for LibSnappy {
+  fn max_compressed_len(&self, len: usize) -> usize { unsafe { crate::raw::snappy_max_compressed_length(len) } }
+
+  fn compress(&self, input: &[u8], output: &mut [MaybeUninit<u8>]) -> Result<usize, SnappyError> {
+    let mut output_len = output.len();
+    unsafe { crate::status(crate::raw::snappy_compress(input.as_ptr(), input.len(), output.as_mut_ptr() as *mut u8, &mut output_len)) }?;
+    Ok(output_len)
+  }
+
+  fn uncompressed_len(&self, input: &[u8]) -> Result<usize, SnappyError> {
+    let mut len = 0;
+    unsafe { crate::status(crate::raw::snappy_uncompressed_length(input.as_ptr(), input.len(), &mut len)) }?;
+    Ok(len)
+  }
+
+  fn decompress(&self, input: &[u8], output: &mut [MaybeUninit<u8>]) -> Result<usize, SnappyError> {
+    // checked here too, so the `Backend` output contract does not rest on the C side alone
+    if self.uncompressed_len(input)? > output.len() { return Err(SnappyError::InsufficientBuffer) }
+    let mut output_len = output.len();
+    unsafe { crate::status(crate::raw::snappy_uncompress(input.as_ptr(), input.len(), output.as_mut_ptr() as *mut u8, &mut output_len)) }?;
+    Ok(output_len)
+  }
+
+  fn validate(&self, input: &[u8]) -> bool {
+    unsafe { crate::raw::snappy_validate_compressed_buffer(input.as_ptr(), input.len()) == crate::raw::SNAPPY_OK }
+  }
+}
+
+/// Rust implementation of `pure`, with the `pure-rust` feature or on wasm32
+#[cfg(any(feature = "pure-rust", target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PureRust;
+
+#[cfg(any(feature = "pure-rust", target_arch = "wasm32"))]
+// `pure` returns the length it wrote, checked against `output` before writing
+unsafe impl Backend for PureRust {
+  fn max_compressed_len(&self, len: usize) -> usize { crate::pure::max_compressed_length(len) }
+  fn compress(&self, input: &[u8], output: &mut [MaybeUninit<u8>]) -> Result<usize, SnappyError> { crate::pure::compress(input, output) }
+  fn uncompressed_len(&self, input: &[u8]) -> Result<usize, SnappyError> { crate::pure::uncompressed_length(input) }
+  fn decompress(&self, input: &[u8], output: &mut [MaybeUninit<u8>]) -> Result<usize, SnappyError> { crate::pure::decompress(input, output) }
+  fn validate(&self, input: &[u8]) -> bool { crate::pure::validate(input) }
+}
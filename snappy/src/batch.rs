@@ -0,0 +1,48 @@
+//! Compression of many independent records on all cores, with the `rayon` feature
+//!
+//! `compress_all` and `decompress_all` take a whole batch; `ParallelSnappyExt` adds the same to any parallel iterator
+//! of records. Each rayon job compresses into a scratch `Compressor` of its own, so outputs are allocated at their
+//! exact length; decompressed lengths are known up front, no scratch is needed for them.
+
+use rayon::iter::{Map, MapInit, ParallelIterator};
+use rayon::prelude::*;
+
+use crate::compressor::Compressor;
+use crate::{decompress, SnappyError};
+
+/// Raw blocks of `records`, in order, compressed on the rayon thread pool
+pub fn compress_all<T: AsRef<[u8]> + Sync>(records: &[T]) -> Vec<Vec<u8>> { records.par_iter().snappy_compress().collect() }
+
+/// Data of raw blocks `records`, in order, decompressed on the rayon thread pool; the first error of any
+pub fn decompress_all<T: AsRef<[u8]> + Sync>(records: &[T]) -> Result<Vec<Vec<u8>>, SnappyError> {
+  records.par_iter().snappy_decompress().collect()
+}
+
+/// Parallel iterator of `ParallelSnappyExt::snappy_compress`
+pub type CompressRecords<I> = MapInit<I, fn() -> Compressor, fn(&mut Compressor, <I as ParallelIterator>::Item) -> Vec<u8>>;
+
+/// Parallel iterator of `ParallelSnappyExt::snappy_decompress`
+pub type DecompressRecords<I> = Map<I, fn(<I as ParallelIterator>::Item) -> Result<Vec<u8>, SnappyError>>;
+
+/// Raw snappy methods on parallel iterators of records, see `SnappyExt` for one record
+pub trait ParallelSnappyExt: ParallelIterator where Self::Item: AsRef<[u8]> {
+  /// Raw block of each record
+  fn snappy_compress(self) -> CompressRecords<Self>;
+
+  /// Data of each raw block record
+  fn snappy_decompress(self) -> DecompressRecords<Self>;
+}
+
+impl<I: ParallelIterator> ParallelSnappyExt for I where I::Item: AsRef<[u8]> {
+  fn snappy_compress(self) -> CompressRecords<Self> {
+    self.map_init(Compressor::new as fn() -> Compressor, compress_record::<I::Item> as fn(&mut Compressor, I::Item) -> Vec<u8>)
+  }
+
+  fn snappy_decompress(self) -> DecompressRecords<Self> {
+    self.map(decompress_record::<I::Item> as fn(I::Item) -> Result<Vec<u8>, SnappyError>)
+  }
+}
+
+fn compress_record<T: AsRef<[u8]>>(compressor: &mut Compressor, record: T) -> Vec<u8> { compressor.compress(record.as_ref()).to_vec() }
+
+fn decompress_record<T: AsRef<[u8]>>(record: T) -> Result<Vec<u8>, SnappyError> { decompress(record.as_ref()) }
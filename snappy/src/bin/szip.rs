@@ -0,0 +1,234 @@
+//! `szip`, command line compressor of the snappy formats, with the `cli` feature
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use snappy::frame::{is_skippable, Frames, CHUNK_COMPRESSED, CHUNK_PADDING, CHUNK_STREAM_IDENTIFIER, CHUNK_UNCOMPRESSED, STREAM_IDENTIFIER};
+use snappy::xerial::MAGIC;
+use snappy::{compress_dir, decompress_auto, DirOptions, validate_frames, Format, HadoopReader, HadoopWriter, SnappyError, SnappyReader, SnappyWriter, XerialReader, XerialWriter};
+
+const USAGE: &str = "\
+usage: szip <command> [--format raw|framed|hadoop|java] [files]
+       szip compress -r [--include glob] [--exclude glob] [--remove] [directories]
+
+commands:
+  compress    compress each file to file.sz (.snappy for hadoop and java, .raw for raw)
+  decompress  decompress each file, removing its suffix
+  cat         decompress files to stdout
+  validate    check files are well-formed, without writing anything
+  list        print the chunks of framed files: offset, type, lengths and checksum status
+
+Files are kept. With no files, stdin is read and stdout written.
+With -r, each file in the directory trees is compressed to file.sz on several threads; globs match file names,
+excludes skip directories too, and --remove deletes each original once its .sz is checked.
+Decompression detects the format unless --format is given; compression defaults to framed.
+";
+
+fn main() {
+  let mut args = std::env::args().skip(1);
+  let command = args.next().unwrap_or_default();
+  let mut format = None;
+  let mut files = Vec::new();
+  let (mut recursive, mut dir_options) = (false, DirOptions::new());
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--format" | "-f" => format = Some(args.next().as_deref().and_then(parse_format).unwrap_or_else(|| usage())),
+      "--recursive" | "-r" => recursive = true,
+      "--include" => dir_options = dir_options.include(&args.next().unwrap_or_else(|| usage())),
+      "--exclude" => dir_options = dir_options.exclude(&args.next().unwrap_or_else(|| usage())),
+      "--remove" => dir_options = dir_options.remove_originals(true),
+      "--help" | "-h" => { print!("{}", USAGE); return },
+      _ => files.push(PathBuf::from(arg)),
+    }
+  }
+
+  if recursive {
+    if command != "compress" || format.is_some_and(|format| format != Format::Framed) || files.is_empty() { usage() }
+    if !compress_dirs(&files, &dir_options) { exit(1) }
+    return
+  }
+
+  let result = match command.as_str() {
+    "compress" => run(&files, |input, output| compress(format.unwrap_or(Format::Framed), input, output), |path| {
+      with_suffix(path, suffix(format.unwrap_or(Format::Framed)))
+    }),
+    "decompress" => run(&files, |input, output| decompress(format, input, output), without_suffix),
+    "cat" => each(&files, |input| decompress(format, input, &mut io::stdout().lock())),
+    "validate" => each(&files, |input| validate(format, input)),
+    "list" => each(&files, list),
+    _ => usage(),
+  };
+  if !result { exit(1) }
+}
+
+fn usage() -> ! {
+  eprint!("{}", USAGE);
+  exit(2)
+}
+
+fn parse_format(name: &str) -> Option<Format> {
+  match name {
+    "raw" => Some(Format::Raw),
+    "framed" => Some(Format::Framed),
+    "hadoop" => Some(Format::Hadoop),
+    "java" => Some(Format::Xerial),
+    _ => None,
+  }
+}
+
+fn suffix(format: Format) -> &'static str {
+  match format {
+    Format::Framed => "sz",
+    Format::Hadoop | Format::Xerial => "snappy",
+    Format::Raw => "raw",
+  }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> Option<PathBuf> {
+  let mut name = path.as_os_str().to_owned();
+  name.push(".");
+  name.push(suffix);
+  Some(name.into())
+}
+
+fn without_suffix(path: &Path) -> Option<PathBuf> {
+  match path.extension()?.to_str()? {
+    "sz" | "snappy" | "raw" => Some(path.with_extension("")),
+    _ => None,
+  }
+}
+
+/// Run `f` from each file to the file `output_path` names for it, or from stdin to stdout; `false` on any error
+fn run(files: &[PathBuf], f: impl Fn(&mut dyn Read, &mut dyn Write) -> io::Result<()>, output_path: impl Fn(&Path) -> Option<PathBuf>) -> bool {
+  if files.is_empty() { return report("-", f(&mut io::stdin().lock(), &mut io::stdout().lock())) }
+
+  let failed = files.iter().filter(|path| {
+    let output = match output_path(path) {
+      Some(output) => output,
+      None => { eprintln!("szip: {}: unknown suffix", path.display()); return true },
+    };
+    let result = File::open(path).and_then(|input| {
+      let mut writer = BufWriter::new(File::create(&output)?);
+      f(&mut BufReader::new(input), &mut writer)?;
+      writer.flush()
+    });
+    !report(&path.display().to_string(), result)
+  });
+  failed.count() == 0
+}
+
+/// Run `f` on each file, or on stdin; `false` on any error
+fn each(files: &[PathBuf], f: impl Fn(&mut dyn Read) -> io::Result<()>) -> bool {
+  if files.is_empty() { return report("-", f(&mut io::stdin().lock())) }
+  let failed = files.iter().filter(|path| !report(&path.display().to_string(), File::open(path).and_then(|input| f(&mut BufReader::new(input)))));
+  failed.count() == 0
+}
+
+fn report(name: &str, result: io::Result<()>) -> bool {
+  match result {
+    Ok(()) => true,
+    Err(e) => { eprintln!("szip: {}: {}", name, e); false },
+  }
+}
+
+/// Compress the trees of `dirs`, reporting each failed file; `false` on any error
+fn compress_dirs(dirs: &[PathBuf], options: &DirOptions) -> bool {
+  let reported = dirs.iter().flat_map(|dir| compress_dir(dir, options));
+  let failed = reported.map(|(path, result)| report(&path.display().to_string(), result.map(drop))).filter(|ok| !ok);
+  failed.count() == 0
+}
+
+fn compress(format: Format, input: &mut dyn Read, output: &mut dyn Write) -> io::Result<()> {
+  match format {
+    Format::Raw => {
+      let mut data = Vec::new();
+      input.read_to_end(&mut data)?;
+      output.write_all(&snappy::compress(&data))
+    },
+    Format::Framed => {
+      let mut writer = SnappyWriter::new(&mut *output);
+      io::copy(input, &mut writer)?;
+      writer.finish()?.flush()
+    },
+    Format::Hadoop => {
+      let mut writer = HadoopWriter::new(&mut *output);
+      io::copy(input, &mut writer)?;
+      writer.finish().map(drop)
+    },
+    Format::Xerial => {
+      let mut writer = XerialWriter::new(&mut *output);
+      io::copy(input, &mut writer)?;
+      writer.finish().map(drop)
+    },
+  }
+}
+
+/// Decompress `input` of `format`, or of the detected format
+///
+/// Framed and java streams are told by their first bytes and decompressed as they come;
+/// detecting the others takes the whole input in memory.
+fn decompress(format: Option<Format>, input: &mut dyn Read, output: &mut dyn Write) -> io::Result<()> {
+  let mut head = [0u8; 8];
+  let head_len = if format.is_none() { read_head(input, &mut head)? } else { 0 };
+  let mut input = (&head[..head_len]).chain(input);
+  let format = format.or_else(|| {
+    if head.starts_with(&STREAM_IDENTIFIER[..8]) { Some(Format::Framed) } else if head.starts_with(MAGIC) { Some(Format::Xerial) } else { None }
+  });
+
+  match format {
+    Some(Format::Raw) | None => {
+      let mut data = Vec::new();
+      input.read_to_end(&mut data)?;
+      let data = if format.is_some() { snappy::decompress(&data) } else { decompress_auto(&data) };
+      output.write_all(&data?)
+    },
+    Some(Format::Framed) => io::copy(&mut SnappyReader::new(input), output).map(drop),
+    Some(Format::Hadoop) => io::copy(&mut HadoopReader::new(input), output).map(drop),
+    Some(Format::Xerial) => io::copy(&mut XerialReader::new(input), output).map(drop),
+  }
+}
+
+fn validate(format: Option<Format>, input: &mut dyn Read) -> io::Result<()> {
+  if format == Some(Format::Framed) { return validate_frames(input).map(drop) }
+  decompress(format, input, &mut io::sink())
+}
+
+/// Print a line per chunk of framed `input`, stopping at the first bad chunk
+fn list(input: &mut dyn Read) -> io::Result<()> {
+  let mut stdout = io::stdout().lock();
+  writeln!(stdout, "{:>12}  {:<18}  {:>10}  {:>12}  checksum", "offset", "type", "compressed", "uncompressed")?;
+  for frame in Frames::new(input) {
+    let frame = frame?;
+    let kind = match frame.kind {
+      CHUNK_STREAM_IDENTIFIER => "stream identifier".to_string(),
+      CHUNK_COMPRESSED => "compressed".to_string(),
+      CHUNK_UNCOMPRESSED => "uncompressed".to_string(),
+      CHUNK_PADDING => "padding".to_string(),
+      kind if is_skippable(kind) => format!("skippable {:#04x}", kind),
+      kind => format!("unknown {:#04x}", kind),
+    };
+    let uncompressed = frame.uncompressed_len.map_or("-".to_string(), |len| len.to_string());
+    let checksum = match frame.decompress() {
+      Ok(_) => "ok",
+      Err(SnappyError::ChecksumMismatch { .. }) => "mismatch",
+      Err(_) if frame.is_data() => "corrupted",
+      Err(_) => "-",
+    };
+    writeln!(stdout, "{:>12}  {:<18}  {:>10}  {:>12}  {}", frame.offset, kind, frame.compressed_len, uncompressed, checksum)?;
+  }
+  Ok(())
+}
+
+/// Read up to `head.len()` bytes, for format detection
+fn read_head(input: &mut dyn Read, head: &mut [u8]) -> io::Result<usize> {
+  let mut len = 0;
+  while len < head.len() {
+    match input.read(&mut head[len..])? {
+      0 => break,
+      n => len += n,
+    }
+  }
+  Ok(len)
+}
@@ -0,0 +1,50 @@
+//! Block compression algorithm under the framing layer
+//!
+//! Frame encoders and decoders, their parallel and async forms, and `Frames` cut data into blocks and wrap each in a
+//! checksummed chunk; a `Codec` given to `EncoderOptions::codec` and `DecoderOptions::codec` compresses the blocks
+//! instead of snappy, e.g. with lz4 or zstd for a custom format. Streams keep the snappy stream identifier and chunk
+//! types, so only decoders given the same codec read them.
+
+use core::fmt;
+
+use crate::{compress_into, decompress_into, max_compress_len, SnappyError};
+
+/// Block compression and decompression, of at most `MAX_BLOCK_SIZE` bytes of data each
+pub trait Codec: fmt::Debug + Send + Sync {
+  /// Max compressed length of `len` bytes
+  ///
+  /// Decoders refuse compressed chunks over `max_compress_len(MAX_BLOCK_SIZE)` of snappy, which lz4 and zstd stay under.
+  fn max_len(&self, len: usize) -> usize;
+
+  /// Compress `input` into `output`, which holds `max_len` bytes, returns the compressed length
+  fn compress_block(&self, input: &[u8], output: &mut [u8]) -> Result<usize, SnappyError>;
+
+  /// Decompress `input` into `output`, returns the decompressed length
+  ///
+  /// Data longer than `output` must give `InsufficientBuffer`, corrupted input any other error.
+  fn decompress_block(&self, input: &[u8], output: &mut [u8]) -> Result<usize, SnappyError>;
+}
+
+/// The raw snappy block format, codec of the streams when none is given
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Snappy;
+
+impl Codec for Snappy {
+  fn max_len(&self, len: usize) -> usize { max_compress_len(len) }
+  fn compress_block(&self, input: &[u8], output: &mut [u8]) -> Result<usize, SnappyError> { compress_into(input, output) }
+  fn decompress_block(&self, input: &[u8], output: &mut [u8]) -> Result<usize, SnappyError> { decompress_into(input, output) }
+}
+
+/// Codec of options, equal to the same codec instance only
+#[derive(Clone, Copy)]
+pub(crate) struct CodecRef(pub(crate) &'static dyn Codec);
+
+impl fmt::Debug for CodecRef {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { self.0.fmt(f) }
+}
+
+impl PartialEq for CodecRef {
+  fn eq(&self, other: &CodecRef) -> bool { core::ptr::addr_eq(self.0, other.0) }
+}
+
+impl Eq for CodecRef {}
@@ -0,0 +1,82 @@
+//! Compression over `bytes` buffers, with the `bytes` feature
+//!
+//! Output is written straight into the spare capacity of `BufMut`/`BytesMut`, never zero-initialized first.
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::{snappy_compress, snappy_max_compressed_length, snappy_uncompress, snappy_uncompressed_length, status, SnappyError};
+
+/// Compress all the remaining data of `input`, which is consumed
+///
+/// Data split over several chunks of `Buf` is gathered first.
+pub fn compress_buf<B: Buf>(mut input: B) -> Bytes {
+  let mut output = BytesMut::new();
+  let data = input.copy_to_bytes(input.remaining());
+  compress_into_bytes_mut(&data, &mut output);
+  output.freeze()
+}
+
+/// Decompress all the remaining data of `input`, which is consumed
+pub fn decompress_buf<B: Buf>(mut input: B) -> Result<Bytes, SnappyError> {
+  let mut output = BytesMut::new();
+  let data = input.copy_to_bytes(input.remaining());
+  decompress_into_bytes_mut(&data, &mut output)?;
+  Ok(output.freeze())
+}
+
+/// Compress `input` appended to `output`, growing it as needed; returns the compressed length
+pub fn compress_into_bytes_mut(input: &[u8], output: &mut BytesMut) -> usize {
+  output.reserve(unsafe { snappy_max_compressed_length(input.len()) });
+  compress_to_buf(input, output)
+}
+
+/// Decompress `input` appended to `output`, growing it as needed; returns the decompressed length
+pub fn decompress_into_bytes_mut(input: &[u8], output: &mut BytesMut) -> Result<usize, SnappyError> {
+  let mut len = 0;
+  unsafe { status(snappy_uncompressed_length(input.as_ptr(), input.len(), &mut len)) }?;
+  output.reserve(len);
+  decompress_to_buf(input, output)
+}
+
+/// Compress `input` into `output`, returns the compressed length
+///
+/// Written in place when the current chunk of `output` holds `snappy_max_compressed_length`, else through a `Vec`.
+pub fn compress_to_buf<B: BufMut>(input: &[u8], output: &mut B) -> usize {
+  let max_len = unsafe { snappy_max_compressed_length(input.len()) };
+  let chunk = output.chunk_mut();
+  if chunk.len() < max_len {
+    let compressed = crate::compress(input);
+    output.put_slice(&compressed);
+    return compressed.len()
+  }
+
+  let mut len = chunk.len();
+  unsafe {
+    let result = status(snappy_compress(input.as_ptr(), input.len(), chunk.as_mut_ptr(), &mut len));
+    debug_assert!(result.is_ok(), "buffer of max compressed length is always enough");
+    output.advance_mut(len);
+  }
+  len
+}
+
+/// Decompress `input` into `output`, returns the decompressed length
+///
+/// Written in place when the current chunk of `output` is long enough, else through a `Vec`.
+pub fn decompress_to_buf<B: BufMut>(input: &[u8], output: &mut B) -> Result<usize, SnappyError> {
+  let mut len = 0;
+  unsafe { status(snappy_uncompressed_length(input.as_ptr(), input.len(), &mut len)) }?;
+  if output.remaining_mut() < len { return Err(SnappyError::InsufficientBuffer) }
+
+  let chunk = output.chunk_mut();
+  if chunk.len() < len {
+    let data = crate::decompress(input)?;
+    output.put_slice(&data);
+    return Ok(data.len())
+  }
+
+  unsafe {
+    status(snappy_uncompress(input.as_ptr(), input.len(), chunk.as_mut_ptr(), &mut len))?;
+    output.advance_mut(len);
+  }
+  Ok(len)
+}
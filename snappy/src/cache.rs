@@ -0,0 +1,118 @@
+//! Least recently used cache keeping its values compressed
+//!
+//! `CompressedLru` compresses each value on insert and decompresses it on get; its budget is of memory taken by the
+//! compressed values, so it holds several times more data than a plain cache of the same size when values compress.
+
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+use crate::{compress, decompress};
+
+/// Cache of byte values by `K`, evicting the least recently used once compressed values are over a byte budget
+///
+/// Sizes count value bytes only, not keys or bookkeeping.
+#[derive(Debug, Clone)]
+pub struct CompressedLru<K> {
+  entries: HashMap<K, Entry>,
+  /// Keys by last use, oldest first
+  order: BTreeMap<u64, K>,
+  /// Use count so far, orders uses
+  tick: u64,
+  budget: usize,
+  logical_size: usize,
+  physical_size: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+  data: Vec<u8>,
+  len: usize,
+  /// Last use, key in `order`
+  tick: u64,
+}
+
+impl<K: Hash + Eq + Clone> CompressedLru<K> {
+  /// Cache holding up to `budget` bytes of compressed values
+  pub fn new(budget: usize) -> Self {
+    CompressedLru { entries: HashMap::new(), order: BTreeMap::new(), tick: 0, budget, logical_size: 0, physical_size: 0 }
+  }
+
+  /// Compress and store `value` under `key`, replacing any value before, then evict down to budget
+  ///
+  /// A value compressing over the whole budget is not stored, and the one before is removed all the same.
+  pub fn insert(&mut self, key: K, value: &[u8]) {
+    self.take(&key);
+    let mut data = compress(value);
+    if data.len() > self.budget { return }
+    data.shrink_to_fit();
+
+    self.logical_size += value.len();
+    self.physical_size += data.len();
+    self.tick += 1;
+    self.order.insert(self.tick, key.clone());
+    self.entries.insert(key, Entry { data, len: value.len(), tick: self.tick });
+    self.evict(self.budget);
+  }
+
+  /// Decompressed value of `key`, which becomes the most recently used
+  pub fn get<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<Vec<u8>> where K: Borrow<Q> {
+    let entry = self.entries.get_mut(key)?;
+    self.tick += 1;
+    let key = self.order.remove(&entry.tick).expect("every entry is in use order");
+    self.order.insert(self.tick, key);
+    entry.tick = self.tick;
+    Some(decompress(&entry.data).expect("values are compressed by the cache itself"))
+  }
+
+  /// Remove `key`, returns its decompressed value
+  pub fn remove<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<Vec<u8>> where K: Borrow<Q> {
+    let entry = self.take(key)?;
+    Some(decompress(&entry.data).expect("values are compressed by the cache itself"))
+  }
+
+  pub fn contains_key<Q: Hash + Eq + ?Sized>(&self, key: &Q) -> bool where K: Borrow<Q> { self.entries.contains_key(key) }
+
+  /// Number of values held
+  pub fn len(&self) -> usize { self.entries.len() }
+
+  pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+  /// Length of the values held, uncompressed
+  pub fn logical_size(&self) -> usize { self.logical_size }
+
+  /// Length of the values held, compressed, at most the budget
+  pub fn physical_size(&self) -> usize { self.physical_size }
+
+  pub fn budget(&self) -> usize { self.budget }
+
+  /// Change the budget, evicting down to it
+  pub fn set_budget(&mut self, budget: usize) {
+    self.budget = budget;
+    self.evict(budget);
+  }
+
+  pub fn clear(&mut self) {
+    self.entries.clear();
+    self.order.clear();
+    self.logical_size = 0;
+    self.physical_size = 0;
+  }
+
+  /// Remove least recently used values until compressed ones take at most `budget` bytes
+  fn evict(&mut self, budget: usize) {
+    while self.physical_size > budget {
+      let key = self.order.values().next().expect("values over budget are in use order").clone();
+      self.take(&key).expect("every key in use order has an entry");
+    }
+  }
+
+  /// Remove the entry of `key` from entries, use order and sizes, still compressed
+  fn take<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<Entry> where K: Borrow<Q> {
+    let entry = self.entries.remove(key)?;
+    self.order.remove(&entry.tick);
+    self.logical_size -= entry.len;
+    self.physical_size -= entry.data.len();
+    Some(entry)
+  }
+}
@@ -0,0 +1,65 @@
+//! Channels between threads holding their messages compressed while queued
+//!
+//! For pipelines buffering many large messages between stages: `compressed_channel` is a bounded `mpsc` channel
+//! whose sender compresses each payload on the sending thread and whose receiver decompresses it on the receiving one.
+
+use std::sync::mpsc::{self, Receiver, RecvError, RecvTimeoutError, SendError, SyncSender, TryRecvError, TrySendError};
+use std::time::Duration;
+
+use crate::{compress, decompress};
+
+/// Sender and receiver of a channel queueing up to `capacity` compressed messages, see `mpsc::sync_channel`
+pub fn compressed_channel(capacity: usize) -> (CompressedSender, CompressedReceiver) {
+  let (sender, receiver) = mpsc::sync_channel(capacity);
+  (CompressedSender { inner: sender }, CompressedReceiver { inner: receiver })
+}
+
+/// Sending half of `compressed_channel`, cloned for each producer
+///
+/// Errors are those of `SyncSender`, without the payload, which the caller still has.
+#[derive(Debug, Clone)]
+pub struct CompressedSender {
+  inner: SyncSender<Vec<u8>>,
+}
+
+impl CompressedSender {
+  /// Compress and queue `payload`, waiting while the channel is full; fails once the receiver is dropped
+  pub fn send(&self, payload: &[u8]) -> Result<(), SendError<()>> {
+    self.inner.send(compress(payload)).map_err(|_| SendError(()))
+  }
+
+  /// `send` failing rather than waiting when the channel is full
+  pub fn try_send(&self, payload: &[u8]) -> Result<(), TrySendError<()>> {
+    self.inner.try_send(compress(payload)).map_err(|e| match e {
+      TrySendError::Full(_) => TrySendError::Full(()),
+      TrySendError::Disconnected(_) => TrySendError::Disconnected(()),
+    })
+  }
+}
+
+/// Receiving half of `compressed_channel`
+#[derive(Debug)]
+pub struct CompressedReceiver {
+  inner: Receiver<Vec<u8>>,
+}
+
+impl CompressedReceiver {
+  /// Next payload, decompressed, waiting for one; fails once the channel is empty and every sender dropped
+  pub fn recv(&self) -> Result<Vec<u8>, RecvError> { self.inner.recv().map(unpack) }
+
+  pub fn try_recv(&self) -> Result<Vec<u8>, TryRecvError> { self.inner.try_recv().map(unpack) }
+
+  pub fn recv_timeout(&self, timeout: Duration) -> Result<Vec<u8>, RecvTimeoutError> { self.inner.recv_timeout(timeout).map(unpack) }
+
+  /// Payloads until every sender is dropped
+  pub fn iter(&self) -> impl Iterator<Item = Vec<u8>> + '_ { self.inner.iter().map(unpack) }
+}
+
+impl IntoIterator for CompressedReceiver {
+  type Item = Vec<u8>;
+  type IntoIter = std::iter::Map<mpsc::IntoIter<Vec<u8>>, fn(Vec<u8>) -> Vec<u8>>;
+
+  fn into_iter(self) -> Self::IntoIter { self.inner.into_iter().map(unpack as fn(Vec<u8>) -> Vec<u8>) }
+}
+
+fn unpack(compressed: Vec<u8>) -> Vec<u8> { decompress(&compressed).expect("payloads are compressed by the sender") }
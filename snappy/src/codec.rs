@@ -0,0 +1,84 @@
+//! tokio_util codec of length-prefixed compressed messages, with the `codec` feature
+//!
+//! Same wire format as `message`: a LEB128 varint of compressed length, then a raw snappy block.
+//! `Framed<TcpStream, SnappyCodec>` makes a compressed message transport.
+
+use std::io;
+
+use bytes::{Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::frame::invalid;
+use crate::message::{encode_varint, DEFAULT_MAX_MESSAGE_LEN};
+use crate::{compress_into, decompress_with_limit, snappy_max_compressed_length, SnappyError};
+
+/// Codec compressing each message on its own, see `message`
+#[derive(Debug, Clone, Copy)]
+pub struct SnappyCodec {
+  max_len: usize,
+  /// Length of the stream decoded so far
+  offset: u64,
+}
+
+impl SnappyCodec {
+  pub fn new() -> Self { SnappyCodec::with_max_len(DEFAULT_MAX_MESSAGE_LEN) }
+
+  /// Codec rejecting messages with compressed or decompressed length over `max_len`, checked before allocating
+  pub fn with_max_len(max_len: usize) -> Self { SnappyCodec { max_len, offset: 0 } }
+}
+
+impl Default for SnappyCodec {
+  fn default() -> Self { SnappyCodec::new() }
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for SnappyCodec {
+  type Error = io::Error;
+
+  fn encode(&mut self, message: T, dst: &mut BytesMut) -> io::Result<()> {
+    let message = message.as_ref();
+    if message.len() > self.max_len { return Err(invalid(SnappyError::OutputLimitExceeded)) }
+    let max_len = unsafe { snappy_max_compressed_length(message.len()) };
+    let mut prefix = [0u8; 10];
+
+    let start = dst.len();
+    dst.resize(start + prefix.len() + max_len, 0);
+    let len = compress_into(message, &mut dst[start + prefix.len()..]).expect("buffer of max compressed length is always enough");
+    if len > self.max_len { dst.truncate(start); return Err(invalid(SnappyError::OutputLimitExceeded)) }
+
+    // move the compressed data right after its actual prefix
+    let prefix_len = encode_varint(len as u64, &mut prefix);
+    dst[start..start + prefix_len].copy_from_slice(&prefix[..prefix_len]);
+    dst.copy_within(start + prefix.len()..start + prefix.len() + len, start + prefix_len);
+    dst.truncate(start + prefix_len + len);
+    Ok(())
+  }
+}
+
+impl Decoder for SnappyCodec {
+  type Item = Bytes;
+  type Error = io::Error;
+
+  fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<Bytes>> {
+    let (len, prefix_len) = match decode_varint(src, self.offset).map_err(invalid)? { Some(varint) => varint, None => return Ok(None) };
+    if len > self.max_len as u64 { return Err(invalid(SnappyError::BadChunkHeader { offset: self.offset })) }
+
+    let end = prefix_len + len as usize;
+    if src.len() < end {
+      src.reserve(end - src.len());
+      return Ok(None)
+    }
+    let message = src.split_to(end);
+    self.offset += end as u64;
+    decompress_with_limit(&message[prefix_len..], self.max_len).map(Bytes::from).map(Some).map_err(invalid)
+  }
+}
+
+/// LEB128 value at the start of `buf`, at `offset` of the stream, and its length, `None` if not whole yet
+fn decode_varint(buf: &[u8], offset: u64) -> Result<Option<(u64, usize)>, SnappyError> {
+  let mut value = 0u64;
+  for (i, &byte) in buf.iter().take(10).enumerate() {
+    value |= u64::from(byte & 0x7f) << (7 * i);
+    if byte & 0x80 == 0 { return Ok(Some((value, i + 1))) }
+  }
+  if buf.len() >= 10 { Err(SnappyError::BadChunkHeader { offset }) } else { Ok(None) }
+}
@@ -0,0 +1,78 @@
+//! Raw compression and decompression into reused buffers, for allocation-free hot loops
+//!
+//! `compress` and `decompress` allocate a fresh `Vec` each call; `Compressor` and `Decompressor` own one buffer,
+//! grown to the largest output so far and lent out until the next call. Each may use its own `Backend`.
+
+use alloc::vec::Vec;
+
+use crate::backend::{default_backend, Backend};
+use crate::SnappyError;
+
+/// Raw compressor reusing its output buffer
+#[derive(Debug, Clone)]
+pub struct Compressor {
+  backend: &'static dyn Backend,
+  buffer: Vec<u8>,
+}
+
+impl Compressor {
+  pub fn new() -> Self { Compressor::with_backend(default_backend()) }
+
+  /// Compressor ready for inputs of up to `len` bytes without allocating
+  pub fn with_capacity(len: usize) -> Self {
+    let backend = default_backend();
+    Compressor { backend, buffer: Vec::with_capacity(backend.max_compressed_len(len)) }
+  }
+
+  /// Compressor using `backend` instead of the crate default
+  pub fn with_backend(backend: &'static dyn Backend) -> Self { Compressor { backend, buffer: Vec::new() } }
+
+  /// Compress `input`, output is valid until the next call
+  pub fn compress(&mut self, input: &[u8]) -> &[u8] {
+    self.buffer.clear();
+    self.buffer.reserve(self.backend.max_compressed_len(input.len()));
+    let len = self.backend.compress(input, self.buffer.spare_capacity_mut()).expect("buffer of max compressed length is always enough");
+    unsafe { self.buffer.set_len(len) }
+    &self.buffer
+  }
+
+  /// Bytes allocated for output
+  pub fn capacity(&self) -> usize { self.buffer.capacity() }
+}
+
+impl Default for Compressor {
+  fn default() -> Self { Compressor::new() }
+}
+
+/// Raw decompressor reusing its output buffer
+#[derive(Debug, Clone)]
+pub struct Decompressor {
+  backend: &'static dyn Backend,
+  buffer: Vec<u8>,
+}
+
+impl Decompressor {
+  pub fn new() -> Self { Decompressor::with_backend(default_backend()) }
+
+  /// Decompressor ready for outputs of up to `len` bytes without allocating
+  pub fn with_capacity(len: usize) -> Self { Decompressor { backend: default_backend(), buffer: Vec::with_capacity(len) } }
+
+  /// Decompressor using `backend` instead of the crate default
+  pub fn with_backend(backend: &'static dyn Backend) -> Self { Decompressor { backend, buffer: Vec::new() } }
+
+  /// Decompress `input`, output is valid until the next call
+  pub fn decompress(&mut self, input: &[u8]) -> Result<&[u8], SnappyError> {
+    self.buffer.clear();
+    self.buffer.reserve(self.backend.uncompressed_len(input)?);
+    let len = self.backend.decompress(input, self.buffer.spare_capacity_mut())?;
+    unsafe { self.buffer.set_len(len) }
+    Ok(&self.buffer)
+  }
+
+  /// Bytes allocated for output
+  pub fn capacity(&self) -> usize { self.buffer.capacity() }
+}
+
+impl Default for Decompressor {
+  fn default() -> Self { Decompressor::new() }
+}
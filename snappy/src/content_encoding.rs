@@ -0,0 +1,236 @@
+//! `Content-Encoding: x-snappy-framed` bodies for the `http` ecosystem, with the `http` feature
+//!
+//! Bodies are wrapped, not collected: chunks are compressed and decompressed as frames of the inner body come.
+
+use std::error::Error;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use bytes::{Buf, Bytes};
+use http::header::{HeaderMap, HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH};
+use http::{Request, Response};
+use http_body::{Body, Frame, SizeHint};
+
+use crate::frame::{FrameEncoder, PushDecoder};
+
+/// Content coding name of the framing format
+pub const X_SNAPPY_FRAMED: &str = "x-snappy-framed";
+
+/// Error of wrapped bodies: error of the inner body, or corrupted stream
+pub type BoxError = Box<dyn Error + Send + Sync>;
+
+/// Whether `Accept-Encoding` of `headers` lists `x-snappy-framed` (without `q=0`)
+pub fn accepts_snappy(headers: &HeaderMap) -> bool {
+  headers.get_all(ACCEPT_ENCODING).iter().filter_map(|value| value.to_str().ok()).flat_map(|value| value.split(','))
+    .any(|coding| {
+      let mut params = coding.split(';').map(str::trim);
+      params.next().is_some_and(|name| name.eq_ignore_ascii_case(X_SNAPPY_FRAMED))
+        && params.all(|param| !matches!(param, "q=0" | "q=0.0" | "q=0.00" | "q=0.000"))
+    })
+}
+
+/// Whether `Content-Encoding` of `headers` is `x-snappy-framed`
+pub fn is_snappy_encoded(headers: &HeaderMap) -> bool {
+  headers.get(CONTENT_ENCODING).is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(X_SNAPPY_FRAMED.as_bytes()))
+}
+
+/// Compress `body`, setting `Content-Encoding` in `headers`; `Content-Length` is removed as it changes
+pub fn compress<B>(headers: &mut HeaderMap, body: B) -> CompressBody<B> {
+  headers.insert(CONTENT_ENCODING, HeaderValue::from_static(X_SNAPPY_FRAMED));
+  headers.remove(CONTENT_LENGTH);
+  CompressBody::with_encoder(body, Some(FrameEncoder::new(Vec::new())))
+}
+
+/// Decompress `body` if `headers` say it is `x-snappy-framed`, consuming that `Content-Encoding`; else pass it through
+pub fn decompress<B>(headers: &mut HeaderMap, body: B) -> DecompressBody<B> {
+  let decoder = if is_snappy_encoded(headers) {
+    headers.remove(CONTENT_ENCODING);
+    headers.remove(CONTENT_LENGTH);
+    Some(PushDecoder::new())
+  } else {
+    None
+  };
+  DecompressBody { inner: body, decoder, trailers: None, inner_done: false, done: false }
+}
+
+/// `compress` the body of `request`
+pub fn compress_request<B>(request: Request<B>) -> Request<CompressBody<B>> {
+  let (mut parts, body) = request.into_parts();
+  let body = compress(&mut parts.headers, body);
+  Request::from_parts(parts, body)
+}
+
+/// `compress` the body of `response`
+pub fn compress_response<B>(response: Response<B>) -> Response<CompressBody<B>> {
+  let (mut parts, body) = response.into_parts();
+  let body = compress(&mut parts.headers, body);
+  Response::from_parts(parts, body)
+}
+
+/// `decompress` the body of `request`
+pub fn decompress_request<B>(request: Request<B>) -> Request<DecompressBody<B>> {
+  let (mut parts, body) = request.into_parts();
+  let body = decompress(&mut parts.headers, body);
+  Request::from_parts(parts, body)
+}
+
+/// `decompress` the body of `response`
+pub fn decompress_response<B>(response: Response<B>) -> Response<DecompressBody<B>> {
+  let (mut parts, body) = response.into_parts();
+  let body = decompress(&mut parts.headers, body);
+  Response::from_parts(parts, body)
+}
+
+/// Body compressed as a framed stream, or passed through by `layer`, see `compress`
+///
+/// Data is gathered into whole blocks; trailers of the inner body come after all the data.
+pub struct CompressBody<B> {
+  inner: B,
+  /// Encodes into its `Vec`, taken as data frames; `None` when passing through
+  encoder: Option<FrameEncoder<Vec<u8>>>,
+  buffer: Vec<u8>,
+  trailers: Option<HeaderMap>,
+  done: bool,
+}
+
+impl<B> CompressBody<B> {
+  pub(crate) fn with_encoder(body: B, encoder: Option<FrameEncoder<Vec<u8>>>) -> Self {
+    CompressBody { inner: body, encoder, buffer: Vec::new(), trailers: None, done: false }
+  }
+
+  /// Whether the body is compressed, not passed through
+  pub fn is_compressing(&self) -> bool { self.encoder.is_some() }
+
+  pub fn get_ref(&self) -> &B { &self.inner }
+  pub fn into_inner(self) -> B { self.inner }
+}
+
+impl<B> Body for CompressBody<B> where B: Body + Unpin, B::Error: Into<BoxError> {
+  type Data = Bytes;
+  type Error = BoxError;
+
+  fn poll_frame(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, BoxError>>> {
+    let this = self.get_mut();
+    let encoder = match this.encoder {
+      Some(ref mut encoder) => encoder,
+      None => {
+        let frame = ready!(Pin::new(&mut this.inner).poll_frame(cx));
+        return Poll::Ready(frame.map(|frame| frame.map(|frame| frame.map_data(|mut data| data.copy_to_bytes(data.remaining()))).map_err(Into::into)))
+      },
+    };
+
+    while !this.done {
+      match ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+        Some(Ok(frame)) => match frame.into_data() {
+          Ok(mut data) => {
+            while data.has_remaining() {
+              let chunk = data.chunk();
+              this.buffer.extend_from_slice(chunk);
+              let len = chunk.len();
+              data.advance(len);
+            }
+            encode(encoder, &mut this.buffer, false);
+          },
+          Err(frame) => {
+            this.trailers = frame.into_trailers().ok();
+            encode(encoder, &mut this.buffer, true);
+            this.done = true;
+          },
+        },
+        Some(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+        None => { encode(encoder, &mut this.buffer, true); this.done = true },
+      }
+
+      let output = std::mem::take(encoder.get_mut());
+      if !output.is_empty() { return Poll::Ready(Some(Ok(Frame::data(Bytes::from(output))))) }
+    }
+    Poll::Ready(this.trailers.take().map(|trailers| Ok(Frame::trailers(trailers))))
+  }
+
+  fn is_end_stream(&self) -> bool {
+    if self.encoder.is_none() { self.inner.is_end_stream() } else { self.done && self.trailers.is_none() }
+  }
+
+  fn size_hint(&self) -> SizeHint {
+    if self.encoder.is_none() { self.inner.size_hint() } else { SizeHint::default() }
+  }
+}
+
+/// Compress buffered data of whole blocks, or all of it at the end
+fn encode(encoder: &mut FrameEncoder<Vec<u8>>, buffer: &mut Vec<u8>, end: bool) {
+  let whole = if end { buffer.len() } else { buffer.len() - buffer.len() % encoder.block_size() };
+  encoder.write_data(&buffer[..whole]).expect("writing to Vec never fails");
+  buffer.drain(..whole);
+  if end { encoder.sync_flush().expect("writing to Vec never fails") }
+}
+
+/// Body decompressed from a framed stream, or passed through, see `decompress`
+pub struct DecompressBody<B> {
+  inner: B,
+  /// `None` when passing through
+  decoder: Option<PushDecoder>,
+  trailers: Option<HeaderMap>,
+  /// Inner body ended
+  inner_done: bool,
+  done: bool,
+}
+
+impl<B> DecompressBody<B> {
+  /// Whether the body is decompressed, not passed through
+  pub fn is_decompressing(&self) -> bool { self.decoder.is_some() }
+
+  pub fn get_ref(&self) -> &B { &self.inner }
+  pub fn into_inner(self) -> B { self.inner }
+}
+
+impl<B> Body for DecompressBody<B> where B: Body + Unpin, B::Error: Into<BoxError> {
+  type Data = Bytes;
+  type Error = BoxError;
+
+  fn poll_frame(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, BoxError>>> {
+    let this = self.get_mut();
+    let decoder = match this.decoder {
+      Some(ref mut decoder) => decoder,
+      None => {
+        let frame = ready!(Pin::new(&mut this.inner).poll_frame(cx));
+        return Poll::Ready(frame.map(|frame| frame.map(|frame| frame.map_data(|mut data| data.copy_to_bytes(data.remaining()))).map_err(Into::into)))
+      },
+    };
+
+    if this.done { return Poll::Ready(None) }
+    loop {
+      match decoder.next_block() {
+        Ok(Some([])) => continue,
+        Ok(Some(block)) => return Poll::Ready(Some(Ok(Frame::data(Bytes::copy_from_slice(block))))),
+        Ok(None) => (),
+        Err(e) => { this.done = true; return Poll::Ready(Some(Err(e.into()))) },
+      }
+      if this.inner_done {
+        this.done = true;
+        decoder.finish()?;
+        return Poll::Ready(this.trailers.take().map(|trailers| Ok(Frame::trailers(trailers))))
+      }
+
+      match ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+        Some(Ok(frame)) => match frame.into_data() {
+          Ok(mut data) => {
+            while data.has_remaining() {
+              let len = data.chunk().len();
+              decoder.push(data.chunk());
+              data.advance(len);
+            }
+          },
+          Err(frame) => { this.trailers = frame.into_trailers().ok(); this.inner_done = true },
+        },
+        Some(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+        None => this.inner_done = true,
+      }
+    }
+  }
+
+  fn is_end_stream(&self) -> bool { self.done || (self.decoder.is_none() && self.inner.is_end_stream()) }
+
+  fn size_hint(&self) -> SizeHint {
+    if self.decoder.is_none() { self.inner.size_hint() } else { SizeHint::default() }
+  }
+}
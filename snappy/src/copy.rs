@@ -0,0 +1,144 @@
+//! `io::copy` through the snappy formats: whole readers compressed into writers, and back
+//!
+//! The loop every user of the encoders and decoders would write, with one block of buffer. Format and threads are
+//! picked by `EncoderOptions` and `DecoderOptions`, framed on the calling thread by default.
+
+use std::io::{self, Read, Write};
+
+use crate::format::Format;
+use crate::frame::{invalid, read_full, DecoderOptions, EncoderOptions, FrameDecoder, FrameEncoder};
+use crate::hadoop::{HadoopReader, HadoopWriter};
+use crate::parallel::{ParallelFrameDecoder, ParallelFrameEncoder};
+use crate::xerial::{XerialReader, XerialWriter};
+use crate::{compress, decompress, decompress_len};
+
+/// Byte counts of `copy_compress` and `copy_decompress`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CopyStats {
+  /// Length read from the reader
+  pub input_len: u64,
+  /// Length written to the writer
+  pub output_len: u64,
+}
+
+/// Compress all of `reader` into a stream written to `writer`, which is flushed
+///
+/// Empty input still gives a valid stream, e.g. the stream identifier alone when framed.
+pub fn copy_compress<R: Read + ?Sized, W: Write + ?Sized>(mut reader: &mut R, writer: &mut W, options: EncoderOptions) -> io::Result<CopyStats> {
+  let block_size = FrameEncoder::with_options(io::sink(), options)?.block_size();
+  let mut output = Counted { inner: writer, len: 0 };
+  let input_len = match options.output_format() {
+    Format::Framed if options.threads() > 1 => {
+      let mut encoder = ParallelFrameEncoder::with_options(&mut output, options)?;
+      let len = io::copy(reader, &mut encoder)?;
+      encoder.finish()?;
+      len
+    },
+    Format::Framed => {
+      let mut encoder = FrameEncoder::with_options(&mut output, options)?;
+      let mut buffer = vec![0; block_size];
+      loop {
+        let len = read_full(&mut reader, &mut buffer)?;
+        encoder.write_data(&buffer[..len])?;
+        if len < buffer.len() { break }
+      }
+      encoder.sync_flush()?;
+      encoder.stats().uncompressed_len
+    },
+    Format::Hadoop => {
+      let mut encoder = HadoopWriter::with_block_size(&mut output, block_size);
+      let len = io::copy(reader, &mut encoder)?;
+      encoder.finish()?.flush()?;
+      len
+    },
+    Format::Xerial => {
+      let mut encoder = XerialWriter::with_block_size(&mut output, block_size);
+      let len = io::copy(reader, &mut encoder)?;
+      encoder.finish()?.flush()?;
+      len
+    },
+    Format::Raw => {
+      let mut data = Vec::new();
+      reader.read_to_end(&mut data)?;
+      output.write_all(&compress(&data))?;
+      output.flush()?;
+      data.len() as u64
+    },
+  };
+  Ok(CopyStats { input_len, output_len: output.len })
+}
+
+/// Decompress the stream read from `reader` into `writer`, which is flushed
+///
+/// `DecoderOptions::max_output_size` holds for every format; the other framed options for framed streams.
+pub fn copy_decompress<R: Read + ?Sized, W: Write + ?Sized>(reader: &mut R, writer: &mut W, options: DecoderOptions) -> io::Result<CopyStats> {
+  let mut input = Counted { inner: reader, len: 0 };
+  let output_len = match options.input_format() {
+    Format::Framed if options.decoding_threads() > 1 => io::copy(&mut ParallelFrameDecoder::with_options(&mut input, options), writer)?,
+    Format::Framed => {
+      let mut decoder = FrameDecoder::with_options(&mut input, options);
+      let mut len = 0;
+      while let Some(block) = decoder.read_block()? {
+        writer.write_all(block)?;
+        len += block.len() as u64;
+      }
+      len
+    },
+    Format::Hadoop => io::copy(&mut HadoopReader::new(&mut input).max_output_size(options.output_limit()), writer)?,
+    Format::Xerial => io::copy(&mut XerialReader::new(&mut input).max_output_size(options.output_limit()), writer)?,
+    Format::Raw => {
+      let mut data = Vec::new();
+      input.read_to_end(&mut data)?;
+      options.check_output_len(decompress_len(&data)? as u64).map_err(invalid)?;
+      let data = decompress(&data)?;
+      writer.write_all(&data)?;
+      data.len() as u64
+    },
+  };
+  writer.flush()?;
+  Ok(CopyStats { input_len: input.len, output_len })
+}
+
+/// `copy_compress` of `input` into a new vector
+pub fn encode_all(input: &[u8], options: EncoderOptions) -> io::Result<Vec<u8>> {
+  let mut output = Vec::new();
+  copy_compress(&mut &input[..], &mut output, options)?;
+  Ok(output)
+}
+
+/// `copy_decompress` of `input` into a new vector
+pub fn decode_all(input: &[u8], options: DecoderOptions) -> io::Result<Vec<u8>> {
+  let mut output = Vec::new();
+  copy_decompress(&mut &input[..], &mut output, options)?;
+  Ok(output)
+}
+
+/// Reader or writer counting the bytes through it
+struct Counted<'a, T: ?Sized> {
+  inner: &'a mut T,
+  len: u64,
+}
+
+impl<R: Read + ?Sized> Read for Counted<'_, R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let len = self.inner.read(buf)?;
+    self.len += len as u64;
+    Ok(len)
+  }
+}
+
+impl<W: Write + ?Sized> Write for Counted<'_, W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let len = self.inner.write(buf)?;
+    self.len += len as u64;
+    Ok(len)
+  }
+
+  fn write_vectored(&mut self, bufs: &[io::IoSlice]) -> io::Result<usize> {
+    let len = self.inner.write_vectored(bufs)?;
+    self.len += len as u64;
+    Ok(len)
+  }
+
+  fn flush(&mut self) -> io::Result<()> { self.inner.flush() }
+}
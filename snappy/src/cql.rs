@@ -0,0 +1,55 @@
+//! Frame bodies of the Cassandra and ScyllaDB native protocol (CQL), versions 3 and 4
+//!
+//! Once `snappy` compression is agreed at `STARTUP`, frames with `COMPRESSION_FLAG` set carry their body as one raw
+//! block; the length in the 9 bytes header is that of the block.
+
+use crate::{compress, decompress, SnappyError};
+
+/// Length of the frame header: version, flags, stream id, opcode and body length
+pub const HEADER_LEN: usize = 9;
+/// Flag of frames with compressed body
+pub const COMPRESSION_FLAG: u8 = 0x01;
+
+/// Compress a frame body
+pub fn compress_body(body: &[u8]) -> Vec<u8> { compress(body) }
+
+/// Decompress a frame body
+pub fn decompress_body(body: &[u8]) -> Result<Vec<u8>, SnappyError> { decompress(body) }
+
+/// Frame of the frame `frame` begins with, its body compressed and header flagged and lengthened to match
+///
+/// Frames already compressed are copied as they are.
+pub fn compress_frame(frame: &[u8]) -> Result<Vec<u8>, SnappyError> {
+  let (header, body) = split_frame(frame)?;
+  if header[1] & COMPRESSION_FLAG != 0 { return Ok(frame[..HEADER_LEN + body.len()].to_vec()) }
+  Ok(join_frame(header, header[1] | COMPRESSION_FLAG, &compress(body)))
+}
+
+/// Frame of the frame `frame` begins with, its body decompressed and header unflagged and lengthened to match
+///
+/// The body is as long as the header says; frames not compressed are copied as they are.
+pub fn decompress_frame(frame: &[u8]) -> Result<Vec<u8>, SnappyError> {
+  let (header, body) = split_frame(frame)?;
+  if header[1] & COMPRESSION_FLAG == 0 { return Ok(frame[..HEADER_LEN + body.len()].to_vec()) }
+  Ok(join_frame(header, header[1] & !COMPRESSION_FLAG, &decompress(body)?))
+}
+
+/// Header and body of the frame `frame` begins with, `TruncatedStream` if it is not all there
+fn split_frame(frame: &[u8]) -> Result<(&[u8], &[u8]), SnappyError> {
+  if frame.len() < HEADER_LEN { return Err(SnappyError::TruncatedStream { missing: (HEADER_LEN - frame.len()) as u64 }) }
+  let (header, rest) = frame.split_at(HEADER_LEN);
+  let len = u32::from_be_bytes([header[5], header[6], header[7], header[8]]) as usize;
+  match rest.get(..len) {
+    Some(body) => Ok((header, body)),
+    None => Err(SnappyError::TruncatedStream { missing: (len - rest.len()) as u64 }),
+  }
+}
+
+fn join_frame(header: &[u8], flags: u8, body: &[u8]) -> Vec<u8> {
+  let mut frame = Vec::with_capacity(HEADER_LEN + body.len());
+  frame.extend_from_slice(&header[..5]);
+  frame[1] = flags;
+  frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+  frame.extend_from_slice(body);
+  frame
+}
@@ -0,0 +1,85 @@
+//! CRC-32C (Castagnoli) checksum, used by the snappy framing format
+//!
+//! See section 3 of [framing_format.txt](https://github.com/google/snappy/blob/main/framing_format.txt)
+//!
+//! SSE 4.2 `crc32` instructions on x86_64 and CRC extension on ARMv8 are used when detected at runtime,
+//! with a lookup table as portable fallback.
+
+/// Reversed Castagnoli polynomial
+const POLY: u32 = 0x82f6_3b78;
+
+/// Byte-wise lookup table
+const TABLE: [u32; 256] = make_table(POLY);
+
+/// Byte-wise lookup table of the reflected CRC-32 of reversed polynomial `poly`, also used by `avro`
+pub(crate) const fn make_table(poly: u32) -> [u32; 256] {
+  let mut table = [0u32; 256];
+  let mut i = 0;
+  while i < 256 {
+    let mut crc = i as u32;
+    let mut bit = 0;
+    while bit < 8 {
+      crc = if crc & 1 == 1 { (crc >> 1) ^ poly } else { crc >> 1 };
+      bit += 1;
+    }
+    table[i] = crc;
+    i += 1;
+  }
+  table
+}
+
+/// CRC-32C of `data`
+pub fn crc32c(data: &[u8]) -> u32 { crc32c_extend(0, data) }
+
+/// CRC-32C of the data `crc` is of, followed by `data`
+pub fn crc32c_extend(crc: u32, data: &[u8]) -> u32 {
+  #[cfg(target_arch = "x86_64")]
+  {
+    if is_x86_feature_detected!("sse4.2") { return unsafe { !update_sse42(!crc, data) } }
+  }
+  #[cfg(target_arch = "aarch64")]
+  {
+    if std::arch::is_aarch64_feature_detected!("crc") { return unsafe { !update_armv8(!crc, data) } }
+  }
+  !update_table(!crc, data)
+}
+
+/// Portable CRC update, byte at a time
+fn update_table(crc: u32, data: &[u8]) -> u32 {
+  data.iter().fold(crc, |crc, &b| TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.2")]
+unsafe fn update_sse42(crc: u32, data: &[u8]) -> u32 {
+  use std::arch::x86_64::{_mm_crc32_u64, _mm_crc32_u8};
+  use std::convert::TryInto;
+
+  let mut words = data.chunks_exact(8);
+  let mut crc = words.by_ref()
+    .fold(crc as u64, |crc, word| _mm_crc32_u64(crc, u64::from_le_bytes(word.try_into().unwrap()))) as u32;
+  for &b in words.remainder() { crc = _mm_crc32_u8(crc, b) }
+  crc
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "crc")]
+unsafe fn update_armv8(crc: u32, data: &[u8]) -> u32 {
+  use std::arch::aarch64::{__crc32cb, __crc32cd};
+  use std::convert::TryInto;
+
+  let mut words = data.chunks_exact(8);
+  let mut crc = words.by_ref().fold(crc, |crc, word| __crc32cd(crc, u64::from_le_bytes(word.try_into().unwrap())));
+  for &b in words.remainder() { crc = __crc32cb(crc, b) }
+  crc
+}
+
+/// Mask a CRC as the framing format requires
+///
+/// Checksums of data that itself contains CRCs are less error-prone this way.
+pub fn mask(crc: u32) -> u32 {
+  crc.rotate_right(15).wrapping_add(0xa282_ead8)
+}
+
+/// Masked CRC-32C of `data`, as stored in data chunks
+pub fn masked_crc32c(data: &[u8]) -> u32 { mask(crc32c(data)) }
@@ -0,0 +1,56 @@
+//! Delta and zigzag transform of integer columns, e.g. timestamps or offsets
+//!
+//! Monotonic integers differ little from one to the next, yet share few whole bytes, so snappy finds few matches in
+//! them. `delta_encode` keeps the differences instead, zigzag-mapped so small negative ones stay small too: their
+//! high bytes are all zero. `compress_delta` records the transform in a metadata chunk of the framed stream,
+//! for `decompress_delta`.
+
+use std::convert::TryInto;
+use std::io;
+
+use crate::frame::{DecoderOptions, EncoderOptions, FrameDecoder, FrameEncoder};
+
+/// Metadata chunk type of delta-encoded streams, holding the element width, 8
+pub const DELTA_TAG: u8 = 0x91;
+
+/// Zigzag-mapped differences of `values` from the value before, the first from 0; overflows wrap
+pub fn delta_encode(values: &[i64]) -> Vec<u64> {
+  let mut last = 0i64;
+  values.iter().map(|&value| {
+    let delta = value.wrapping_sub(last);
+    last = value;
+    ((delta << 1) ^ (delta >> 63)) as u64
+  }).collect()
+}
+
+/// Values back from their `delta_encode`
+pub fn delta_decode(deltas: &[u64]) -> Vec<i64> {
+  let mut last = 0i64;
+  deltas.iter().map(|&delta| {
+    last = last.wrapping_add((delta >> 1) as i64 ^ -((delta & 1) as i64));
+    last
+  }).collect()
+}
+
+/// Framed stream of the deltas of `values`, little-endian, after a `DELTA_TAG` metadata chunk
+pub fn compress_delta(values: &[i64], options: EncoderOptions) -> io::Result<Vec<u8>> {
+  let data: Vec<u8> = delta_encode(values).iter().flat_map(|delta| delta.to_le_bytes()).collect();
+  let mut encoder = FrameEncoder::with_options(Vec::new(), options)?;
+  encoder.write_metadata(DELTA_TAG, &[8])?;
+  encoder.write_data(&data)?;
+  encoder.finish()
+}
+
+/// Values of framed stream `input` of `compress_delta`
+///
+/// A stream without the `DELTA_TAG` metadata chunk, or not of whole elements, gives `InvalidData`.
+pub fn decompress_delta(input: &[u8], options: DecoderOptions) -> io::Result<Vec<i64>> {
+  let mut decoder = FrameDecoder::with_options(input, options.read_metadata(true));
+  let mut data = Vec::new();
+  decoder.read_to_end(&mut data)?;
+
+  let delta = decoder.metadata().any(|metadata| metadata.tag == DELTA_TAG && metadata.data == [8]);
+  if !delta || data.len() % 8 != 0 { return Err(io::Error::new(io::ErrorKind::InvalidData, "not a delta-encoded stream")) }
+  let deltas: Vec<u64> = data.chunks_exact(8).map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap())).collect();
+  Ok(delta_decode(&deltas))
+}
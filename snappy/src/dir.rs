@@ -0,0 +1,143 @@
+//! Recursive compression of directory trees, each file to a framed stream file next to it
+//!
+//! Like `gzip -r`: `compress_dir` walks a tree and compresses every selected file `name` into `name.sz`,
+//! files on several threads.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::file::{compress_file, FileOptions, FileStats};
+use crate::frame::{read_full, EncoderOptions, FrameDecoder};
+
+/// Which files `compress_dir` takes and what it does with them
+#[derive(Debug, Clone, Default)]
+pub struct DirOptions {
+  encoder: EncoderOptions,
+  file: FileOptions,
+  include: Vec<String>,
+  exclude: Vec<String>,
+  remove: bool,
+  threads: Option<usize>,
+}
+
+impl DirOptions {
+  pub fn new() -> Self { DirOptions::default() }
+
+  /// Options of each compressed file
+  pub fn encoder(mut self, options: EncoderOptions) -> Self { self.encoder = options; self }
+  pub fn file(mut self, options: FileOptions) -> Self { self.file = options; self }
+
+  /// Only take files whose name matches one of the included globs, all files if none
+  ///
+  /// Globs match file names, not paths: `*` is any run of characters, `?` any one character.
+  pub fn include(mut self, glob: &str) -> Self { self.include.push(glob.to_string()); self }
+
+  /// Skip files and whole directories whose name matches `glob`, over includes
+  pub fn exclude(mut self, glob: &str) -> Self { self.exclude.push(glob.to_string()); self }
+
+  /// Remove each original once its output is decompressed and compared to it, off by default
+  pub fn remove_originals(mut self, remove: bool) -> Self { self.remove = remove; self }
+
+  /// Files compressed at once, one per CPU by default
+  pub fn threads(mut self, threads: usize) -> Self { self.threads = Some(threads); self }
+
+  fn takes(&self, name: &str) -> bool {
+    (self.include.is_empty() || self.include.iter().any(|glob| glob_match(glob, name))) && !self.excludes(name)
+  }
+
+  fn excludes(&self, name: &str) -> bool { self.exclude.iter().any(|glob| glob_match(glob, name)) }
+}
+
+/// Compress each file under `root` to the same path with `.sz` appended
+///
+/// Files already ending in `.sz` and symbolic links are skipped. Every file taken, and every directory failing to be
+/// listed, gets its result, in path order; a failed file never loses its original.
+pub fn compress_dir<P: AsRef<Path>>(root: P, options: &DirOptions) -> Vec<(PathBuf, io::Result<FileStats>)> {
+  let mut results = Vec::new();
+  let mut files = Vec::new();
+  walk(root.as_ref(), options, &mut files, &mut results);
+
+  let threads = options.threads.unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+  let queue = Mutex::new(files.into_iter());
+  let done = Mutex::new(results);
+  thread::scope(|scope| {
+    for _ in 0..threads.clamp(1, 64) {
+      scope.spawn(|| loop {
+        let path = match queue.lock().unwrap().next() { Some(path) => path, None => return };
+        let result = compress_one(&path, options);
+        done.lock().unwrap().push((path, result));
+      });
+    }
+  });
+
+  let mut results = done.into_inner().unwrap();
+  results.sort_by(|a, b| a.0.cmp(&b.0));
+  results
+}
+
+/// Collect files to compress under `dir` into `files`, errors listing directories into `results`
+fn walk(dir: &Path, options: &DirOptions, files: &mut Vec<PathBuf>, results: &mut Vec<(PathBuf, io::Result<FileStats>)>) {
+  let entries = match fs::read_dir(dir).and_then(|entries| entries.collect::<io::Result<Vec<_>>>()) {
+    Ok(entries) => entries,
+    Err(e) => return results.push((dir.to_owned(), Err(e))),
+  };
+  for entry in entries {
+    let (path, name) = (entry.path(), entry.file_name());
+    let name = name.to_string_lossy();
+    match entry.file_type() {
+      Ok(kind) if kind.is_dir() => if !options.excludes(&name) { walk(&path, options, files, results) },
+      Ok(kind) if kind.is_file() => if !name.ends_with(".sz") && options.takes(&name) { files.push(path) },
+      Ok(_) => {},
+      Err(e) => results.push((path, Err(e))),
+    }
+  }
+}
+
+fn compress_one(path: &Path, options: &DirOptions) -> io::Result<FileStats> {
+  let mut dst = path.as_os_str().to_owned();
+  dst.push(".sz");
+  let dst = PathBuf::from(dst);
+  let stats = compress_file(path, &dst, options.encoder, options.file)?;
+  if options.remove {
+    verify(path, &dst)?;
+    fs::remove_file(path)?;
+  }
+  Ok(stats)
+}
+
+/// Check framed stream file `compressed` decompresses to the contents of `original`
+fn verify(original: &Path, compressed: &Path) -> io::Result<()> {
+  let mut original = BufReader::new(File::open(original)?);
+  let mut decoder = FrameDecoder::new(BufReader::new(File::open(compressed)?));
+  let mut buffer = Vec::new();
+  while let Some(block) = decoder.read_block()? {
+    buffer.resize(block.len(), 0);
+    if read_full(&mut original, &mut buffer)? != block.len() || buffer != block { return Err(mismatch()) }
+  }
+  if original.read(&mut [0])? != 0 { return Err(mismatch()) }
+  Ok(())
+}
+
+fn mismatch() -> io::Error { io::Error::new(io::ErrorKind::InvalidData, "compressed file differs from original") }
+
+/// Whether `name` matches `glob` of `*` and `?` wildcards
+fn glob_match(glob: &str, name: &str) -> bool {
+  let (glob, name): (Vec<char>, Vec<char>) = (glob.chars().collect(), name.chars().collect());
+  let (mut g, mut n) = (0, 0);
+  // after the last `*`: where in the glob, and where in the name it took up to
+  let mut star = None;
+  while n < name.len() {
+    match glob.get(g) {
+      Some('*') => { star = Some((g, n)); g += 1 },
+      Some(&c) if c == '?' || c == name[n] => { g += 1; n += 1 },
+      _ => match star {
+        Some((star_g, star_n)) => { star = Some((star_g, star_n + 1)); g = star_g + 1; n = star_n + 1 },
+        None => return false,
+      },
+    }
+  }
+  glob[g..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,113 @@
+//! libsnappy loaded at runtime instead of linked at build time, with the `dlopen` feature
+//!
+//! For deployments where the library is only known to exist, if at all, once running: `DynamicLibSnappy::load`
+//! finds it by its platform name, and gives an error rather than failing to start when it is missing.
+//! Build with `default-features = false` and `pure-rust` so nothing is linked, with `load_or_pure` as fallback.
+
+use core::ffi::c_int;
+use core::fmt;
+use core::mem::MaybeUninit;
+use std::ffi::OsStr;
+use std::io;
+use std::sync::OnceLock;
+
+use libloading::Library;
+
+use crate::backend::Backend;
+use crate::{status, SnappyError};
+
+type CompressFn = unsafe extern "C" fn(*const u8, usize, *mut u8, *mut usize) -> c_int;
+type MaxLengthFn = unsafe extern "C" fn(usize) -> usize;
+type LengthFn = unsafe extern "C" fn(*const u8, usize, *mut usize) -> c_int;
+type ValidateFn = unsafe extern "C" fn(*const u8, usize) -> c_int;
+
+/// libsnappy loaded with `dlopen`, or `LoadLibrary` on Windows
+pub struct DynamicLibSnappy {
+  compress: CompressFn,
+  uncompress: CompressFn,
+  max_compressed_length: MaxLengthFn,
+  uncompressed_length: LengthFn,
+  validate: ValidateFn,
+  /// Keeps the functions loaded
+  _library: Library,
+}
+
+impl DynamicLibSnappy {
+  /// The library found by its platform name, e.g. `libsnappy.so` or `snappy.dll`, loaded once per process
+  ///
+  /// A missing library or function gives `NotFound`, every time.
+  pub fn load() -> io::Result<&'static DynamicLibSnappy> {
+    static LIBRARY: OnceLock<Result<DynamicLibSnappy, String>> = OnceLock::new();
+    let library = LIBRARY.get_or_init(|| {
+      let mut names = vec![libloading::library_filename("snappy")];
+      if cfg!(target_os = "linux") { names.push("libsnappy.so.1".into()) }
+      let mut error = String::new();
+      for name in names {
+        match DynamicLibSnappy::open(&name) {
+          Ok(library) => return Ok(library),
+          Err(e) => error = e.to_string(),
+        }
+      }
+      Err(error)
+    });
+    library.as_ref().map_err(|e| io::Error::new(io::ErrorKind::NotFound, e.clone()))
+  }
+
+  /// Load the library at `path`, or of file name `path` searched where the platform looks for libraries
+  ///
+  /// Each call loads it anew; the library must really be libsnappy, as its functions are trusted.
+  pub fn open<P: AsRef<OsStr>>(path: P) -> io::Result<DynamicLibSnappy> {
+    let not_found = |e: libloading::Error| io::Error::new(io::ErrorKind::NotFound, e);
+    unsafe {
+      let library = Library::new(path).map_err(not_found)?;
+      Ok(DynamicLibSnappy {
+        compress: *library.get::<CompressFn>(b"snappy_compress\0").map_err(not_found)?,
+        uncompress: *library.get::<CompressFn>(b"snappy_uncompress\0").map_err(not_found)?,
+        max_compressed_length: *library.get::<MaxLengthFn>(b"snappy_max_compressed_length\0").map_err(not_found)?,
+        uncompressed_length: *library.get::<LengthFn>(b"snappy_uncompressed_length\0").map_err(not_found)?,
+        validate: *library.get::<ValidateFn>(b"snappy_validate_compressed_buffer\0").map_err(not_found)?,
+        _library: library,
+      })
+    }
+  }
+}
+
+impl fmt::Debug for DynamicLibSnappy {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("DynamicLibSnappy") }
+}
+
+// Same contract as `LibSnappy`, the symbols are those of libsnappy
+unsafe impl Backend for DynamicLibSnappy {
+  fn max_compressed_len(&self, len: usize) -> usize { unsafe { (self.max_compressed_length)(len) } }
+
+  fn compress(&self, input: &[u8], output: &mut [MaybeUninit<u8>]) -> Result<usize, SnappyError> {
+    let mut output_len = output.len();
+    unsafe { status((self.compress)(input.as_ptr(), input.len(), output.as_mut_ptr() as *mut u8, &mut output_len)) }?;
+    Ok(output_len)
+  }
+
+  fn uncompressed_len(&self, input: &[u8]) -> Result<usize, SnappyError> {
+    let mut len = 0;
+    unsafe { status((self.uncompressed_length)(input.as_ptr(), input.len(), &mut len)) }?;
+    Ok(len)
+  }
+
+  fn decompress(&self, input: &[u8], output: &mut [MaybeUninit<u8>]) -> Result<usize, SnappyError> {
+    // checked here too, so the `Backend` output contract does not rest on the loaded library alone
+    if self.uncompressed_len(input)? > output.len() { return Err(SnappyError::InsufficientBuffer) }
+    let mut output_len = output.len();
+    unsafe { status((self.uncompress)(input.as_ptr(), input.len(), output.as_mut_ptr() as *mut u8, &mut output_len)) }?;
+    Ok(output_len)
+  }
+
+  fn validate(&self, input: &[u8]) -> bool { unsafe { (self.validate)(input.as_ptr(), input.len()) == 0 } }
+}
+
+/// `DynamicLibSnappy` if it loads, else the `PureRust` backend, with the `pure-rust` feature too
+#[cfg(feature = "pure-rust")]
+pub fn load_or_pure() -> &'static dyn Backend {
+  match DynamicLibSnappy::load() {
+    Ok(library) => library,
+    Err(_) => &crate::backend::PureRust,
+  }
+}
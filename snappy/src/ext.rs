@@ -0,0 +1,21 @@
+//! Extension methods on byte slices, for scripting-style one-liners
+//!
+//! `data.snappy_compress()` instead of `snappy::compress(&data)`; works on `Vec<u8>` and arrays through deref.
+
+use alloc::vec::Vec;
+
+use crate::{compress, decompress, validate, SnappyError};
+
+/// Raw snappy methods on `[u8]`, see the functions of the same name without `snappy_` at the crate root
+pub trait SnappyExt {
+  fn snappy_compress(&self) -> Vec<u8>;
+  fn snappy_decompress(&self) -> Result<Vec<u8>, SnappyError>;
+  /// Whether this is a valid raw snappy block
+  fn snappy_valid(&self) -> bool;
+}
+
+impl SnappyExt for [u8] {
+  fn snappy_compress(&self) -> Vec<u8> { compress(self) }
+  fn snappy_decompress(&self) -> Result<Vec<u8>, SnappyError> { decompress(self) }
+  fn snappy_valid(&self) -> bool { unsafe { validate(self.as_ptr(), self.len()) } }
+}
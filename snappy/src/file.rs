@@ -0,0 +1,214 @@
+//! One-call compression of files to framed streams and back
+//!
+//! With the `mmap` feature, `compress_mapped` and `decompress_mapped` work on memory maps of both files instead of
+//! buffered reads and writes, leaving the caching to the OS page cache. With the `io-uring` feature on Linux,
+//! `uring::compress_file_uring` overlaps them with compression instead.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "mmap")]
+use memmap2::{Mmap, MmapMut};
+
+use crate::copy::{copy_compress, copy_decompress, CopyStats};
+use crate::format::Format;
+use crate::frame::{DecoderOptions, EncoderOptions, FrameDecoder};
+#[cfg(feature = "mmap")]
+use crate::frame::{invalid, FrameEncoder, CHUNK_HEADER_SIZE, STREAM_IDENTIFIER};
+#[cfg(feature = "mmap")]
+use crate::seek::FrameIndex;
+
+/// Buffer size of the input and output files, a few blocks so reads and writes stay large
+const BUFFER_SIZE: usize = 256 * 1024;
+
+/// How the file functions write their output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileOptions {
+  atomic: bool,
+}
+
+impl FileOptions {
+  pub fn new() -> Self { FileOptions { atomic: false } }
+
+  /// Write a temporary file next to `dst` and rename it to `dst` on success, off by default
+  ///
+  /// An interrupted or failed run then never leaves a truncated `dst`: the old file, if any, is kept,
+  /// and the temporary file is removed on error.
+  pub fn atomic(mut self, atomic: bool) -> Self { self.atomic = atomic; self }
+}
+
+/// Byte counts of a file compressed or decompressed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileStats {
+  /// Length of the file read
+  pub input_len: u64,
+  /// Length of the file written
+  pub output_len: u64,
+}
+
+/// Compress file `src` into stream file `dst`, which gets the permissions of `src`
+///
+/// The stream is framed unless `EncoderOptions::format` says otherwise, see `copy_compress`.
+/// `dst` is created or truncated; it is left partly written on error, unless `FileOptions::atomic`.
+pub fn compress_file<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q, options: EncoderOptions, file: FileOptions) -> io::Result<FileStats> {
+  let mut input = BufReader::with_capacity(BUFFER_SIZE, File::open(src.as_ref())?);
+  let output = Output::create(dst.as_ref(), file)?;
+  let mut writer = BufWriter::with_capacity(BUFFER_SIZE, &output.file);
+  let stats = copy_compress(&mut input, &mut writer, options)?;
+  writer.into_inner().map_err(|e| e.into_error())?;
+  output.commit(src.as_ref())?;
+  Ok(FileStats { input_len: stats.input_len, output_len: stats.output_len })
+}
+
+/// Decompress stream file `src` into file `dst`, which gets the permissions of `src`
+///
+/// The stream is framed unless `DecoderOptions::format` says otherwise, see `copy_decompress`.
+/// `dst` is created or truncated; it is left partly written on error, e.g. when `src` is corrupted,
+/// unless `FileOptions::atomic`.
+pub fn decompress_file<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q, options: DecoderOptions, file: FileOptions) -> io::Result<FileStats> {
+  let mut input = BufReader::with_capacity(BUFFER_SIZE, File::open(src.as_ref())?);
+  let output = Output::create(dst.as_ref(), file)?;
+  let mut writer = BufWriter::with_capacity(BUFFER_SIZE, &output.file);
+
+  let stats = if options.input_format() == Format::Framed && options.decoding_threads() <= 1 {
+    // chunks borrowed from the read buffer, not copied out like `copy_decompress` does
+    let mut decoder = FrameDecoder::from_bufread_with_options(input, options);
+    let mut output_len = 0;
+    while let Some(block) = decoder.read_block()? {
+      writer.write_all(block)?;
+      output_len += block.len() as u64;
+    }
+    CopyStats { input_len: decoder.offset(), output_len }
+  } else {
+    copy_decompress(&mut input, &mut writer, options)?
+  };
+
+  writer.into_inner().map_err(|e| e.into_error())?;
+  output.commit(src.as_ref())?;
+  Ok(FileStats { input_len: stats.input_len, output_len: stats.output_len })
+}
+
+/// Output file being written, at a temporary path with `FileOptions::atomic`
+pub(crate) struct Output {
+  pub(crate) file: File,
+  dst: PathBuf,
+  /// Temporary path, removed on drop unless committed
+  temp: Option<PathBuf>,
+}
+
+impl Output {
+  /// Create `dst`, or a new temporary file in its directory, open for reading too so it can be mapped
+  pub(crate) fn create(dst: &Path, options: FileOptions) -> io::Result<Output> {
+    if !options.atomic {
+      let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(dst)?;
+      return Ok(Output { file, dst: dst.to_owned(), temp: None })
+    }
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let name = dst.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "destination is not a file path"))?;
+    loop {
+      let mut temp_name = std::ffi::OsString::from(".");
+      temp_name.push(name);
+      temp_name.push(format!(".{}.{}.tmp", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+      let temp = dst.with_file_name(temp_name);
+      match OpenOptions::new().read(true).write(true).create_new(true).open(&temp) {
+        Ok(file) => return Ok(Output { file, dst: dst.to_owned(), temp: Some(temp) }),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+        Err(e) => return Err(e),
+      }
+    }
+  }
+
+  /// Sync the file and give it the permissions of `src`, then rename it to `dst` if temporary
+  ///
+  /// Permissions come last as they may make it read-only.
+  pub(crate) fn commit(mut self, src: &Path) -> io::Result<()> {
+    self.file.sync_all()?;
+    let path = self.temp.as_deref().unwrap_or(&self.dst);
+    fs::set_permissions(path, fs::metadata(src)?.permissions())?;
+    if let Some(temp) = self.temp.take() {
+      if let Err(e) = fs::rename(&temp, &self.dst) {
+        let _ = fs::remove_file(&temp);
+        return Err(e)
+      }
+    }
+    Ok(())
+  }
+}
+
+impl Drop for Output {
+  fn drop(&mut self) {
+    if let Some(ref temp) = self.temp { let _ = fs::remove_file(temp); }
+  }
+}
+
+/// `compress_file` through memory maps, with the `mmap` feature
+///
+/// `dst` is sized for the worst case, mapped and written in place, then truncated to the stream length.
+/// The files must not be changed by others meanwhile: a mapped file truncated under us is undefined behavior.
+/// Other formats than framed, `EncoderOptions::parallel` and `EncoderOptions::max_frame_size`, whose splits add chunk
+/// headers to the worst case, fall back to `compress_file`.
+#[cfg(feature = "mmap")]
+pub fn compress_mapped<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q, options: EncoderOptions, file: FileOptions) -> io::Result<FileStats> {
+  if options.output_format() != Format::Framed || options.threads() > 1 || options.frame_size().is_some() {
+    return compress_file(src, dst, options, file)
+  }
+  let input = File::open(src.as_ref())?;
+  let input = unsafe { Mmap::map(&input)? };
+  let block_size = FrameEncoder::with_options(io::sink(), options)?.block_size();
+  let blocks = input.len().div_ceil(block_size);
+  let max_chunk_len = CHUNK_HEADER_SIZE + 4 + options.max_compressed_len(block_size);
+  let max_len = STREAM_IDENTIFIER.len() + blocks * max_chunk_len;
+
+  let output = Output::create(dst.as_ref(), file)?;
+  output.file.set_len(max_len as u64)?;
+  let mut map = unsafe { MmapMut::map_mut(&output.file)? };
+  let mut encoder = FrameEncoder::with_options(&mut map[..], options).unwrap();
+  encoder.write_data(&input)?;
+  encoder.sync_flush()?;
+  let stats = encoder.stats();
+  drop(encoder);
+
+  close_mapped(map, output, stats.stream_len, src.as_ref())?;
+  Ok(FileStats { input_len: stats.uncompressed_len, output_len: stats.stream_len })
+}
+
+/// `decompress_file` through memory maps, with the `mmap` feature
+///
+/// Chunk headers of `src` are scanned first for the decompressed length, `dst` is sized for it and written in place.
+/// The files must not be changed by others meanwhile: a mapped file truncated under us is undefined behavior.
+/// Other formats than framed, `DecoderOptions::parallel` and `DecoderOptions::codec` fall back to `decompress_file`.
+#[cfg(feature = "mmap")]
+pub fn decompress_mapped<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q, options: DecoderOptions, file: FileOptions) -> io::Result<FileStats> {
+  if options.input_format() != Format::Framed || options.decoding_threads() > 1 || options.custom_codec().is_some() {
+    return decompress_file(src, dst, options, file)
+  }
+  let input = File::open(src.as_ref())?;
+  let input = unsafe { Mmap::map(&input)? };
+  let len = FrameIndex::scan(&mut io::Cursor::new(&input[..]))?.uncompressed_len();
+  options.check_output_len(len).map_err(invalid)?;
+
+  let output = Output::create(dst.as_ref(), file)?;
+  output.file.set_len(len)?;
+  let mut map = unsafe { MmapMut::map_mut(&output.file)? };
+  let mut decoder = FrameDecoder::from_bufread_with_options(&input[..], options);
+  let mut rest = &mut map[..];
+  while let Some(block) = decoder.read_block()? {
+    rest.write_all(block)?;
+  }
+  let output_len = len - rest.len() as u64;
+
+  close_mapped(map, output, output_len, src.as_ref())?;
+  Ok(FileStats { input_len: input.len() as u64, output_len })
+}
+
+/// Flush and unmap `map`, truncate `output` to `len`, then commit it
+#[cfg(feature = "mmap")]
+fn close_mapped(map: MmapMut, output: Output, len: u64, src: &Path) -> io::Result<()> {
+  map.flush()?;
+  drop(map);
+  output.file.set_len(len)?;
+  output.commit(src)
+}
@@ -0,0 +1,69 @@
+//! Sniffing of snappy flavors, so tools can accept any of them
+
+use std::io::{self, Read};
+
+use crate::frame::{FrameDecoder, STREAM_IDENTIFIER};
+use crate::hadoop::HadoopReader;
+use crate::xerial::{XerialReader, MAGIC};
+use crate::{decompress, snappy_uncompressed_length, snappy_validate_compressed_buffer, status, SnappyError};
+
+/// Snappy flavors `detect_format` knows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+  /// One raw snappy block, as `compress` gives
+  Raw,
+  /// Official framing format, see `frame`
+  Framed,
+  /// Hadoop block stream, see `hadoop`
+  Hadoop,
+  /// snappy-java stream, see `xerial`
+  Xerial,
+}
+
+/// Guess the format of compressed `input`
+///
+/// Framed and xerial streams are told by their magic bytes. A Hadoop stream is assumed when the first subchunk
+/// is a valid snappy block within its block length, a raw block when the whole input is valid.
+pub fn detect_format(input: &[u8]) -> Option<Format> {
+  if input.starts_with(STREAM_IDENTIFIER) { return Some(Format::Framed) }
+  if input.starts_with(MAGIC) { return Some(Format::Xerial) }
+  if is_hadoop(input) { return Some(Format::Hadoop) }
+  if unsafe { status(snappy_validate_compressed_buffer(input.as_ptr(), input.len())) }.is_ok() { return Some(Format::Raw) }
+  None
+}
+
+/// Whether `input` starts with a Hadoop block holding a valid subchunk
+fn is_hadoop(input: &[u8]) -> bool {
+  if input.len() < 8 { return false }
+  let block_len = u32::from_be_bytes([input[0], input[1], input[2], input[3]]) as usize;
+  let len = u32::from_be_bytes([input[4], input[5], input[6], input[7]]) as usize;
+  let subchunk = match input[8..].get(..len) { Some(subchunk) => subchunk, None => return false };
+
+  let mut data_len = 0;
+  unsafe {
+    status(snappy_uncompressed_length(subchunk.as_ptr(), len, &mut data_len)).is_ok() && data_len <= block_len && block_len != 0
+      && status(snappy_validate_compressed_buffer(subchunk.as_ptr(), len)).is_ok()
+  }
+}
+
+/// Decompress `input` of any format `detect_format` knows
+///
+/// Unknown formats give `InvalidInput`, corrupted streams the error of their decoder.
+pub fn decompress_auto(input: &[u8]) -> Result<Vec<u8>, SnappyError> {
+  let mut output = Vec::new();
+  match detect_format(input).ok_or(SnappyError::InvalidInput)? {
+    Format::Raw => return decompress(input),
+    Format::Framed => FrameDecoder::new(input).read_to_end(&mut output).map(drop),
+    Format::Hadoop => HadoopReader::new(input).read_to_end(&mut output).map(drop),
+    Format::Xerial => XerialReader::new(input).read_to_end(&mut output).map(drop),
+  }.map_err(snappy_error)?;
+  Ok(output)
+}
+
+/// Take back the `SnappyError` of a decoder error
+pub(crate) fn snappy_error(error: io::Error) -> SnappyError {
+  match error.into_inner().map(|e| e.downcast::<SnappyError>()) {
+    Some(Ok(error)) => *error,
+    _ => SnappyError::InvalidInput,
+  }
+}
@@ -0,0 +1,1101 @@
+//! Snappy framing format (`x-snappy-framed`)
+//!
+//! Raw snappy blocks are chunked into a stream, so inputs of any size can be handled.
+//! See [framing_format.txt](https://github.com/google/snappy/blob/main/framing_format.txt)
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, IoSlice, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::ops::Range;
+
+use crate::block::{Codec, CodecRef, Snappy};
+use crate::crc32c::masked_crc32c;
+use crate::format::Format;
+use crate::seek::{FrameIndex, SeekableDecoder};
+use crate::{compress_into_with_options, decompress, decompress_len, snappy_max_compressed_length, snappy_uncompressed_length, status, CompressionOptions, SnappyError};
+
+/// Stream identifier chunk, starts every framed stream
+pub const STREAM_IDENTIFIER: &[u8] = b"\xff\x06\x00\x00sNaPpY";
+
+/// Max uncompressed length of data in one chunk
+pub const MAX_BLOCK_SIZE: usize = 65536;
+/// Min block size `FrameEncoder` accepts
+pub const MIN_BLOCK_SIZE: usize = 1024;
+
+/// Chunk type of compressed data
+pub const CHUNK_COMPRESSED: u8 = 0x00;
+/// Chunk type of uncompressed data
+pub const CHUNK_UNCOMPRESSED: u8 = 0x01;
+/// Chunk type of padding, skipped by decoders
+pub const CHUNK_PADDING: u8 = 0xfe;
+/// Chunk type of the stream identifier
+pub const CHUNK_STREAM_IDENTIFIER: u8 = 0xff;
+
+/// Length of chunk header: 1 byte type and 3 bytes little-endian length
+pub const CHUNK_HEADER_SIZE: usize = 4;
+/// Max length of chunk body, limited by the 3 bytes length field
+pub const MAX_CHUNK_LEN: usize = (1 << 24) - 1;
+/// Min frame size cap `FrameEncoder` accepts: stream identifier, or a data chunk of 1 byte
+pub const MIN_FRAME_SIZE: usize = 10;
+
+/// Whether chunks of type `kind` are to be skipped by decoders: reserved skippable chunks (0x80-0xfd) and padding
+pub fn is_skippable(kind: u8) -> bool { (0x80..=CHUNK_PADDING).contains(&kind) }
+
+/// Whether `tag` can be used for metadata: skippable chunk types except padding
+pub fn is_metadata_tag(tag: u8) -> bool { (0x80..CHUNK_PADDING).contains(&tag) }
+
+/// Application metadata, e.g. original file name or mtime, stored as a skippable chunk
+///
+/// Other decoders just skip it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+  /// Chunk type, see `is_metadata_tag`
+  pub tag: u8,
+  pub data: Vec<u8>,
+}
+
+/// Options of `FrameEncoder`, builder style
+///
+/// The same options go to the streaming, parallel, async, copy and file encoders; `copy_compress`, `encode_all` and
+/// the file functions also pick the format and threads from them.
+///
+/// ```
+/// # use snappy::frame::{EncoderOptions, FrameEncoder};
+/// let encoder = FrameEncoder::with_options(Vec::new(), EncoderOptions::new().block_size(4096)).unwrap();
+/// let options = EncoderOptions::new().block_size(32 * 1024).parallel(4);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncoderOptions {
+  block_size: usize,
+  checksum: bool,
+  index: bool,
+  max_frame_size: Option<usize>,
+  store_threshold: u8,
+  compression: CompressionOptions,
+  threads: usize,
+  format: Format,
+  codec: Option<CodecRef>,
+}
+
+impl EncoderOptions {
+  pub fn new() -> Self {
+    EncoderOptions {
+      block_size: MAX_BLOCK_SIZE, checksum: true, index: false, max_frame_size: None, store_threshold: 88,
+      compression: CompressionOptions::new(), threads: 1, format: Format::Framed, codec: None,
+    }
+  }
+
+  /// Uncompressed length of data in each chunk, from `MIN_BLOCK_SIZE` to `MAX_BLOCK_SIZE` (default)
+  ///
+  /// Smaller blocks lower latency at the cost of compression ratio.
+  pub fn block_size(mut self, block_size: usize) -> Self { self.block_size = block_size; self }
+
+  /// Compute CRC-32C of each block, on by default
+  ///
+  /// When off, zero is stored as checksum: the stream is only readable by decoders not verifying checksums.
+  pub fn checksum(mut self, checksum: bool) -> Self { self.checksum = checksum; self }
+
+  /// Build a `FrameIndex` of the data chunks written, off by default, see `FrameEncoder::index`
+  pub fn index(mut self, index: bool) -> Self { self.index = index; self }
+
+  /// Cap every chunk, header included, at `size` bytes, e.g. 1400 to send each as a single UDP datagram
+  ///
+  /// Blocks compressing over the cap are split in halves until each fits, or stored uncompressed;
+  /// metadata and padding over it are refused. Must be at least `MIN_FRAME_SIZE`.
+  pub fn max_frame_size(mut self, size: usize) -> Self { self.max_frame_size = Some(size); self }
+
+  /// Store blocks uncompressed when compressed data is over `percent` of their length, 88 (about 7/8) by default
+  ///
+  /// Incompressible blocks are then decoded with a plain copy. 100 stores only blocks that compression grows, 255 none.
+  pub fn store_threshold(mut self, percent: u8) -> Self { self.store_threshold = percent; self }
+
+  /// Options of compressing each block, e.g. its level
+  pub fn compression(mut self, options: CompressionOptions) -> Self { self.compression = options; self }
+
+  /// Compress blocks on `threads` worker threads, 1 (the calling thread) by default
+  ///
+  /// Taken by `copy_compress` and what is built on it, which then use `ParallelFrameEncoder` for framed streams.
+  pub fn parallel(mut self, threads: usize) -> Self { self.threads = threads; self }
+
+  /// Format written by `copy_compress` and what is built on it, `Format::Framed` by default
+  ///
+  /// Blocks of Hadoop and snappy-java streams are of `block_size` too. The encoder types each write their own format.
+  pub fn format(mut self, format: Format) -> Self { self.format = format; self }
+
+  /// Compress blocks with `codec` instead of snappy, for custom formats, see `block`
+  ///
+  /// The store threshold still applies, `compression` is for snappy only.
+  pub fn codec(mut self, codec: &'static dyn Codec) -> Self { self.codec = Some(CodecRef(codec)); self }
+
+  pub(crate) fn threads(&self) -> usize { self.threads }
+  pub(crate) fn output_format(&self) -> Format { self.format }
+  #[cfg(feature = "mmap")]
+  pub(crate) fn frame_size(&self) -> Option<usize> { self.max_frame_size }
+
+  /// Max length of `len` bytes of data compressed by the codec
+  pub(crate) fn max_compressed_len(&self, len: usize) -> usize {
+    match self.codec {
+      Some(codec) => codec.0.max_len(len),
+      None => unsafe { snappy_max_compressed_length(len) },
+    }
+  }
+
+  /// Check the options against the limits of framing format
+  pub fn validate(&self) -> Result<(), SnappyError> {
+    if !(MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&self.block_size) { return Err(SnappyError::BadBlockSize(self.block_size)) }
+    match self.max_frame_size {
+      Some(size) if size < MIN_FRAME_SIZE => return Err(SnappyError::BadFrameSize(size)),
+      _ => (),
+    }
+    Ok(())
+  }
+}
+
+impl Default for EncoderOptions {
+  fn default() -> Self { EncoderOptions::new() }
+}
+
+/// Options of `FrameDecoder`, builder style
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecoderOptions {
+  verify_checksum: bool,
+  read_metadata: bool,
+  multi_stream: bool,
+  lenient: bool,
+  max_output_size: Option<u64>,
+  threads: usize,
+  format: Format,
+  codec: Option<CodecRef>,
+}
+
+impl DecoderOptions {
+  pub fn new() -> Self {
+    DecoderOptions {
+      verify_checksum: true, read_metadata: false, multi_stream: true, lenient: false, max_output_size: None, threads: 1,
+      format: Format::Framed, codec: None,
+    }
+  }
+
+  /// Verify CRC-32C of each block, on by default
+  ///
+  /// Turn off only for trusted input, e.g. read from storage that has checksums itself.
+  pub fn verify_checksum(mut self, verify: bool) -> Self { self.verify_checksum = verify; self }
+
+  /// Keep metadata chunks met for `FrameDecoder::metadata`, off by default so they are just skipped
+  pub fn read_metadata(mut self, read: bool) -> Self { self.read_metadata = read; self }
+
+  /// Decode concatenated streams as one (like `cat a.sz b.sz`), on by default
+  ///
+  /// When off, decoding ends at the stream identifier of the second stream.
+  /// With `FrameDecoder::from_bufread` that identifier is left unread, so a new decoder can go on with the next stream.
+  pub fn multi_stream(mut self, multi: bool) -> Self { self.multi_stream = multi; self }
+
+  /// Recover from corruption instead of failing, off by default
+  ///
+  /// After a bad chunk, the decoder scans forward byte by byte until a stream identifier or a data chunk
+  /// with valid checksum, and resumes there. Skipped byte ranges are reported by `FrameDecoder::skipped`.
+  /// Chunks are always copied out of `BufRead` in this mode, skippable ones included.
+  pub fn lenient(mut self, lenient: bool) -> Self { self.lenient = lenient; self }
+
+  /// Fail with `OutputLimitExceeded` rather than decode more than `size` bytes of data in all, no limit by default
+  ///
+  /// Checked before each block is handed out, so a stream of untrusted origin never makes callers buffer more;
+  /// `decompress_with_limit` at the crate root is the same for raw blocks.
+  pub fn max_output_size(mut self, size: u64) -> Self { self.max_output_size = Some(size); self }
+
+  /// Decompress chunks on `threads` worker threads, 1 (the calling thread) by default
+  ///
+  /// Taken by `copy_decompress` and what is built on it, which then use `ParallelFrameDecoder` for framed streams;
+  /// not with `lenient`, `verify_checksum(false)` or `multi_stream(false)`, which only `FrameDecoder` applies.
+  pub fn parallel(mut self, threads: usize) -> Self { self.threads = threads; self }
+
+  /// Format read by `copy_decompress` and what is built on it, `Format::Framed` by default
+  pub fn format(mut self, format: Format) -> Self { self.format = format; self }
+
+  /// Decompress blocks with `codec` instead of snappy, for streams written with `EncoderOptions::codec`
+  pub fn codec(mut self, codec: &'static dyn Codec) -> Self { self.codec = Some(CodecRef(codec)); self }
+
+  pub(crate) fn threads(&self) -> usize { self.threads }
+
+  /// Threads `copy_decompress` decodes with: 1 when options only `FrameDecoder` applies are set
+  pub(crate) fn decoding_threads(&self) -> usize {
+    if self.lenient || !self.verify_checksum || !self.multi_stream { 1 } else { self.threads }
+  }
+  pub(crate) fn input_format(&self) -> Format { self.format }
+  pub(crate) fn output_limit(&self) -> u64 { self.max_output_size.unwrap_or(u64::MAX) }
+  pub(crate) fn custom_codec(&self) -> Option<&'static dyn Codec> { self.codec.map(|codec| codec.0) }
+  pub(crate) fn block_codec(&self) -> &'static dyn Codec { self.custom_codec().unwrap_or(&Snappy) }
+
+  /// Check `len` bytes of data decoded in all are within `max_output_size`
+  pub(crate) fn check_output_len(&self, len: u64) -> Result<(), SnappyError> {
+    match self.max_output_size {
+      Some(size) if len > size => Err(SnappyError::OutputLimitExceeded),
+      _ => Ok(()),
+    }
+  }
+}
+
+impl Default for DecoderOptions {
+  fn default() -> Self { DecoderOptions::new() }
+}
+
+/// Counts from `FrameEncoder::stats`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EncoderStats {
+  /// Blocks written as compressed data chunks
+  pub compressed_blocks: usize,
+  /// Blocks written as uncompressed data chunks, see `EncoderOptions::store_threshold`
+  pub stored_blocks: usize,
+  /// Length of data written
+  pub uncompressed_len: u64,
+  /// Length of the framed stream written
+  pub stream_len: u64,
+}
+
+/// Framing format encoder, writes chunks to `W`
+///
+/// Input is split into blocks of the configured block size, each compressed as one data chunk;
+/// stream identifier is written before the first chunk.
+/// Clones go on with the same stream into their own copy of the writer; see `with_writer` for a new stream.
+#[derive(Clone)]
+pub struct FrameEncoder<W: Write> {
+  inner: W,
+  options: EncoderOptions,
+  header_written: bool,
+  /// Length of the stream written so far
+  offset: u64,
+  /// Index of data chunks, with `EncoderOptions::index`
+  index: Option<FrameIndex>,
+  stats: EncoderStats,
+  /// Scratch space for one chunk
+  chunk: Vec<u8>,
+}
+
+impl<W: Write> FrameEncoder<W> {
+  pub fn new(inner: W) -> Self {
+    FrameEncoder::with_options(inner, EncoderOptions::new()).unwrap()
+  }
+
+  /// Encoder with `options`, which are validated first
+  pub fn with_options(inner: W, options: EncoderOptions) -> Result<Self, SnappyError> {
+    options.validate()?;
+    let chunk_capacity = CHUNK_HEADER_SIZE + 4 + options.max_compressed_len(options.block_size);
+    let index = if options.index { Some(FrameIndex::new()) } else { None };
+    Ok(FrameEncoder { inner, options, header_written: false, offset: 0, index, stats: EncoderStats::default(), chunk: vec![0; chunk_capacity] })
+  }
+
+  /// Encoder of chunks following a stream identifier written elsewhere, e.g. by another encoder
+  pub(crate) fn mid_stream(inner: W, options: EncoderOptions) -> Result<Self, SnappyError> {
+    let mut encoder = FrameEncoder::with_options(inner, options)?;
+    encoder.header_written = true;
+    Ok(encoder)
+  }
+
+  /// New stream into `inner`, with the options and scratch size of this encoder, e.g. one per connection from a prototype
+  pub fn with_writer<V: Write>(&self, inner: V) -> FrameEncoder<V> {
+    let index = if self.options.index { Some(FrameIndex::new()) } else { None };
+    FrameEncoder {
+      inner, options: self.options, header_written: false, offset: 0, index, stats: EncoderStats::default(), chunk: vec![0; self.chunk.len()],
+    }
+  }
+
+  /// Uncompressed length of data in each chunk
+  pub fn block_size(&self) -> usize { self.options.block_size }
+
+  /// Compress `data` into data chunks, each holding at most `block_size` bytes of it
+  pub fn write_data(&mut self, data: &[u8]) -> io::Result<()> {
+    for block in data.chunks(self.options.block_size) { self.write_block(block)? }
+    Ok(())
+  }
+
+  /// Write one compressed data chunk of `block`, no longer than `block_size`
+  ///
+  /// Stream identifier, chunk header and body go out in one vectored write; stored blocks are not copied.
+  fn write_block(&mut self, block: &[u8]) -> io::Result<()> {
+    debug_assert!(block.len() <= self.options.block_size);
+
+    let body = CHUNK_HEADER_SIZE + 4;
+    let compressed_len = match self.options.codec {
+      Some(codec) => codec.0.compress_block(block, &mut self.chunk[body..]).map_err(io::Error::other)?,
+      None => compress_into_with_options(block, &mut self.chunk[body..], self.options.compression)
+        .expect("chunk buffer of max compressed length is always enough"),
+    };
+
+    // decoders refuse compressed chunks over the snappy bound, whatever the codec
+    let mut store = compressed_len * 100 > block.len() * self.options.store_threshold as usize || 4 + compressed_len > max_compressed_chunk_len();
+    if let Some(max_frame_size) = self.options.max_frame_size {
+      if body + compressed_len > max_frame_size && body + block.len() <= max_frame_size {
+        store = true;
+      } else if body + if store { block.len() } else { compressed_len } > max_frame_size {
+        let (first, second) = block.split_at(block.len() / 2);
+        self.write_block(first)?;
+        return self.write_block(second)
+      }
+    }
+    let (kind, len) = if store {
+      self.stats.stored_blocks += 1;
+      (CHUNK_UNCOMPRESSED, block.len())
+    } else {
+      self.stats.compressed_blocks += 1;
+      (CHUNK_COMPRESSED, compressed_len)
+    };
+
+    write_chunk_header(&mut self.chunk, kind, 4 + len);
+    let crc = if self.options.checksum { masked_crc32c(block) } else { 0 };
+    self.chunk[CHUNK_HEADER_SIZE..body].copy_from_slice(&crc.to_le_bytes());
+    let identifier = self.take_stream_identifier();
+    let (head, data) = if store { (&self.chunk[..body], block) } else { (&self.chunk[..body + len], &[][..]) };
+    write_all_vectored(&mut self.inner, &mut [IoSlice::new(identifier), IoSlice::new(head), IoSlice::new(data)])?;
+
+    if let Some(ref mut index) = self.index { index.push(self.offset, block.len()) }
+    self.offset += (body + len) as u64;
+    self.stats.uncompressed_len += block.len() as u64;
+    Ok(())
+  }
+
+  /// Refuse a chunk of `len` bytes in total over `max_frame_size`
+  fn check_frame_size(&self, len: usize) -> io::Result<()> {
+    match self.options.max_frame_size {
+      Some(max) if len > max => Err(io::Error::new(io::ErrorKind::InvalidInput, "chunk over max frame size")),
+      _ => Ok(()),
+    }
+  }
+
+  /// Write a padding chunk of `len` bytes in total (header included), e.g. to align the stream for direct I/O
+  ///
+  /// `len` must be from `CHUNK_HEADER_SIZE` to `CHUNK_HEADER_SIZE + MAX_CHUNK_LEN`.
+  pub fn write_padding(&mut self, len: usize) -> io::Result<()> {
+    if !(CHUNK_HEADER_SIZE..=CHUNK_HEADER_SIZE + MAX_CHUNK_LEN).contains(&len) {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, "padding length out of chunk range"))
+    }
+    self.check_frame_size(len)?;
+
+    let mut header = [0u8; CHUNK_HEADER_SIZE];
+    write_chunk_header(&mut header, CHUNK_PADDING, len - CHUNK_HEADER_SIZE);
+    let identifier = self.take_stream_identifier();
+    write_all_vectored(&mut self.inner, &mut [IoSlice::new(identifier), IoSlice::new(&header)])?;
+    io::copy(&mut io::repeat(0).take((len - CHUNK_HEADER_SIZE) as u64), &mut self.inner)?;
+    self.offset += len as u64;
+    Ok(())
+  }
+
+  /// Write metadata `data` as a skippable chunk of type `tag`, from 0x80 to 0xfd
+  pub fn write_metadata(&mut self, tag: u8, data: &[u8]) -> io::Result<()> {
+    if !is_metadata_tag(tag) { return Err(io::Error::new(io::ErrorKind::InvalidInput, "metadata tag out of 0x80..=0xfd")) }
+    if data.len() > MAX_CHUNK_LEN { return Err(io::Error::new(io::ErrorKind::InvalidInput, "metadata too long for a chunk")) }
+    self.check_frame_size(CHUNK_HEADER_SIZE + data.len())?;
+
+    let mut header = [0u8; CHUNK_HEADER_SIZE];
+    write_chunk_header(&mut header, tag, data.len());
+    let identifier = self.take_stream_identifier();
+    write_all_vectored(&mut self.inner, &mut [IoSlice::new(identifier), IoSlice::new(&header), IoSlice::new(data)])?;
+    self.offset += (CHUNK_HEADER_SIZE + data.len()) as u64;
+    Ok(())
+  }
+
+  /// Stream identifier to write before the next chunk, empty if written already
+  fn take_stream_identifier(&mut self) -> &'static [u8] {
+    if self.header_written { return &[] }
+    self.header_written = true;
+    self.offset += STREAM_IDENTIFIER.len() as u64;
+    STREAM_IDENTIFIER
+  }
+
+  /// Write stream identifier if not yet
+  fn write_stream_identifier(&mut self) -> io::Result<()> {
+    let identifier = self.take_stream_identifier();
+    self.inner.write_all(identifier)
+  }
+
+  /// Flush the inner writer
+  pub fn flush(&mut self) -> io::Result<()> { self.inner.flush() }
+
+  /// Make everything written so far decodable on the other side of a pipe or socket: the stream identifier is
+  /// written if not yet, then the inner writer flushed
+  ///
+  /// Every `write_data` call already ends its last block, so no data is held here.
+  pub fn sync_flush(&mut self) -> io::Result<()> {
+    self.write_stream_identifier()?;
+    self.inner.flush()
+  }
+
+  /// Counts of the data written so far
+  pub fn stats(&self) -> EncoderStats { EncoderStats { stream_len: self.offset, ..self.stats } }
+
+  /// Index of data chunks written so far, with `EncoderOptions::index`
+  ///
+  /// Chunk offsets count from where the encoder started writing.
+  pub fn index(&self) -> Option<&FrameIndex> { self.index.as_ref() }
+
+  pub fn get_ref(&self) -> &W { &self.inner }
+  pub fn get_mut(&mut self) -> &mut W { &mut self.inner }
+
+  /// Get back the inner writer, stream identifier is written first so even empty input is a valid stream
+  pub fn into_inner(mut self) -> io::Result<W> {
+    self.write_stream_identifier()?;
+    Ok(self.inner)
+  }
+
+  /// End the stream: write the stream identifier if not yet, flush, and get back the inner writer
+  ///
+  /// The encoder is consumed, so writing after the end is caught at compile time:
+  ///
+  /// ```compile_fail
+  /// # use snappy::FrameEncoder;
+  /// let mut encoder = FrameEncoder::new(Vec::new());
+  /// let stream = encoder.finish().unwrap();
+  /// encoder.write_data(b"too late").unwrap();
+  /// ```
+  pub fn finish(mut self) -> io::Result<W> {
+    self.sync_flush()?;
+    Ok(self.inner)
+  }
+}
+
+impl<W: Read + Write + Seek> FrameEncoder<W> {
+  /// Encoder going on with the framed stream already in `file`, e.g. a compressed log
+  ///
+  /// The whole existing stream is checked to be well-framed, and the last data chunk to match its checksum,
+  /// so records are never appended after a torn write; new chunks are written at the end, nothing is rewritten.
+  /// An empty `file` gets a new stream.
+  pub fn append(file: W) -> io::Result<Self> { FrameEncoder::append_with_options(file, EncoderOptions::new()) }
+
+  /// `append` with `options`; with `EncoderOptions::index`, the existing chunks are indexed too
+  ///
+  /// With `EncoderOptions::codec`, every existing data chunk is decompressed by it and checked, as only the codec
+  /// knows their data lengths.
+  pub fn append_with_options(mut file: W, options: EncoderOptions) -> io::Result<Self> {
+    let offset = file.seek(SeekFrom::End(0))?;
+    file.seek(SeekFrom::Start(0))?;
+    let index = match options.codec {
+      Some(_) if offset == 0 => FrameIndex::new(),
+      Some(codec) => scan_with_codec(&mut file, codec.0)?,
+      None => {
+        let index = FrameIndex::scan(&mut file)?;
+        if index.uncompressed_len() != 0 {
+          let mut tail = SeekableDecoder::with_index(&mut file, index.clone());
+          tail.seek(SeekFrom::End(-1))?;
+          tail.read_exact(&mut [0])?;
+        }
+        index
+      },
+    };
+    file.seek(SeekFrom::End(0))?;
+
+    let mut encoder = FrameEncoder::with_options(file, options)?;
+    encoder.offset = offset;
+    encoder.header_written = offset != 0;
+    if options.index { encoder.index = Some(index) }
+    Ok(encoder)
+  }
+}
+
+/// Index of the framed stream of `file` compressed by `codec`, every data chunk decompressed and checked
+fn scan_with_codec<R: Read>(file: R, codec: &'static dyn Codec) -> io::Result<FrameIndex> {
+  let mut index = FrameIndex::new();
+  for frame in Frames::with_codec(file, codec) {
+    let frame = frame?;
+    if frame.is_data() { index.push(frame.offset, frame.decompress_with(codec).map_err(invalid)?.len()) }
+  }
+  Ok(index)
+}
+
+/// Fill `chunk[..4]` with chunk header of type `kind` and data length `len`
+fn write_chunk_header(chunk: &mut [u8], kind: u8, len: usize) {
+  assert!(len < 1 << 24, "chunk length over 3 bytes");
+  chunk[0] = kind;
+  chunk[1..CHUNK_HEADER_SIZE].copy_from_slice(&(len as u32).to_le_bytes()[..3]);
+}
+
+/// `Write::write_all` of several buffers, with as few `write_vectored` calls as the writer allows
+fn write_all_vectored<W: Write>(writer: &mut W, mut bufs: &mut [IoSlice<'_>]) -> io::Result<()> {
+  IoSlice::advance_slices(&mut bufs, 0);
+  while !bufs.is_empty() {
+    match writer.write_vectored(bufs) {
+      Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+      Ok(n) => IoSlice::advance_slices(&mut bufs, n),
+      Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+      Err(e) => return Err(e),
+    }
+  }
+  Ok(())
+}
+
+/// Max length of compressed data chunk body, checksum included
+pub(crate) fn max_compressed_chunk_len() -> usize { 4 + unsafe { snappy_max_compressed_length(MAX_BLOCK_SIZE) } }
+
+/// Framing format decoder, reads chunks from `R`
+///
+/// Stream identifier and checksum of every data chunk are verified, data is served one block at a time.
+/// Clones go on from the same point of the stream; see `with_reader` for a new stream.
+#[derive(Clone)]
+pub struct FrameDecoder<R: Read> {
+  inner: R,
+  /// `BufRead` methods of `inner`, see `from_bufread`
+  bufread: Option<BufReadFns<R>>,
+  options: DecoderOptions,
+  header_read: bool,
+  /// End of the first stream met, without `multi_stream`
+  ended: bool,
+  /// Offset of the next chunk in stream
+  offset: u64,
+  /// Length of data decoded so far
+  output_len: u64,
+  /// Data chunks decoded so far
+  data_chunks: u64,
+  /// Body of the current chunk, when not borrowed from `inner`
+  chunk: Vec<u8>,
+  /// Decompressed data of the current chunk
+  block: Vec<u8>,
+  block_len: usize,
+  /// Metadata met but not taken yet
+  metadata: VecDeque<Metadata>,
+  /// Lenient mode: raw bytes of the chunk being read, header and body length in `chunk`
+  raw_header: [u8; CHUNK_HEADER_SIZE],
+  raw_len: (usize, usize),
+  /// Lenient mode: bytes read from `inner` to be scanned again
+  pending: VecDeque<u8>,
+  /// Lenient mode: start offset of the corrupted bytes being skipped
+  recovering: Option<u64>,
+  /// Lenient mode: ranges skipped but not taken yet
+  skipped: VecDeque<Range<u64>>,
+}
+
+/// `BufRead` methods, kept as function pointers so `FrameDecoder<R>` needs only `R: Read`
+struct BufReadFns<R> {
+  fill_buf: fn(&mut R) -> io::Result<&[u8]>,
+  consume: fn(&mut R, usize),
+}
+
+impl<R> Clone for BufReadFns<R> {
+  fn clone(&self) -> Self { BufReadFns { fill_buf: self.fill_buf, consume: self.consume } }
+}
+
+impl<R: Read> FrameDecoder<R> {
+  pub fn new(inner: R) -> Self { FrameDecoder::with_options(inner, DecoderOptions::new()) }
+
+  /// Decoder with `options`
+  pub fn with_options(inner: R, options: DecoderOptions) -> Self {
+    FrameDecoder { inner, bufread: None, options, header_read: false, ended: false, offset: 0, output_len: 0, data_chunks: 0,
+      chunk: vec![0; max_compressed_chunk_len()], block: vec![0; MAX_BLOCK_SIZE], block_len: 0, metadata: VecDeque::new(),
+      raw_header: [0; CHUNK_HEADER_SIZE], raw_len: (0, 0), pending: VecDeque::new(), recovering: None, skipped: VecDeque::new() }
+  }
+
+  /// New stream from `inner`, with the options of this decoder, e.g. one per connection from a prototype
+  ///
+  /// A decoder made by `from_bufread` gives one reading `inner` as plain `Read`.
+  pub fn with_reader<S: Read>(&self, inner: S) -> FrameDecoder<S> { FrameDecoder::with_options(inner, self.options) }
+
+  /// Decode next data chunk, `None` at the end of stream
+  ///
+  /// Returned data is borrowed from the decoder, valid until the next call.
+  pub fn read_block(&mut self) -> io::Result<Option<&[u8]>> {
+    self.block_len = 0;
+    if self.ended { return Ok(None) }
+    loop {
+      let chunk_start = self.offset;
+      let decoded = match self.read_buffered_chunk() {
+        Ok(Some(decoded)) => Ok(decoded),
+        Ok(None) => self.read_chunk(),
+        Err(e) => Err(e),
+      };
+
+      let decoded = match decoded {
+        Ok(decoded) => decoded,
+        Err(ref e) if self.options.lenient && is_corruption(e) => {
+          self.recovering.get_or_insert(chunk_start);
+          self.unread_chunk();
+          continue
+        },
+        Err(e) => return Err(e),
+      };
+      if let Some(start) = self.recovering.take() {
+        let end = if let ChunkRead::End = decoded { self.offset } else { chunk_start };
+        self.skipped.push_back(start..end);
+      }
+
+      match decoded {
+        ChunkRead::Block(len) => {
+          self.options.check_output_len(self.output_len + len as u64).map_err(invalid)?;
+          self.output_len += len as u64;
+          self.data_chunks += 1;
+          self.block_len = len;
+          return Ok(Some(self.current_block()))
+        },
+        ChunkRead::StreamIdentifier if self.header_read && !self.options.multi_stream => {
+          self.ended = true;
+          return Ok(None)
+        },
+        ChunkRead::StreamIdentifier => self.header_read = true,
+        ChunkRead::Skipped => (),
+        ChunkRead::End => return Ok(None),
+      }
+    }
+  }
+
+  /// Decode a chunk wholly inside the buffer of `BufRead` without copying it, `None` if not possible
+  fn read_buffered_chunk(&mut self) -> io::Result<Option<ChunkRead>> {
+    if self.options.lenient { return Ok(None) }
+    let fns = match self.bufread { Some(ref fns) => fns, None => return Ok(None) };
+    let buf = (fns.fill_buf)(&mut self.inner)?;
+    if buf.len() < CHUNK_HEADER_SIZE { return Ok(None) }
+
+    let (kind, len) = parse_chunk_header(buf);
+    let pos = ChunkPos { offset: self.offset, data_index: self.data_chunks };
+    check_chunk_header(kind, len, self.header_read, false, pos.offset).map_err(invalid)?;
+    // next stream is left unread
+    if kind == CHUNK_STREAM_IDENTIFIER && self.header_read && !self.options.multi_stream { return Ok(Some(ChunkRead::End)) }
+    if buf.len() < CHUNK_HEADER_SIZE + len { return Ok(None) }
+
+    if self.options.read_metadata && is_metadata_tag(kind) {
+      self.metadata.push_back(Metadata { tag: kind, data: buf[CHUNK_HEADER_SIZE..CHUNK_HEADER_SIZE + len].to_vec() });
+    }
+    let decoded = decode_chunk(kind, &buf[CHUNK_HEADER_SIZE..CHUNK_HEADER_SIZE + len], &mut self.block, self.options.verify_checksum, self.options.block_codec(), pos);
+    (fns.consume)(&mut self.inner, CHUNK_HEADER_SIZE + len);
+    self.offset += (CHUNK_HEADER_SIZE + len) as u64;
+    decoded.map(Some).map_err(invalid)
+  }
+
+  /// Read a chunk into `self.chunk` and decode it
+  fn read_chunk(&mut self) -> io::Result<ChunkRead> {
+    let resync = self.recovering.is_some();
+    let pos = ChunkPos { offset: self.offset, data_index: self.data_chunks };
+    let mut header = [0u8; CHUNK_HEADER_SIZE];
+    self.raw_len = (self.read_bytes(&mut header)?, 0);
+    self.raw_header = header;
+    match self.raw_len.0 {
+      0 => return if self.header_read || resync { Ok(ChunkRead::End) } else { Err(invalid(SnappyError::BadStreamIdentifier)) },
+      CHUNK_HEADER_SIZE => (),
+      n => return Err(invalid(truncated(n, CHUNK_HEADER_SIZE))),
+    }
+
+    let (kind, len) = parse_chunk_header(&header);
+    check_chunk_header(kind, len, self.header_read, resync, pos.offset).map_err(invalid)?;
+
+    if self.options.read_metadata && is_metadata_tag(kind) && !self.options.lenient {
+      let mut data = vec![0; len];
+      let n = self.read_bytes(&mut data)?;
+      if n != len { return Err(invalid(truncated(n, len))) }
+      self.metadata.push_back(Metadata { tag: kind, data });
+      return Ok(ChunkRead::Skipped)
+    }
+    if is_skippable(kind) && !self.options.lenient {
+      let mut skipped = 0;
+      let mut buf = [0u8; 512];
+      while skipped < len {
+        let n = self.read_bytes(&mut buf[..(len - skipped).min(512)])?;
+        if n == 0 { return Err(invalid(truncated(skipped, len))) }
+        skipped += n;
+      }
+      return Ok(ChunkRead::Skipped)
+    }
+
+    // lenient mode keeps skippable bodies too, to scan them again if cut short by a corrupted length
+    if self.chunk.len() < len { self.chunk.resize(len, 0) }
+    let mut chunk = mem::take(&mut self.chunk);
+    let read = self.read_bytes(&mut chunk[..len]);
+    self.chunk = chunk;
+    self.raw_len.1 = read?;
+    if self.raw_len.1 != len { return Err(invalid(truncated(self.raw_len.1, len))) }
+    if is_skippable(kind) {
+      if self.options.read_metadata && is_metadata_tag(kind) {
+        self.metadata.push_back(Metadata { tag: kind, data: self.chunk[..len].to_vec() });
+      }
+      return Ok(ChunkRead::Skipped)
+    }
+
+    decode_chunk(kind, &self.chunk[..len], &mut self.block, self.options.verify_checksum || resync, self.options.block_codec(), pos).map_err(invalid)
+  }
+
+  /// Read from `pending`, then `inner`, until `buf` is full or EOF
+  fn read_bytes(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = read_full(&mut self.pending, buf)?;
+    len += read_full(&mut self.inner, &mut buf[len..])?;
+    self.offset += len as u64;
+    Ok(len)
+  }
+
+  /// Push back the raw bytes of the chunk just read except its first one, to be scanned again
+  fn unread_chunk(&mut self) {
+    let (header_len, body_len) = mem::take(&mut self.raw_len);
+    let raw: Vec<u8> = self.raw_header[..header_len].iter().chain(&self.chunk[..body_len]).skip(1).copied().collect();
+    for &b in raw.iter().rev() { self.pending.push_front(b) }
+    self.offset -= (header_len + body_len).saturating_sub(1) as u64;
+  }
+
+  /// Take metadata chunks met so far, in stream order
+  ///
+  /// Only kept with `DecoderOptions::read_metadata`; metadata written before a block is met when that block is read.
+  pub fn metadata(&mut self) -> impl Iterator<Item = Metadata> + '_ { self.metadata.drain(..) }
+
+  /// Take byte ranges of the stream skipped as corrupted so far, with `DecoderOptions::lenient`
+  pub fn skipped(&mut self) -> impl Iterator<Item = Range<u64>> + '_ { self.skipped.drain(..) }
+
+  /// Length of the stream decoded so far
+  pub fn offset(&self) -> u64 { self.offset }
+
+  /// Data of the last block read
+  pub(crate) fn current_block(&self) -> &[u8] { &self.block[..self.block_len] }
+
+  /// Decode all the remaining data, appended to `output`; returns its length
+  pub fn read_to_end(&mut self, output: &mut Vec<u8>) -> io::Result<usize> {
+    let start = output.len();
+    while let Some(block) = self.read_block()? { output.extend_from_slice(block) }
+    Ok(output.len() - start)
+  }
+
+  pub fn get_ref(&self) -> &R { &self.inner }
+  pub fn get_mut(&mut self) -> &mut R { &mut self.inner }
+  pub fn into_inner(self) -> R { self.inner }
+}
+
+impl<R: BufRead> FrameDecoder<R> {
+  /// Decoder over a `BufRead`, chunks wholly inside its buffer are decoded without copying
+  pub fn from_bufread(inner: R) -> Self { FrameDecoder::from_bufread_with_options(inner, DecoderOptions::new()) }
+
+  /// `from_bufread` with `options`
+  pub fn from_bufread_with_options(inner: R, options: DecoderOptions) -> Self {
+    let mut decoder = FrameDecoder::with_options(inner, options);
+    decoder.bufread = Some(BufReadFns { fill_buf: R::fill_buf, consume: R::consume });
+    decoder
+  }
+}
+
+/// Framed stream decoder fed by pushing input pieces, for callers doing I/O on their own (e.g. async adapters)
+///
+/// Input is gathered until a whole chunk is there, then decoded by a `FrameDecoder`. Chunk headers are checked
+/// as soon as they are pushed: unknown unskippable types and oversized data chunks fail without being buffered.
+#[derive(Clone)]
+pub struct PushDecoder {
+  /// Fed with whole chunks only
+  decoder: FrameDecoder<VecDeque<u8>>,
+  /// Input not yet a whole chunk
+  input: Vec<u8>,
+  /// Nothing was pushed yet
+  empty: bool,
+}
+
+impl PushDecoder {
+  pub fn new() -> Self { PushDecoder::with_options(DecoderOptions::new()) }
+
+  /// Decoder with `options`
+  pub fn with_options(options: DecoderOptions) -> Self {
+    PushDecoder { decoder: FrameDecoder::with_options(VecDeque::new(), options), input: Vec::new(), empty: true }
+  }
+
+  /// Append `input` to the stream
+  pub fn push(&mut self, input: &[u8]) {
+    self.input.extend_from_slice(input);
+    self.empty &= input.is_empty();
+  }
+
+  /// Decode next data chunk, `None` when more input is needed
+  pub fn next_block(&mut self) -> io::Result<Option<&[u8]>> {
+    loop {
+      if self.input.len() < CHUNK_HEADER_SIZE { return Ok(None) }
+      let (kind, len) = parse_chunk_header(&self.input);
+      // before gathering the body, lenient decoding left aside as it skips bad chunks itself
+      let decoder = &self.decoder;
+      if !decoder.ended && !decoder.options.lenient {
+        check_chunk_header(kind, len, decoder.header_read, false, decoder.offset).map_err(invalid)?;
+      }
+      let len = CHUNK_HEADER_SIZE + len;
+      if self.input.len() < len { return Ok(None) }
+      self.decoder.get_mut().extend(self.input.drain(..len));
+      if self.decoder.read_block()?.is_some() { return Ok(Some(self.decoder.current_block())) }
+    }
+  }
+
+  /// Data of the last block `next_block` decoded, kept until it is called again
+  pub fn current_block(&self) -> &[u8] { self.decoder.current_block() }
+
+  /// Check the end of input: some stream was pushed, no chunk is left partial
+  pub fn finish(&self) -> io::Result<()> {
+    if self.empty { return Err(invalid(SnappyError::BadStreamIdentifier)) }
+    if self.input.len() >= CHUNK_HEADER_SIZE {
+      return Err(invalid(truncated(self.input.len(), CHUNK_HEADER_SIZE + parse_chunk_header(&self.input).1)))
+    }
+    if !self.input.is_empty() { return Err(invalid(truncated(self.input.len(), CHUNK_HEADER_SIZE))) }
+    Ok(())
+  }
+}
+
+impl Default for PushDecoder {
+  fn default() -> Self { PushDecoder::new() }
+}
+
+/// One chunk of a framed stream, as met by `Frames`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+  /// Chunk type, e.g. `CHUNK_COMPRESSED`
+  pub kind: u8,
+  /// Offset of the chunk header in stream
+  pub offset: u64,
+  /// Body length, checksum included
+  pub compressed_len: usize,
+  /// Length of data, for data chunks
+  pub uncompressed_len: Option<usize>,
+  /// Masked CRC-32C stored in data chunks
+  pub checksum: Option<u32>,
+  /// Index among the data chunks of the stream, for data chunks
+  pub data_index: Option<u64>,
+  /// Chunk body as stored
+  pub body: Vec<u8>,
+}
+
+impl Frame {
+  /// Whether this is a compressed or uncompressed data chunk
+  pub fn is_data(&self) -> bool { self.kind == CHUNK_COMPRESSED || self.kind == CHUNK_UNCOMPRESSED }
+
+  /// Data of a data chunk, checksum verified; other chunks give `UnsupportedChunk`
+  ///
+  /// Data over `MAX_BLOCK_SIZE` gives `BadChunkHeader`, before anything is allocated for it.
+  pub fn decompress(&self) -> Result<Vec<u8>, SnappyError> {
+    self.decompress_data(|body| {
+      if decompress_len(body)? > MAX_BLOCK_SIZE { return Err(SnappyError::BadChunkHeader { offset: self.offset }) }
+      decompress(body)
+    })
+  }
+
+  /// `decompress` of a chunk compressed by `codec`, see `EncoderOptions::codec`
+  pub fn decompress_with(&self, codec: &dyn Codec) -> Result<Vec<u8>, SnappyError> {
+    self.decompress_data(|body| {
+      let mut data = vec![0; MAX_BLOCK_SIZE];
+      let len = codec.decompress_block(body, &mut data).map_err(|e| match e {
+        SnappyError::InsufficientBuffer => SnappyError::BadChunkHeader { offset: self.offset },
+        e => e,
+      })?;
+      data.truncate(len);
+      Ok(data)
+    })
+  }
+
+  fn decompress_data(&self, decompress: impl FnOnce(&[u8]) -> Result<Vec<u8>, SnappyError>) -> Result<Vec<u8>, SnappyError> {
+    let data = match self.kind {
+      CHUNK_COMPRESSED => decompress(&self.body[4..])?,
+      CHUNK_UNCOMPRESSED => self.body[4..].to_vec(),
+      kind => return Err(SnappyError::UnsupportedChunk(kind)),
+    };
+    let (expected, actual) = (self.checksum.unwrap_or(0), masked_crc32c(&data));
+    if actual != expected {
+      return Err(SnappyError::ChecksumMismatch { chunk_index: self.data_index.unwrap_or(0), expected, actual })
+    }
+    Ok(data)
+  }
+}
+
+/// Iterator over the chunks of a framed stream, without decompressing them
+///
+/// Chunk headers are checked like `FrameDecoder` does, the first error ends iteration.
+pub struct Frames<R: Read> {
+  inner: R,
+  /// Codec of compressed chunks, when not snappy
+  codec: Option<&'static dyn Codec>,
+  offset: u64,
+  data_chunks: u64,
+  header_read: bool,
+  done: bool,
+}
+
+impl<R: Read> Frames<R> {
+  pub fn new(inner: R) -> Self { Frames { inner, codec: None, offset: 0, data_chunks: 0, header_read: false, done: false } }
+
+  /// Chunks of a stream compressed by `codec`, see `EncoderOptions::codec`
+  ///
+  /// Their data length is only known once decompressed: `uncompressed_len` of compressed chunks is `None`.
+  pub fn with_codec(inner: R, codec: &'static dyn Codec) -> Self { Frames { codec: Some(codec), ..Frames::new(inner) } }
+
+  fn read_frame(&mut self) -> io::Result<Option<Frame>> {
+    let mut header = [0u8; CHUNK_HEADER_SIZE];
+    match read_full(&mut self.inner, &mut header)? {
+      0 if self.header_read => return Ok(None),
+      0 => return Err(invalid(SnappyError::BadStreamIdentifier)),
+      CHUNK_HEADER_SIZE => (),
+      n => return Err(invalid(truncated(n, CHUNK_HEADER_SIZE))),
+    }
+    let (kind, len) = parse_chunk_header(&header);
+    check_chunk_header(kind, len, self.header_read, false, self.offset).map_err(invalid)?;
+
+    let mut body = vec![0; len];
+    let n = read_full(&mut self.inner, &mut body)?;
+    if n != len { return Err(invalid(truncated(n, len))) }
+    let (uncompressed_len, checksum) = match kind {
+      CHUNK_STREAM_IDENTIFIER => {
+        if body != STREAM_IDENTIFIER[CHUNK_HEADER_SIZE..] { return Err(invalid(SnappyError::BadStreamIdentifier)) }
+        self.header_read = true;
+        (None, None)
+      },
+      CHUNK_COMPRESSED if self.codec.is_some() => (None, Some(u32::from_le_bytes([body[0], body[1], body[2], body[3]]))),
+      CHUNK_COMPRESSED => {
+        let mut data_len = 0;
+        unsafe { status(snappy_uncompressed_length(body[4..].as_ptr(), len - 4, &mut data_len)) }.map_err(invalid)?;
+        if data_len > MAX_BLOCK_SIZE { return Err(invalid(SnappyError::BadChunkHeader { offset: self.offset })) }
+        (Some(data_len), Some(u32::from_le_bytes([body[0], body[1], body[2], body[3]])))
+      },
+      CHUNK_UNCOMPRESSED => (Some(len - 4), Some(u32::from_le_bytes([body[0], body[1], body[2], body[3]]))),
+      _ => (None, None),
+    };
+
+    let data_index = checksum.map(|_| self.data_chunks);
+    self.data_chunks += data_index.is_some() as u64;
+    let frame = Frame { kind, offset: self.offset, compressed_len: len, uncompressed_len, checksum, data_index, body };
+    self.offset += (CHUNK_HEADER_SIZE + len) as u64;
+    Ok(Some(frame))
+  }
+
+  pub fn get_ref(&self) -> &R { &self.inner }
+  pub fn into_inner(self) -> R { self.inner }
+}
+
+impl<R: Read> Iterator for Frames<R> {
+  type Item = io::Result<Frame>;
+
+  fn next(&mut self) -> Option<io::Result<Frame>> {
+    if self.done { return None }
+    let frame = self.read_frame().transpose();
+    if !matches!(frame, Some(Ok(_))) { self.done = true }
+    frame
+  }
+}
+
+/// Counts from `validate_frames`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamStats {
+  /// Stream identifiers, one per concatenated stream
+  pub streams: usize,
+  pub data_chunks: usize,
+  /// Skippable chunks, padding and metadata
+  pub skipped_chunks: usize,
+  /// Length of the framed stream
+  pub compressed_len: u64,
+  /// Length of the data it holds
+  pub uncompressed_len: u64,
+}
+
+/// Check a framed stream for integrity: chunk structure and checksums, like `snappy_validate_compressed_buffer` for blocks
+///
+/// Every data chunk is decompressed into the same block buffer for its CRC-32C, output is never kept;
+/// errors are `InvalidData` holding a `SnappyError`, like `FrameDecoder` gives.
+pub fn validate_frames<R: Read>(mut reader: R) -> io::Result<StreamStats> {
+  let mut stats = StreamStats::default();
+  let mut chunk = vec![0; max_compressed_chunk_len()];
+  let mut block = vec![0; MAX_BLOCK_SIZE];
+  loop {
+    let mut header = [0u8; CHUNK_HEADER_SIZE];
+    match read_full(&mut reader, &mut header)? {
+      0 if stats.streams != 0 => return Ok(stats),
+      0 => return Err(invalid(SnappyError::BadStreamIdentifier)),
+      CHUNK_HEADER_SIZE => (),
+      n => return Err(invalid(truncated(n, CHUNK_HEADER_SIZE))),
+    }
+    let (kind, len) = parse_chunk_header(&header);
+    let pos = ChunkPos { offset: stats.compressed_len, data_index: stats.data_chunks as u64 };
+    check_chunk_header(kind, len, stats.streams != 0, false, pos.offset).map_err(invalid)?;
+
+    if is_skippable(kind) {
+      let n = io::copy(&mut reader.by_ref().take(len as u64), &mut io::sink())? as usize;
+      if n != len { return Err(invalid(truncated(n, len))) }
+      stats.skipped_chunks += 1;
+    } else {
+      let n = read_full(&mut reader, &mut chunk[..len])?;
+      if n != len { return Err(invalid(truncated(n, len))) }
+      match decode_chunk(kind, &chunk[..len], &mut block, true, &Snappy, pos).map_err(invalid)? {
+        ChunkRead::Block(block_len) => { stats.data_chunks += 1; stats.uncompressed_len += block_len as u64 },
+        _ => stats.streams += 1,
+      }
+    }
+    stats.compressed_len += (CHUNK_HEADER_SIZE + len) as u64;
+  }
+}
+
+/// What a chunk turned out to be
+pub(crate) enum ChunkRead {
+  /// Data chunk decoded, of length
+  Block(usize),
+  StreamIdentifier,
+  /// Skippable chunk or padding
+  Skipped,
+  End,
+}
+
+/// Type and body length from chunk header
+pub(crate) fn parse_chunk_header(header: &[u8]) -> (u8, usize) {
+  (header[0], u32::from_le_bytes([header[1], header[2], header[3], 0]) as usize)
+}
+
+/// Where a chunk is in its stream, for errors
+#[derive(Clone, Copy)]
+pub(crate) struct ChunkPos {
+  /// Offset of the chunk header
+  pub(crate) offset: u64,
+  /// Index among data chunks, of the chunk if it is one
+  pub(crate) data_index: u64,
+}
+
+/// Check chunk type and body length of the header at `offset` before reading the body
+///
+/// When `resync`ing after corruption, only stream identifier and data chunks are accepted.
+pub(crate) fn check_chunk_header(kind: u8, len: usize, header_read: bool, resync: bool, offset: u64) -> Result<(), SnappyError> {
+  if !header_read && kind != CHUNK_STREAM_IDENTIFIER { return Err(SnappyError::BadStreamIdentifier) }
+  if resync && is_skippable(kind) { return Err(SnappyError::UnsupportedChunk(kind)) }
+
+  let valid = match kind {
+    CHUNK_STREAM_IDENTIFIER => return if len == STREAM_IDENTIFIER.len() - CHUNK_HEADER_SIZE { Ok(()) } else { Err(SnappyError::BadStreamIdentifier) },
+    CHUNK_COMPRESSED => (4..=max_compressed_chunk_len()).contains(&len),
+    CHUNK_UNCOMPRESSED => (4..=4 + MAX_BLOCK_SIZE).contains(&len),
+    kind if is_skippable(kind) => true,
+    kind => return Err(SnappyError::UnsupportedChunk(kind)),
+  };
+  if valid { Ok(()) } else { Err(SnappyError::BadChunkHeader { offset }) }
+}
+
+/// Decode checked chunk `body` of type `kind` at `pos` with `codec`, data goes into `block`
+pub(crate) fn decode_chunk(kind: u8, body: &[u8], block: &mut [u8], verify_checksum: bool, codec: &dyn Codec, pos: ChunkPos) -> Result<ChunkRead, SnappyError> {
+  let len = match kind {
+    CHUNK_STREAM_IDENTIFIER => {
+      if body != &STREAM_IDENTIFIER[CHUNK_HEADER_SIZE..] { return Err(SnappyError::BadStreamIdentifier) }
+      return Ok(ChunkRead::StreamIdentifier)
+    },
+    kind if is_skippable(kind) => return Ok(ChunkRead::Skipped),
+    CHUNK_COMPRESSED => codec.decompress_block(&body[4..], block).map_err(|e| match e {
+      SnappyError::InsufficientBuffer => SnappyError::BadChunkHeader { offset: pos.offset },
+      e => e,
+    })?,
+    _ => {
+      block[..body.len() - 4].copy_from_slice(&body[4..]);
+      body.len() - 4
+    },
+  };
+
+  let expected = u32::from_le_bytes([body[0], body[1], body[2], body[3]]);
+  if verify_checksum {
+    let actual = masked_crc32c(&block[..len]);
+    if actual != expected { return Err(SnappyError::ChecksumMismatch { chunk_index: pos.data_index, expected, actual }) }
+  }
+  Ok(ChunkRead::Block(len))
+}
+
+/// Read until `buf` is full or EOF, returns the length read
+pub(crate) fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+  let mut len = 0;
+  while len < buf.len() {
+    match reader.read(&mut buf[len..]) {
+      Ok(0) => break,
+      Ok(n) => len += n,
+      Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+      Err(e) => return Err(e),
+    }
+  }
+  Ok(len)
+}
+
+/// Stream ended after `read` bytes of `len` expected
+pub(crate) fn truncated(read: usize, len: usize) -> SnappyError { SnappyError::TruncatedStream { missing: (len - read) as u64 } }
+
+/// Corrupted stream error
+pub(crate) fn invalid(error: SnappyError) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, error) }
+
+/// Whether `error` is made by `invalid`
+pub(crate) fn is_corruption(error: &io::Error) -> bool {
+  error.get_ref().is_some_and(|e| e.is::<SnappyError>())
+}
@@ -0,0 +1,83 @@
+//! tonic codec of compressed `Bytes` messages, with the `tonic` feature
+//!
+//! Every gRPC message is one raw snappy block. tonic's `grpc-encoding` list is closed, so this is a message codec
+//! instead: pass `GrpcCodec` to `Grpc::unary` and friends on both sides, with messages serialized by the caller.
+
+use bytes::{Buf, Bytes, BytesMut};
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::Status;
+
+use crate::buf::{compress_to_buf, decompress_into_bytes_mut};
+use crate::message::DEFAULT_MAX_MESSAGE_LEN;
+use crate::{snappy_max_compressed_length, snappy_uncompressed_length, status};
+
+/// Codec compressing each message on its own
+#[derive(Debug, Clone, Copy)]
+pub struct GrpcCodec {
+  max_len: usize,
+}
+
+impl GrpcCodec {
+  pub fn new() -> Self { GrpcCodec::with_max_len(DEFAULT_MAX_MESSAGE_LEN) }
+
+  /// Codec rejecting messages with decompressed length over `max_len`, so a small message can't make it allocate much
+  pub fn with_max_len(max_len: usize) -> Self { GrpcCodec { max_len } }
+}
+
+impl Default for GrpcCodec {
+  fn default() -> Self { GrpcCodec::new() }
+}
+
+impl Codec for GrpcCodec {
+  type Encode = Bytes;
+  type Decode = Bytes;
+  type Encoder = GrpcEncoder;
+  type Decoder = GrpcDecoder;
+
+  fn encoder(&mut self) -> GrpcEncoder { GrpcEncoder { _private: () } }
+  fn decoder(&mut self) -> GrpcDecoder { GrpcDecoder { max_len: self.max_len, buffer: BytesMut::new() } }
+}
+
+/// Compressing half of `GrpcCodec`, writes straight into the message buffer
+#[derive(Debug)]
+pub struct GrpcEncoder {
+  _private: (),
+}
+
+impl Encoder for GrpcEncoder {
+  type Item = Bytes;
+  type Error = Status;
+
+  fn encode(&mut self, message: Bytes, dst: &mut EncodeBuf<'_>) -> Result<(), Status> {
+    dst.reserve(unsafe { snappy_max_compressed_length(message.len()) });
+    compress_to_buf(&message, dst);
+    Ok(())
+  }
+}
+
+/// Decompressing half of `GrpcCodec`
+///
+/// Messages are split off one buffer, which is reused once they are dropped.
+#[derive(Debug)]
+pub struct GrpcDecoder {
+  max_len: usize,
+  buffer: BytesMut,
+}
+
+impl Decoder for GrpcDecoder {
+  type Item = Bytes;
+  type Error = Status;
+
+  fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Bytes>, Status> {
+    let compressed = src.copy_to_bytes(src.remaining());
+    let mut len = 0;
+    unsafe { status(snappy_uncompressed_length(compressed.as_ptr(), compressed.len(), &mut len)) }
+      .map_err(|e| Status::data_loss(e.to_string()))?;
+    if len > self.max_len {
+      return Err(Status::out_of_range(format!("Decompressed message length {} over limit {}", len, self.max_len)))
+    }
+
+    decompress_into_bytes_mut(&compressed, &mut self.buffer).map_err(|e| Status::data_loss(e.to_string()))?;
+    Ok(Some(self.buffer.split().freeze()))
+  }
+}
@@ -0,0 +1,174 @@
+//! Hadoop `BlockCompressorStream` format, as written by `SnappyCodec` in HDFS, MapReduce and Spark
+//!
+//! Data is split into blocks, each is a big-endian `u32` uncompressed length followed by one or more
+//! subchunks of a big-endian `u32` compressed length and a raw snappy block. There is no header nor checksum.
+
+use std::io::{self, Read, Write};
+
+use crate::frame::{invalid, read_full, truncated};
+use crate::{compress_into, decompress_into, snappy_max_compressed_length, snappy_uncompressed_length, status, SnappyError};
+
+/// Uncompressed length of blocks `HadoopWriter` writes by default, same as Hadoop: 256 KiB buffer minus compression overhead
+pub const DEFAULT_BLOCK_SIZE: usize = 256 * 1024 - (256 * 1024 / 6 + 32);
+/// Max uncompressed length of blocks `HadoopReader` accepts by default, for buffer sizes up to 4 MiB
+pub const DEFAULT_MAX_BLOCK_LEN: usize = 4 * 1024 * 1024;
+
+/// Compressing writer of Hadoop snappy streams
+///
+/// Each block is written as a single subchunk. Buffered input is written on drop, call `finish` to handle errors of it.
+pub struct HadoopWriter<W: Write> {
+  /// Always `Some` until finished
+  inner: Option<W>,
+  block_size: usize,
+  buffer: Vec<u8>,
+  compressed: Vec<u8>,
+}
+
+impl<W: Write> HadoopWriter<W> {
+  pub fn new(inner: W) -> Self { HadoopWriter::with_block_size(inner, DEFAULT_BLOCK_SIZE) }
+
+  /// Writer with blocks of `block_size`, keep it at most `DEFAULT_BLOCK_SIZE` for Hadoop readers
+  pub fn with_block_size(inner: W, block_size: usize) -> Self {
+    assert!(block_size != 0 && block_size <= u32::MAX as usize, "block size out of range");
+    let compressed = vec![0; 8 + unsafe { snappy_max_compressed_length(block_size) }];
+    HadoopWriter { inner: Some(inner), block_size, buffer: Vec::with_capacity(block_size), compressed }
+  }
+
+  /// Compress buffered input as a block
+  fn write_buffer(&mut self) -> io::Result<()> {
+    if self.buffer.is_empty() { return Ok(()) }
+    let len = compress_into(&self.buffer, &mut self.compressed[8..]).expect("buffer of max compressed length is always enough");
+    self.compressed[..4].copy_from_slice(&(self.buffer.len() as u32).to_be_bytes());
+    self.compressed[4..8].copy_from_slice(&(len as u32).to_be_bytes());
+    self.inner.as_mut().unwrap().write_all(&self.compressed[..8 + len])?;
+    self.buffer.clear();
+    Ok(())
+  }
+
+  /// Write buffered input and get back the inner writer
+  pub fn finish(mut self) -> io::Result<W> {
+    self.write_buffer()?;
+    Ok(self.inner.take().unwrap())
+  }
+
+  pub fn get_ref(&self) -> &W { self.inner.as_ref().unwrap() }
+  pub fn get_mut(&mut self) -> &mut W { self.inner.as_mut().unwrap() }
+}
+
+impl<W: Write> Write for HadoopWriter<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let len = buf.len().min(self.block_size - self.buffer.len());
+    self.buffer.extend_from_slice(&buf[..len]);
+    if self.buffer.len() == self.block_size { self.write_buffer()? }
+    Ok(len)
+  }
+
+  /// Ends the current block early
+  fn flush(&mut self) -> io::Result<()> {
+    self.write_buffer()?;
+    self.get_mut().flush()
+  }
+}
+
+impl<W: Write> Drop for HadoopWriter<W> {
+  fn drop(&mut self) {
+    if self.inner.is_some() { let _ = self.write_buffer(); }
+  }
+}
+
+/// Decompressing reader of Hadoop snappy streams
+///
+/// Subchunks are decoded one at a time; their data must add up to the uncompressed length of the block.
+pub struct HadoopReader<R: Read> {
+  inner: R,
+  max_block_len: usize,
+  max_output_size: u64,
+  /// Length of data of the blocks started so far
+  output_len: u64,
+  /// Uncompressed length still expected in the current block
+  remaining: usize,
+  compressed: Vec<u8>,
+  block: Vec<u8>,
+  /// Position in `block`
+  pos: usize,
+  /// Length of the stream read so far
+  offset: u64,
+}
+
+impl<R: Read> HadoopReader<R> {
+  pub fn new(inner: R) -> Self {
+    HadoopReader {
+      inner, max_block_len: DEFAULT_MAX_BLOCK_LEN, max_output_size: u64::MAX, output_len: 0, remaining: 0, compressed: Vec::new(),
+      block: Vec::new(), pos: 0, offset: 0,
+    }
+  }
+
+  /// Refuse blocks of data over `len` bytes as `BadChunkHeader`, checked before anything is allocated for them;
+  /// `DEFAULT_MAX_BLOCK_LEN` by default
+  pub fn max_block_len(mut self, len: usize) -> Self { self.max_block_len = len; self }
+
+  /// Fail with `OutputLimitExceeded` once the block lengths read add up to over `size`, no limit by default
+  ///
+  /// Checked on each block header, before its data is read, see `DecoderOptions::max_output_size`.
+  pub fn max_output_size(mut self, size: u64) -> Self { self.max_output_size = size; self }
+
+  /// Read a big-endian length, `None` at EOF
+  fn read_len(&mut self) -> io::Result<Option<usize>> {
+    let mut len = [0u8; 4];
+    let n = read_full(&mut self.inner, &mut len)?;
+    self.offset += n as u64;
+    match n {
+      0 => Ok(None),
+      4 => Ok(Some(u32::from_be_bytes(len) as usize)),
+      n => Err(invalid(truncated(n, 4))),
+    }
+  }
+
+  /// Decode the next subchunk into `block`, `false` at the end of stream
+  fn read_subchunk(&mut self) -> io::Result<bool> {
+    while self.remaining == 0 {
+      let offset = self.offset;
+      match self.read_len()? {
+        Some(len) if len > self.max_block_len => return Err(invalid(SnappyError::BadChunkHeader { offset })),
+        Some(len) if self.output_len + len as u64 > self.max_output_size => return Err(invalid(SnappyError::OutputLimitExceeded)),
+        Some(len) => { self.remaining = len; self.output_len += len as u64 },
+        None => return Ok(false),
+      }
+    }
+    let offset = self.offset;
+    let len = self.read_len()?.ok_or_else(|| invalid(truncated(0, 4)))?;
+    if len > unsafe { snappy_max_compressed_length(self.remaining) } { return Err(invalid(SnappyError::BadChunkHeader { offset })) }
+    self.compressed.resize(len, 0);
+    let n = read_full(&mut self.inner, &mut self.compressed)?;
+    self.offset += n as u64;
+    if n != len { return Err(invalid(truncated(n, len))) }
+
+    let mut block_len = 0;
+    unsafe { status(snappy_uncompressed_length(self.compressed.as_ptr(), len, &mut block_len)) }.map_err(invalid)?;
+    if block_len > self.remaining { return Err(invalid(SnappyError::BadChunkHeader { offset })) }
+    self.block.resize(block_len, 0);
+    decompress_into(&self.compressed, &mut self.block).map_err(invalid)?;
+
+    self.remaining -= block_len;
+    self.pos = 0;
+    Ok(true)
+  }
+
+  pub fn get_ref(&self) -> &R { &self.inner }
+  pub fn get_mut(&mut self) -> &mut R { &mut self.inner }
+  pub fn into_inner(self) -> R { self.inner }
+}
+
+impl<R: Read> Read for HadoopReader<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    while self.pos == self.block.len() {
+      if !self.read_subchunk()? { return Ok(0) }
+    }
+
+    let block = &self.block[self.pos..];
+    let len = block.len().min(buf.len());
+    buf[..len].copy_from_slice(&block[..len]);
+    self.pos += len;
+    Ok(len)
+  }
+}
@@ -0,0 +1,26 @@
+//! Snappy payloads of Kafka messages and record batches
+//!
+//! Depending on the client, records are compressed as a snappy-java (xerial) stream, as the Java client does, or as
+//! one raw block. `decompress` takes either, told apart by the xerial magic; `compress` writes xerial, which every
+//! client reads.
+
+use std::io::{Read, Write};
+
+use crate::format::snappy_error;
+use crate::xerial::{XerialReader, XerialWriter, MAGIC};
+use crate::SnappyError;
+
+/// Compress `input` as a xerial stream of 32 KiB blocks, like the Java client
+pub fn compress(input: &[u8]) -> Vec<u8> {
+  let mut writer = XerialWriter::new(Vec::with_capacity(crate::max_compress_len(input.len()) + 16));
+  writer.write_all(input).expect("writes to Vec never fail");
+  writer.finish().expect("writes to Vec never fail")
+}
+
+/// Decompress a payload of either format
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>, SnappyError> {
+  if !input.starts_with(MAGIC) { return crate::decompress(input) }
+  let mut output = Vec::new();
+  XerialReader::new(input).read_to_end(&mut output).map_err(snappy_error)?;
+  Ok(output)
+}
@@ -0,0 +1,100 @@
+//! tower middleware of `Content-Encoding: x-snappy-framed`, with the `tower` feature
+//!
+//! For the server side of any tower http stack: requests are decompressed when snappy-encoded, responses compressed
+//! when the request accepts it, see `content_encoding`.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use http::header::{HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_RANGE, VARY};
+use http::{Method, Request, Response, StatusCode};
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::content_encoding::{accepts_snappy, compress_response, decompress_request, CompressBody, DecompressBody};
+
+/// Layer wrapping services in `SnappyService`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SnappyLayer {
+  _private: (),
+}
+
+impl SnappyLayer {
+  pub fn new() -> Self { SnappyLayer { _private: () } }
+}
+
+impl<S> Layer<S> for SnappyLayer {
+  type Service = SnappyService<S>;
+
+  fn layer(&self, inner: S) -> SnappyService<S> { SnappyService::new(inner) }
+}
+
+/// Service decompressing requests and compressing responses of `inner`
+///
+/// Responses already having a `Content-Encoding`, without a body (to HEAD requests, 1xx, 204 and 304) and partial
+/// ones (206 or with `Content-Range`) are passed through.
+#[derive(Debug, Clone)]
+pub struct SnappyService<S> {
+  inner: S,
+}
+
+impl<S> SnappyService<S> {
+  pub fn new(inner: S) -> Self { SnappyService { inner } }
+
+  pub fn get_ref(&self) -> &S { &self.inner }
+  pub fn get_mut(&mut self) -> &mut S { &mut self.inner }
+  pub fn into_inner(self) -> S { self.inner }
+}
+
+impl<S, ReqB, ResB> Service<Request<ReqB>> for SnappyService<S>
+where S: Service<Request<DecompressBody<ReqB>>, Response = Response<ResB>> {
+  type Response = Response<CompressBody<ResB>>;
+  type Error = S::Error;
+  type Future = ResponseFuture<S::Future>;
+
+  fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> { self.inner.poll_ready(cx) }
+
+  fn call(&mut self, request: Request<ReqB>) -> ResponseFuture<S::Future> {
+    // responses to HEAD have no body, not even an empty stream
+    let compress = accepts_snappy(request.headers()) && request.method() != Method::HEAD;
+    ResponseFuture { inner: self.inner.call(decompress_request(request)), compress }
+  }
+}
+
+pin_project! {
+  /// Response future of `SnappyService`
+  pub struct ResponseFuture<F> {
+    #[pin]
+    inner: F,
+    // whether the request accepts snappy and its response may have a body
+    compress: bool,
+  }
+}
+
+impl<F, B, E> Future for ResponseFuture<F> where F: Future<Output = Result<Response<B>, E>> {
+  type Output = Result<Response<CompressBody<B>>, E>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let this = self.project();
+    let mut response = ready!(this.inner.poll(cx))?;
+    if response.headers().contains_key(CONTENT_ENCODING) || !may_compress(&response) {
+      let (parts, body) = response.into_parts();
+      return Poll::Ready(Ok(Response::from_parts(parts, CompressBody::with_encoder(body, None))))
+    }
+
+    // the response depends on Accept-Encoding either way
+    response.headers_mut().append(VARY, HeaderValue::from_static(ACCEPT_ENCODING.as_str()));
+    if *this.compress { return Poll::Ready(Ok(compress_response(response))) }
+    let (parts, body) = response.into_parts();
+    Poll::Ready(Ok(Response::from_parts(parts, CompressBody::with_encoder(body, None))))
+  }
+}
+
+/// Whether `response` has a body which is not a range of another, `CompressBody` writing a stream even for no data
+fn may_compress<B>(response: &Response<B>) -> bool {
+  let status = response.status();
+  !(status.is_informational() || status == StatusCode::NO_CONTENT || status == StatusCode::NOT_MODIFIED
+    || status == StatusCode::PARTIAL_CONTENT || response.headers().contains_key(CONTENT_RANGE))
+}
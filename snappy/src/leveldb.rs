@@ -0,0 +1,59 @@
+//! Table blocks of LevelDB and RocksDB SST files
+//!
+//! Each block is stored with a 5 bytes trailer: a compression type, then the masked CRC-32C of the stored contents
+//! and type byte, little-endian. Snappy blocks are plain raw blocks, as `compress` and `decompress` give;
+//! the functions here add and check the trailer, for tooling reading SSTs without the engine.
+//! RocksDB is covered with its default `kCRC32c` checksums.
+
+use std::borrow::Cow;
+
+use crate::crc32c::{crc32c, crc32c_extend, mask};
+use crate::{compress, decompress, SnappyError};
+
+/// Compression type of blocks stored as they are
+pub const NO_COMPRESSION: u8 = 0;
+/// Compression type of snappy blocks
+pub const SNAPPY_COMPRESSION: u8 = 1;
+/// Length of the trailer after each block
+pub const BLOCK_TRAILER_SIZE: usize = 5;
+
+/// Block of `contents` with its trailer, as `TableBuilder` writes it
+///
+/// Like LevelDB, contents are stored uncompressed unless compression saves at least 1/8 of them.
+pub fn compress_block(contents: &[u8]) -> Vec<u8> {
+  let compressed = compress(contents);
+  let (mut block, kind) = if compressed.len() < contents.len() - contents.len() / 8 {
+    (compressed, SNAPPY_COMPRESSION)
+  } else {
+    (contents.to_vec(), NO_COMPRESSION)
+  };
+  let crc = mask(crc32c_extend(crc32c(&block), &[kind]));
+  block.push(kind);
+  block.extend_from_slice(&crc.to_le_bytes());
+  block
+}
+
+/// Stored contents and compression type of `block`, its trailer stripped and checksum verified
+///
+/// A block shorter than the trailer gives `TruncatedStream`, a bad checksum `ChecksumMismatch` of chunk index 0.
+pub fn split_trailer(block: &[u8]) -> Result<(&[u8], u8), SnappyError> {
+  if block.len() < BLOCK_TRAILER_SIZE {
+    return Err(SnappyError::TruncatedStream { missing: (BLOCK_TRAILER_SIZE - block.len()) as u64 })
+  }
+  let (stored, trailer) = block.split_at(block.len() - BLOCK_TRAILER_SIZE);
+  let expected = u32::from_le_bytes([trailer[1], trailer[2], trailer[3], trailer[4]]);
+  let actual = mask(crc32c_extend(crc32c(stored), &trailer[..1]));
+  if actual != expected { return Err(SnappyError::ChecksumMismatch { chunk_index: 0, expected, actual }) }
+  Ok((stored, trailer[0]))
+}
+
+/// Contents of `block`, trailer included, borrowed when stored uncompressed
+///
+/// Compression types other than none and snappy, e.g. zlib or zstd of RocksDB, give `UnsupportedChunk` of the type.
+pub fn decompress_block(block: &[u8]) -> Result<Cow<'_, [u8]>, SnappyError> {
+  match split_trailer(block)? {
+    (stored, NO_COMPRESSION) => Ok(Cow::Borrowed(stored)),
+    (stored, SNAPPY_COMPRESSION) => decompress(stored).map(Cow::Owned),
+    (_, kind) => Err(SnappyError::UnsupportedChunk(kind)),
+  }
+}
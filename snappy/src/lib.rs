@@ -0,0 +1,673 @@
+// Copyright (c) 2018 duangsuse
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+//! Snappy is a compression/decompression library.
+//!
+//! It does not aim for maximum compression, or compatibility with any other compression library
+//!
+//! instead, it aims for very high speeds and reasonable compression.
+//!
+//! GitHub repository see [google/snappy](https://github.com/google/snappy)
+//!
+//! Simple Rust binding by duangsuse
+//!
+//! Safe functions allocate with `Vec` only, so buffers are freed on drop and custom global allocators are respected;
+//! the `extern "C"` ones never allocate, the caller owns every buffer.
+//!
+//! libsnappy is linked by default; with the `pure-rust` feature, and always on wasm32, the block format is done in Rust
+//! instead, see `pure`.
+//! Without the default `std` feature, only the raw block functions, `backend`, `compressor` and `ext` are left, on `core`
+//! and `alloc`, for embedded and kernel use.
+//!
+//! Codec types are `Send` and `Sync` whenever their inner reader or writer is, parallel ones only `Send`, checked at
+//! build time; libsnappy is re-entrant, so calls on distinct buffers may run on any threads at once.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+#![doc(html_logo_url = "https://www.rust-lang.org/logos/rust-logo-128x128-blk-v2.png",
+  html_favicon_url = "https://doc.rust-lang.org/favicon.ico")]
+
+extern crate alloc;
+
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::error;
+use core::ffi::c_int;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::slice;
+
+#[cfg(not(any(feature = "libsnappy", feature = "pure-rust", target_arch = "wasm32")))]
+compile_error!("enable feature `libsnappy` or `pure-rust` for a snappy backend");
+
+/// Raw libsnappy C API, see the `snappy-sys` crate
+#[cfg(all(feature = "libsnappy", not(target_arch = "wasm32")))]
+pub use snappy_sys as raw;
+
+#[cfg(not(any(feature = "pure-rust", target_arch = "wasm32")))]
+pub use raw::*;
+#[cfg(any(feature = "pure-rust", target_arch = "wasm32"))]
+pub use pure::ffi::*;
+
+#[cfg(feature = "tar")]
+pub mod archive;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod async_core;
+#[cfg(feature = "futures-io")]
+pub mod async_futures;
+#[cfg(feature = "tokio")]
+pub mod async_tokio;
+#[cfg(feature = "std")]
+pub mod avro;
+pub mod backend;
+#[cfg(feature = "rayon")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod block;
+#[cfg(feature = "bytes")]
+pub mod buf;
+#[cfg(feature = "std")]
+pub mod cache;
+#[cfg(feature = "std")]
+pub mod channel;
+#[cfg(feature = "codec")]
+pub mod codec;
+pub mod compressor;
+#[cfg(feature = "http")]
+pub mod content_encoding;
+#[cfg(feature = "std")]
+pub mod copy;
+#[cfg(feature = "std")]
+pub mod cql;
+#[cfg(feature = "std")]
+pub mod crc32c;
+#[cfg(feature = "std")]
+pub mod delta;
+#[cfg(feature = "std")]
+pub mod dir;
+#[cfg(feature = "dlopen")]
+pub mod dlopen;
+pub mod ext;
+#[cfg(feature = "std")]
+pub mod file;
+#[cfg(feature = "std")]
+pub mod format;
+#[cfg(feature = "std")]
+pub mod frame;
+#[cfg(feature = "tonic")]
+pub mod grpc;
+#[cfg(feature = "std")]
+pub mod hadoop;
+#[cfg(feature = "std")]
+pub mod kafka;
+#[cfg(feature = "tower")]
+pub mod layer;
+#[cfg(feature = "std")]
+pub mod leveldb;
+#[cfg(feature = "std")]
+pub mod message;
+#[cfg(feature = "std")]
+pub mod parallel;
+#[cfg(feature = "parquet")]
+pub mod parquet_codec;
+#[cfg(feature = "std")]
+pub mod pooled;
+#[cfg(any(feature = "pure-rust", target_arch = "wasm32"))]
+pub mod pure;
+#[cfg(feature = "prost")]
+pub mod protobuf;
+#[cfg(feature = "std")]
+pub mod read;
+#[cfg(feature = "std")]
+pub mod seek;
+#[cfg(feature = "serde")]
+pub mod serialize;
+#[cfg(feature = "mmap")]
+pub mod shm;
+#[cfg(feature = "std")]
+pub mod shuffle;
+#[cfg(feature = "std")]
+pub mod store;
+#[cfg(feature = "stream")]
+pub mod stream;
+#[cfg(feature = "base64")]
+pub mod text;
+#[cfg(feature = "bytemuck")]
+pub mod typed;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub mod uring;
+#[cfg(feature = "std")]
+pub mod volume;
+#[cfg(feature = "std")]
+pub mod wal;
+#[cfg(feature = "std")]
+pub mod write;
+#[cfg(feature = "std")]
+pub mod xerial;
+
+#[cfg(feature = "std")]
+pub use avro::{avro_compress_block, avro_decompress_block};
+pub use backend::{default_backend, Backend};
+#[cfg(feature = "rayon")]
+pub use batch::{compress_all, decompress_all, ParallelSnappyExt};
+#[cfg(feature = "std")]
+pub use cache::CompressedLru;
+#[cfg(feature = "std")]
+pub use channel::{compressed_channel, CompressedReceiver, CompressedSender};
+pub use compressor::{Compressor, Decompressor};
+#[cfg(feature = "std")]
+pub use copy::{copy_compress, copy_decompress, decode_all, encode_all, CopyStats};
+#[cfg(feature = "std")]
+pub use dir::{compress_dir, DirOptions};
+pub use ext::SnappyExt;
+#[cfg(feature = "std")]
+pub use file::{compress_file, decompress_file, FileOptions, FileStats};
+#[cfg(feature = "std")]
+pub use format::{decompress_auto, detect_format, Format};
+#[cfg(feature = "std")]
+pub use frame::{validate_frames, Frame, FrameDecoder, FrameEncoder, Frames, StreamStats};
+#[cfg(feature = "std")]
+pub use hadoop::{HadoopReader, HadoopWriter};
+#[cfg(feature = "std")]
+pub use message::{MessageReader, MessageWriter};
+#[cfg(feature = "std")]
+pub use parallel::{ParallelFrameDecoder, ParallelFrameEncoder};
+#[cfg(feature = "std")]
+pub use read::SnappyReader;
+#[cfg(feature = "std")]
+pub use seek::{FrameIndex, SeekableDecoder};
+#[cfg(feature = "serde")]
+pub use serialize::{from_compressed_bincode, to_compressed_bincode, Compressed};
+#[cfg(feature = "std")]
+pub use store::CompressedBuf;
+#[cfg(feature = "stream")]
+pub use stream::{compress_stream, decompress_stream};
+#[cfg(feature = "base64")]
+pub use text::{compress_to_base64, decompress_from_base64};
+#[cfg(feature = "bytemuck")]
+pub use typed::{compress_slice, decompress_slice};
+#[cfg(feature = "std")]
+pub use volume::{VolumeReader, VolumeWriter};
+#[cfg(feature = "std")]
+pub use wal::{WalReader, WalRecord, WalWriter};
+#[cfg(feature = "std")]
+pub use write::SnappyWriter;
+#[cfg(feature = "std")]
+pub use xerial::{XerialReader, XerialWriter};
+
+// Codec types move to and are shared across threads, e.g. tokio tasks and rayon workers, whenever their inner reader,
+// writer or stream does; libsnappy keeps no global state, so calls on distinct buffers run in parallel.
+// Parallel codecs are only `Send`: they own the channels to their workers.
+const _: fn() = || {
+  fn send_sync<T: Send + Sync>() {}
+
+  send_sync::<Compressor>();
+  send_sync::<Decompressor>();
+  send_sync::<CompressionOptions>();
+  send_sync::<SnappyError>();
+  send_sync::<&'static dyn Backend>();
+  #[cfg(feature = "std")]
+  {
+    fn send<T: Send>() {}
+    send_sync::<frame::EncoderOptions>();
+    send_sync::<frame::DecoderOptions>();
+    send_sync::<FrameEncoder<Vec<u8>>>();
+    send_sync::<FrameDecoder<&[u8]>>();
+    send_sync::<frame::PushDecoder>();
+    send_sync::<Frames<&[u8]>>();
+    send_sync::<SnappyWriter<Vec<u8>>>();
+    send_sync::<SnappyReader<&[u8]>>();
+    send_sync::<SeekableDecoder<std::io::Cursor<Vec<u8>>>>();
+    send_sync::<HadoopWriter<Vec<u8>>>();
+    send_sync::<HadoopReader<&[u8]>>();
+    send_sync::<XerialWriter<Vec<u8>>>();
+    send_sync::<XerialReader<&[u8]>>();
+    send_sync::<MessageWriter<Vec<u8>>>();
+    send_sync::<MessageReader<&[u8]>>();
+    send_sync::<VolumeWriter>();
+    send_sync::<VolumeReader>();
+    send::<ParallelFrameEncoder<Vec<u8>>>();
+    send::<ParallelFrameDecoder<&[u8]>>();
+    send_sync::<CompressedSender>();
+    send::<CompressedReceiver>();
+  }
+  #[cfg(feature = "codec")]
+  send_sync::<codec::SnappyCodec>();
+  #[cfg(feature = "tokio")]
+  {
+    send_sync::<async_tokio::AsyncFrameEncoder<Vec<u8>>>();
+    send_sync::<async_tokio::AsyncFrameDecoder<&[u8]>>();
+  }
+  #[cfg(feature = "futures-io")]
+  {
+    send_sync::<async_futures::AsyncFrameEncoder<Vec<u8>>>();
+    send_sync::<async_futures::AsyncFrameDecoder<&[u8]>>();
+  }
+  #[cfg(feature = "dlopen")]
+  send_sync::<dlopen::DynamicLibSnappy>();
+};
+
+/// Return values for snappy operations
+///
+/// See the documentation for each function to know what each can return.
+#[repr(C)]
+pub enum SnappyResult {
+  /// Operation succeed, no exception
+  Ok = 0,
+  /// Bad input buffer given
+  InvalidInput = 1,
+  /// Allocated buffer too small
+  InsufficientBuffer = 2
+}
+
+/// `Display` implementation for `SnappyResult`
+///
+/// + SnappyResult::Ok => "Ok"
+/// + SnappyResult::InvalidInput => "Invalid Input"
+/// + SnappyResult::InsufficientBuffer => "Insufficient Buffer"
+impl fmt::Display for SnappyResult {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      SnappyResult::Ok => f.write_str("Ok"),
+      SnappyResult::InvalidInput => f.write_str("Invalid Input"),
+      SnappyResult::InsufficientBuffer => f.write_str("Insufficient Buffer"),
+    }
+  }
+}
+
+/// Result type checker methods
+///
+/// Check if result is ok, or error kind of this result enumeration
+///
+impl SnappyResult {
+  pub fn is_ok(&self) -> bool { matches!(self, SnappyResult::Ok) }
+  pub fn not_ok(&self) -> bool { !self.is_ok() }
+  pub fn bad_input(&self) -> bool { matches!(self, SnappyResult::InvalidInput) }
+  pub fn insuff_buf(&self) -> bool { matches!(self, SnappyResult::InsufficientBuffer) }
+
+  /// Convert into `Result`, so the status can be propagated using `?`
+  pub fn into_result(self) -> Result<(), SnappyError> {
+    match self {
+      SnappyResult::Ok => Ok(()),
+      SnappyResult::InvalidInput => Err(SnappyError::InvalidInput),
+      SnappyResult::InsufficientBuffer => Err(SnappyError::InsufficientBuffer),
+    }
+  }
+}
+
+/// Status codes from libsnappy, unexpected values give `SnappyError::Unknown`
+impl TryFrom<c_int> for SnappyResult {
+  type Error = SnappyError;
+
+  fn try_from(code: c_int) -> Result<Self, SnappyError> {
+    match code {
+      SNAPPY_OK => Ok(SnappyResult::Ok),
+      SNAPPY_INVALID_INPUT => Ok(SnappyResult::InvalidInput),
+      SNAPPY_BUFFER_TOO_SMALL => Ok(SnappyResult::InsufficientBuffer),
+      n => Err(SnappyError::Unknown(n)),
+    }
+  }
+}
+
+impl From<SnappyResult> for Result<(), SnappyError> {
+  fn from(result: SnappyResult) -> Self { result.into_result() }
+}
+
+impl From<SnappyError> for SnappyResult {
+  fn from(error: SnappyError) -> Self {
+    match error {
+      SnappyError::InvalidInput => SnappyResult::InvalidInput,
+      SnappyError::InsufficientBuffer => SnappyResult::InsufficientBuffer,
+      _ => SnappyResult::InvalidInput,
+    }
+  }
+}
+
+/// C callers only see the three known statuses, other errors are reported as `InvalidInput`
+impl From<Result<(), SnappyError>> for SnappyResult {
+  fn from(result: Result<(), SnappyError>) -> Self {
+    match result {
+      Ok(()) => SnappyResult::Ok,
+      Err(error) => error.into(),
+    }
+  }
+}
+
+/// Check a raw status code returned by libsnappy
+fn status(code: c_int) -> Result<(), SnappyError> {
+  SnappyResult::try_from(code)?.into_result()
+}
+
+/// Errors of the safe snappy operations
+///
+/// Failed `SnappyResult` cases, for use with `Result` and `?`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnappyError {
+  /// Bad input buffer given
+  InvalidInput,
+  /// Allocated buffer too small
+  InsufficientBuffer,
+  /// libsnappy returned a status code out of `SnappyResult`
+  Unknown(i32),
+  /// Framed stream does not start with a valid stream identifier
+  BadStreamIdentifier,
+  /// Chunk length out of range for its type, in the chunk header at `offset` of the stream
+  BadChunkHeader { offset: u64 },
+  /// Reserved unskippable chunk type met in framed stream
+  UnsupportedChunk(u8),
+  /// Masked CRC-32C of decompressed data differs from the one stored in chunk
+  ///
+  /// `chunk_index` counts data chunks only, from 0.
+  ChecksumMismatch { chunk_index: u64, expected: u32, actual: u32 },
+  /// Framed stream ended in the middle of a chunk, at least `missing` bytes short
+  TruncatedStream { missing: u64 },
+  /// Frame block size out of the range framing format allows
+  BadBlockSize(usize),
+  /// Frame size cap too small for any chunk
+  BadFrameSize(usize),
+  /// Data would be over the limit set, see `decompress_with_limit`; or a message over its max length
+  OutputLimitExceeded
+}
+
+/// `Display` implementation for `SnappyError`, same messages as `SnappyResult`
+impl fmt::Display for SnappyError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      SnappyError::InvalidInput => f.write_str("Invalid Input"),
+      SnappyError::InsufficientBuffer => f.write_str("Insufficient Buffer"),
+      SnappyError::Unknown(code) => write!(f, "Unknown Status {}", code),
+      SnappyError::BadStreamIdentifier => f.write_str("Bad Stream Identifier"),
+      SnappyError::BadChunkHeader { offset } => write!(f, "Bad Chunk Header at offset {}", offset),
+      SnappyError::UnsupportedChunk(kind) => write!(f, "Unsupported Chunk {:#04x}", kind),
+      SnappyError::ChecksumMismatch { chunk_index, expected, actual } => {
+        write!(f, "Checksum Mismatch in chunk {}: expected {:#010x}, actual {:#010x}", chunk_index, expected, actual)
+      },
+      SnappyError::TruncatedStream { missing } => write!(f, "Truncated Stream, {} bytes missing", missing),
+      SnappyError::BadBlockSize(size) => write!(f, "Bad Block Size {}", size),
+      SnappyError::BadFrameSize(size) => write!(f, "Bad Frame Size {}", size),
+      SnappyError::OutputLimitExceeded => f.write_str("Output Limit Exceeded"),
+    }
+  }
+}
+
+impl error::Error for SnappyError {}
+
+/// `SnappyError` as `io::Error`, so `?` works in `io::Result` functions
+///
+/// The error is kept whole, `get_ref` and `into_inner` give it back for downcasting. Its kind is `InvalidInput` for
+/// errors of the caller's buffers and options, `InvalidData` for bad compressed data.
+#[cfg(feature = "std")]
+impl From<SnappyError> for std::io::Error {
+  fn from(error: SnappyError) -> Self {
+    let kind = match error {
+      SnappyError::InsufficientBuffer | SnappyError::BadBlockSize(_) | SnappyError::BadFrameSize(_) => std::io::ErrorKind::InvalidInput,
+      _ => std::io::ErrorKind::InvalidData,
+    };
+    std::io::Error::new(kind, error)
+  }
+}
+
+/// Max compressed length of `len` bytes, safe version of `snappy_max_compressed_length`
+///
+/// Enough for the output buffer of `compress_into` and the other compressing functions.
+pub fn max_compress_len(len: usize) -> usize { unsafe { snappy_max_compressed_length(len) } }
+
+/// Uncompressed length stored in the header of `input`, safe version of `snappy_uncompressed_length`
+///
+/// Only the header is read, in O(1): a length is no proof the rest of `input` is valid. Check it before allocating
+/// for untrusted input, whose header may claim any size.
+pub fn decompress_len(input: &[u8]) -> Result<usize, SnappyError> {
+  let mut len = 0;
+  unsafe { status(snappy_uncompressed_length(input.as_ptr(), input.len(), &mut len)) }?;
+  Ok(len)
+}
+
+/// Deflates(compress) a byte slice
+///
+/// Nothing is allocated here: `output` is owned by the caller, and stays so.
+///
+/// `output_length` signals the space available in `output`, which must be at least
+/// `snappy_max_compressed_length(length)`, after success it contains the true compressed length.
+///
+/// # Safety
+///
+/// `input` must be valid for `length` bytes, `output` valid for `*output_length` bytes.
+pub unsafe extern "C" fn deflate(input: *const u8, length: size_t, output: *mut u8, output_length: *mut size_t) -> SnappyResult {
+  status(snappy_compress(input, length, output, output_length)).into()
+}
+
+/// Compress a byte slice, safe version of `deflate`
+///
+/// Output buffer is sized with `snappy_max_compressed_length`, then truncated to the real compressed length.
+pub fn compress(input: &[u8]) -> Vec<u8> {
+  unsafe {
+    let mut output_len = snappy_max_compressed_length(input.len());
+    let mut output = Vec::with_capacity(output_len);
+
+    let result = status(snappy_compress(input.as_ptr(), input.len(), output.as_mut_ptr(), &mut output_len));
+    debug_assert!(result.is_ok(), "buffer of max compressed length is always enough");
+
+    output.set_len(output_len);
+    output
+  }
+}
+
+/// Output of `compress_or_borrow`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaybeCompressed<'a> {
+  /// Compressed data, or the input itself
+  pub data: Cow<'a, [u8]>,
+  /// Whether `data` is compressed, to be stored along with it for `decompress_or_borrow`
+  pub compressed: bool,
+}
+
+/// Compress a byte slice, or borrow it when compressing does not make it shorter
+///
+/// For caches and stores of small values: incompressible ones are kept as they are, and read back without
+/// decompressing through `decompress_or_borrow`.
+pub fn compress_or_borrow(input: &[u8]) -> MaybeCompressed<'_> {
+  let output = compress(input);
+  if output.len() < input.len() {
+    MaybeCompressed { data: Cow::Owned(output), compressed: true }
+  } else {
+    MaybeCompressed { data: Cow::Borrowed(input), compressed: false }
+  }
+}
+
+/// Decompress `data` if `compressed`, else borrow it, see `compress_or_borrow`
+pub fn decompress_or_borrow(data: &[u8], compressed: bool) -> Result<Cow<'_, [u8]>, SnappyError> {
+  if compressed { decompress(data).map(Cow::Owned) } else { Ok(Cow::Borrowed(data)) }
+}
+
+/// Predict the ratio of compressed to uncompressed length of `input` from about `sample_bytes` of it
+///
+/// Up to 8 samples of at least 1 KiB, spread evenly over `input`, are compressed; inputs no longer than
+/// `sample_bytes` are compressed whole. Under 1.0 compression pays off, e.g. storage engines compress pages
+/// estimated under 0.9. Empty input gives 1.0.
+pub fn estimate_ratio(input: &[u8], sample_bytes: usize) -> f32 {
+  if input.is_empty() { return 1.0 }
+  if input.len() <= sample_bytes { return compress(input).len() as f32 / input.len() as f32 }
+
+  let count = (sample_bytes / 1024).clamp(1, 8);
+  let sample_len = (sample_bytes / count).max(1);
+  let mut output = alloc::vec![0; max_compress_len(sample_len)];
+  let (mut sampled, mut compressed) = (0, 0);
+  for i in 0..count {
+    // first and last samples at the ends of input, a single one in the middle
+    let start = if count == 1 { (input.len() - sample_len) / 2 } else { i * (input.len() - sample_len) / (count - 1) };
+    let sample = &input[start..start + sample_len];
+    compressed += compress_into(sample, &mut output).expect("buffer of max compressed length is always enough");
+    sampled += sample.len();
+  }
+  compressed as f32 / sampled as f32
+}
+
+/// Compress a byte slice into caller-provided buffer, returns the written length
+///
+/// `output` must hold at least `snappy_max_compressed_length(input.len())` bytes, or `InsufficientBuffer` is returned.
+pub fn compress_into(input: &[u8], output: &mut [u8]) -> Result<usize, SnappyError> {
+  let mut output_len = output.len();
+  unsafe { status(snappy_compress(input.as_ptr(), input.len(), output.as_mut_ptr(), &mut output_len)) }?;
+
+  Ok(output_len)
+}
+
+/// Options of `compress_with_options`, builder style
+///
+/// ```
+/// let compressed = snappy::compress_with_options(b"dense dense dense", snappy::CompressionOptions::new().level(2));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionOptions {
+  level: u8,
+}
+
+impl CompressionOptions {
+  pub fn new() -> Self { CompressionOptions { level: 1 } }
+
+  /// Compression level, 1 (fastest, default) or 2 (a little slower and denser); others are clamped
+  ///
+  /// Level 2 takes libsnappy with `CompressionOptions` (1.2 and later) or the `pure-rust` backend, see
+  /// `SNAPPY_MAX_LEVEL`; other libsnappy compresses at level 1 whatever the level. Output reads the same either way.
+  pub fn level(mut self, level: u8) -> Self { self.level = level.clamp(1, 2); self }
+}
+
+impl Default for CompressionOptions {
+  fn default() -> Self { CompressionOptions::new() }
+}
+
+/// Compress a byte slice with `options`, see `compress`
+pub fn compress_with_options(input: &[u8], options: CompressionOptions) -> Vec<u8> {
+  let mut output = alloc::vec![0; max_compress_len(input.len())];
+  let len = compress_into_with_options(input, &mut output, options).expect("buffer of max compressed length is always enough");
+  output.truncate(len);
+  output
+}
+
+/// Compress a byte slice with `options` into caller-provided buffer, see `compress_into`
+pub fn compress_into_with_options(input: &[u8], output: &mut [u8], options: CompressionOptions) -> Result<usize, SnappyError> {
+  let mut output_len = output.len();
+  unsafe { status(snappy_compress_level(input.as_ptr(), input.len(), output.as_mut_ptr(), &mut output_len, options.level.into())) }?;
+
+  Ok(output_len)
+}
+
+/// Inflates(uncompress) a byte slice
+///
+/// Like `deflate`, `output` is owned by the caller.
+///
+/// `output_length` signals the space available in `output`, after success it contains the true uncompressed length.
+///
+/// # Safety
+///
+/// `input` must be valid for `length` bytes, `output` valid for `*output_length` bytes.
+pub unsafe extern "C" fn inflate(input: *const u8, length: size_t, output: *mut u8, output_length: *mut size_t) -> SnappyResult {
+  let mut needed_len = 0;
+  let check = status(snappy_uncompressed_length(input, length, &mut needed_len));
+
+  if check.is_err() { return SnappyResult::InvalidInput }
+  if needed_len > *output_length { return SnappyResult::InsufficientBuffer }
+
+  status(snappy_uncompress(input, length, output, output_length)).into()
+}
+
+/// Decompress a byte slice, safe version of `inflate`
+///
+/// Output buffer is sized with `snappy_uncompressed_length` stored in the input header.
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>, SnappyError> {
+  unsafe {
+    let mut output_len = 0;
+    status(snappy_uncompressed_length(input.as_ptr(), input.len(), &mut output_len))?;
+
+    let mut output = Vec::with_capacity(output_len);
+    status(snappy_uncompress(input.as_ptr(), input.len(), output.as_mut_ptr(), &mut output_len))?;
+
+    output.set_len(output_len);
+    Ok(output)
+  }
+}
+
+/// Decompress a byte slice of at most `max_output_size` bytes, see `decompress`
+///
+/// The length stored in the header is checked before allocating, over the limit gives `OutputLimitExceeded`:
+/// untrusted input can claim up to 4 GiB in a few bytes. `DecoderOptions::max_output_size` limits framed streams.
+pub fn decompress_with_limit(input: &[u8], max_output_size: usize) -> Result<Vec<u8>, SnappyError> {
+  if decompress_len(input)? > max_output_size { return Err(SnappyError::OutputLimitExceeded) }
+  decompress(input)
+}
+
+/// Decompress a byte slice into caller-provided buffer, returns the written length
+///
+/// Needed length is checked against `output` before uncompressing, too small buffer gives `InsufficientBuffer`.
+pub fn decompress_into(input: &[u8], output: &mut [u8]) -> Result<usize, SnappyError> {
+  unsafe {
+    let mut output_len = 0;
+    status(snappy_uncompressed_length(input.as_ptr(), input.len(), &mut output_len))?;
+
+    if output_len > output.len() { return Err(SnappyError::InsufficientBuffer) }
+
+    status(snappy_uncompress(input.as_ptr(), input.len(), output.as_mut_ptr(), &mut output_len))?;
+    Ok(output_len)
+  }
+}
+
+/// Compress a byte slice into uninitialized memory, returns the written part of `output`
+///
+/// Same as `compress_into` without zero-filling `output` first.
+pub fn compress_uninit<'a>(input: &[u8], output: &'a mut [MaybeUninit<u8>]) -> Result<&'a mut [u8], SnappyError> {
+  let mut output_len = output.len();
+  unsafe {
+    status(snappy_compress(input.as_ptr(), input.len(), output.as_mut_ptr() as *mut u8, &mut output_len))?;
+    Ok(slice::from_raw_parts_mut(output.as_mut_ptr() as *mut u8, output_len))
+  }
+}
+
+/// Decompress a byte slice into uninitialized memory, returns the written part of `output`
+///
+/// Same as `decompress_into` without zero-filling `output` first.
+pub fn decompress_uninit<'a>(input: &[u8], output: &'a mut [MaybeUninit<u8>]) -> Result<&'a mut [u8], SnappyError> {
+  unsafe {
+    let mut output_len = 0;
+    status(snappy_uncompressed_length(input.as_ptr(), input.len(), &mut output_len))?;
+
+    if output_len > output.len() { return Err(SnappyError::InsufficientBuffer) }
+
+    status(snappy_uncompress(input.as_ptr(), input.len(), output.as_mut_ptr() as *mut u8, &mut output_len))?;
+    Ok(slice::from_raw_parts_mut(output.as_mut_ptr() as *mut u8, output_len))
+  }
+}
+
+/// Compress a byte slice, appending to `output`, returns the appended length
+///
+/// Spare capacity is reserved for the max compressed length and written in place, nothing is zero-filled.
+pub fn compress_to_spare(input: &[u8], output: &mut Vec<u8>) -> usize {
+  output.reserve(max_compress_len(input.len()));
+  let len = compress_uninit(input, output.spare_capacity_mut()).expect("buffer of max compressed length is always enough").len();
+  unsafe { output.set_len(output.len() + len) }
+  len
+}
+
+/// Decompress a byte slice, appending to `output`, returns the appended length
+///
+/// Spare capacity is reserved for the length stored in the input header and written in place.
+pub fn decompress_to_spare(input: &[u8], output: &mut Vec<u8>) -> Result<usize, SnappyError> {
+  output.reserve(decompress_len(input)?);
+  let len = decompress_uninit(input, output.spare_capacity_mut())?.len();
+  unsafe { output.set_len(output.len() + len) }
+  Ok(len)
+}
+
+/// Validate a byte slice
+///
+/// # Safety
+///
+/// `input` must be valid for `length` bytes.
+pub unsafe extern "C" fn validate(input: *const u8, length: size_t) -> bool {
+  status(snappy_validate_compressed_buffer(input, length)).is_ok()
+}
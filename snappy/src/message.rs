@@ -0,0 +1,112 @@
+//! Length-prefixed messages, each compressed on its own
+//!
+//! Every message is a LEB128 varint of its compressed length followed by a raw snappy block, like
+//! protobuf delimited messages; so many small messages can share one connection and be decompressed one by one.
+//! With the `prost` feature, protobuf messages go in directly, see `protobuf`.
+
+use std::io::{self, Read, Write};
+
+use crate::frame::{invalid, read_full, truncated};
+use crate::{compress_into, decompress_with_limit, snappy_max_compressed_length, SnappyError};
+
+/// Max compressed and decompressed length `MessageReader` accepts by default
+pub const DEFAULT_MAX_MESSAGE_LEN: usize = 64 << 20;
+
+/// Writer of compressed messages
+pub struct MessageWriter<W: Write> {
+  inner: W,
+  /// Scratch space for varint and compressed message
+  buffer: Vec<u8>,
+}
+
+impl<W: Write> MessageWriter<W> {
+  pub fn new(inner: W) -> Self { MessageWriter { inner, buffer: Vec::new() } }
+
+  /// Compress and write `message` with its length prefix
+  pub fn write_message(&mut self, message: &[u8]) -> io::Result<()> {
+    let max_len = unsafe { snappy_max_compressed_length(message.len()) };
+    self.buffer.resize(10 + max_len, 0);
+    let len = compress_into(message, &mut self.buffer[10..]).expect("buffer of max compressed length is always enough");
+
+    let mut prefix = [0u8; 10];
+    let prefix_len = encode_varint(len as u64, &mut prefix);
+    self.inner.write_all(&prefix[..prefix_len])?;
+    self.inner.write_all(&self.buffer[10..10 + len])
+  }
+
+  /// Flush the inner writer
+  pub fn flush(&mut self) -> io::Result<()> { self.inner.flush() }
+
+  pub fn get_ref(&self) -> &W { &self.inner }
+  pub fn get_mut(&mut self) -> &mut W { &mut self.inner }
+  pub fn into_inner(self) -> W { self.inner }
+}
+
+/// Reader of compressed messages, also an iterator over them
+///
+/// Length prefixes are read byte by byte, wrap unbuffered readers in `BufReader`.
+pub struct MessageReader<R: Read> {
+  inner: R,
+  max_len: usize,
+  compressed: Vec<u8>,
+  /// Length of the stream read so far
+  offset: u64,
+}
+
+impl<R: Read> MessageReader<R> {
+  pub fn new(inner: R) -> Self { MessageReader::with_max_len(inner, DEFAULT_MAX_MESSAGE_LEN) }
+
+  /// Reader rejecting messages with compressed or decompressed length over `max_len`
+  ///
+  /// Both are checked before allocating, so neither a bad prefix nor a bad block header can make it allocate much.
+  pub fn with_max_len(inner: R, max_len: usize) -> Self { MessageReader { inner, max_len, compressed: Vec::new(), offset: 0 } }
+
+  /// Read and decompress the next message, `None` at EOF between messages
+  pub fn read_message(&mut self) -> io::Result<Option<Vec<u8>>> {
+    let offset = self.offset;
+    let (len, prefix_len) = match read_varint(&mut self.inner, offset)? { Some(varint) => varint, None => return Ok(None) };
+    if len > self.max_len as u64 { return Err(invalid(SnappyError::BadChunkHeader { offset })) }
+
+    self.compressed.resize(len as usize, 0);
+    let n = read_full(&mut self.inner, &mut self.compressed)?;
+    self.offset += (prefix_len + n) as u64;
+    if n != len as usize { return Err(invalid(truncated(n, len as usize))) }
+    decompress_with_limit(&self.compressed, self.max_len).map(Some).map_err(invalid)
+  }
+
+  pub fn get_ref(&self) -> &R { &self.inner }
+  pub fn get_mut(&mut self) -> &mut R { &mut self.inner }
+  pub fn into_inner(self) -> R { self.inner }
+}
+
+impl<R: Read> Iterator for MessageReader<R> {
+  type Item = io::Result<Vec<u8>>;
+
+  fn next(&mut self) -> Option<io::Result<Vec<u8>>> { self.read_message().transpose() }
+}
+
+/// Write LEB128 `value` into `buf`, returns its length, at most 10
+pub(crate) fn encode_varint(mut value: u64, buf: &mut [u8; 10]) -> usize {
+  let mut len = 0;
+  while value >= 0x80 {
+    buf[len] = value as u8 | 0x80;
+    value >>= 7;
+    len += 1;
+  }
+  buf[len] = value as u8;
+  len + 1
+}
+
+/// Read LEB128 value at `offset` of the stream and its length, `None` at EOF before its first byte
+pub(crate) fn read_varint<R: Read>(reader: &mut R, offset: u64) -> io::Result<Option<(u64, usize)>> {
+  let mut value = 0u64;
+  for i in 0..10 {
+    let mut byte = [0u8];
+    if read_full(reader, &mut byte)? == 0 {
+      return if i == 0 { Ok(None) } else { Err(invalid(SnappyError::TruncatedStream { missing: 1 })) }
+    }
+    value |= u64::from(byte[0] & 0x7f) << (7 * i);
+    if byte[0] & 0x80 == 0 { return Ok(Some((value, i + 1))) }
+  }
+  Err(invalid(SnappyError::BadChunkHeader { offset }))
+}
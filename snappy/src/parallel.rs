@@ -0,0 +1,288 @@
+//! Framed stream compression and decompression on several threads
+//!
+//! Blocks of the framing format are independent, so they are handled by a pool of worker threads
+//! and put back in order; output is the same as of `FrameEncoder` and `FrameDecoder`.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::frame::{invalid, DecoderOptions, EncoderOptions, EncoderStats, Frame, FrameEncoder, Frames, STREAM_IDENTIFIER};
+use crate::SnappyError;
+
+/// Worker threads running jobs, results come back tagged with the sequence number of their job, in any order
+///
+/// Jobs in flight are capped at two per thread, so memory use stays bounded.
+struct Pool<J, T> {
+  /// `None` once the workers are told to stop
+  jobs: Option<Sender<(u64, J)>>,
+  results: Receiver<(u64, T)>,
+  workers: Vec<JoinHandle<()>>,
+  /// Sequence number of the next job, and of the next result to take
+  next_job: u64,
+  next_result: u64,
+  /// Results ahead of the next one to take
+  done: BTreeMap<u64, T>,
+}
+
+impl<J: Send + 'static, T: Send + 'static> Pool<J, T> {
+  /// Pool of `threads` workers, each running the job function `make` gives it
+  fn new<F: FnMut(J) -> T + Send + 'static>(threads: usize, make: impl Fn() -> F) -> Self {
+    let (jobs, job_receiver) = channel::<(u64, J)>();
+    let (result_sender, results) = channel();
+    let job_receiver = Arc::new(Mutex::new(job_receiver));
+    let workers = (0..threads.max(1)).map(|_| {
+      let (jobs, results, mut work) = (job_receiver.clone(), result_sender.clone(), make());
+      thread::spawn(move || loop {
+        let job = jobs.lock().unwrap().recv();
+        let (seq, job) = match job { Ok(job) => job, Err(_) => return };
+        if results.send((seq, work(job))).is_err() { return }
+      })
+    }).collect();
+    Pool { jobs: Some(jobs), results, workers, next_job: 0, next_result: 0, done: BTreeMap::new() }
+  }
+
+  fn in_flight(&self) -> u64 { self.next_job - self.next_result }
+  fn is_full(&self) -> bool { self.in_flight() >= 2 * self.workers.len() as u64 }
+
+  fn send(&mut self, job: J) {
+    self.jobs.as_ref().unwrap().send((self.next_job, job)).expect("workers live until the pool is dropped");
+    self.next_job += 1;
+  }
+
+  /// Result of the oldest job in flight, waiting for it
+  fn next_result(&mut self) -> T {
+    let result = loop {
+      if let Some(result) = self.done.remove(&self.next_result) { break result }
+      let (seq, result) = self.results.recv().expect("workers live until the pool is dropped");
+      self.done.insert(seq, result);
+    };
+    self.next_result += 1;
+    result
+  }
+}
+
+impl<J, T> Drop for Pool<J, T> {
+  fn drop(&mut self) {
+    self.jobs = None;
+    for worker in self.workers.drain(..) { let _ = worker.join(); }
+  }
+}
+
+/// Thread count of `new` constructors: one per CPU
+fn default_threads() -> usize { thread::available_parallelism().map_or(1, |n| n.get()) }
+
+/// Compressing writer of a framed stream, blocks compressed by worker threads
+///
+/// `EncoderOptions::index` and `format` are ignored. Buffered input is written on drop, call `finish` to handle errors of it.
+/// After an error, blocks may be lost, so the encoder refuses all further calls.
+pub struct ParallelFrameEncoder<W: Write> {
+  /// Always `Some` until finished
+  inner: Option<W>,
+  block_size: usize,
+  buffer: Vec<u8>,
+  /// Blocks in, their chunks and counts out
+  pool: Pool<Vec<u8>, io::Result<(Vec<u8>, EncoderStats)>>,
+  stats: EncoderStats,
+  failed: bool,
+}
+
+impl<W: Write> ParallelFrameEncoder<W> {
+  /// Encoder with a thread per CPU
+  pub fn new(inner: W) -> Self {
+    ParallelFrameEncoder::with_options(inner, EncoderOptions::new().parallel(default_threads())).unwrap()
+  }
+
+  /// Encoder with `options`, which are validated first, and as many workers as `EncoderOptions::parallel` says
+  pub fn with_options(inner: W, options: EncoderOptions) -> Result<Self, SnappyError> {
+    let (options, threads) = (options.index(false), options.threads());
+    let block_size = FrameEncoder::with_options(io::sink(), options)?.block_size();
+
+    let pool = Pool::new(threads, || {
+      let mut encoder = FrameEncoder::mid_stream(Vec::new(), options).unwrap();
+      move |block: Vec<u8>| {
+        let before = encoder.stats();
+        encoder.write_data(&block)?;
+        let after = encoder.stats();
+        let stats = EncoderStats {
+          compressed_blocks: after.compressed_blocks - before.compressed_blocks,
+          stored_blocks: after.stored_blocks - before.stored_blocks,
+          uncompressed_len: after.uncompressed_len - before.uncompressed_len,
+          stream_len: after.stream_len - before.stream_len,
+        };
+        Ok((std::mem::take(encoder.get_mut()), stats))
+      }
+    });
+    let buffer = Vec::with_capacity(block_size);
+    Ok(ParallelFrameEncoder { inner: Some(inner), block_size, buffer, pool, stats: EncoderStats::default(), failed: false })
+  }
+
+  /// Send buffered input to the workers as a block, writing finished blocks first if too many are in flight
+  fn send_buffer(&mut self) -> io::Result<()> {
+    if self.failed { return Err(io::Error::other("parallel encoder failed before")) }
+    if self.buffer.is_empty() { return Ok(()) }
+    while self.pool.is_full() { self.write_next()? }
+
+    let block = std::mem::replace(&mut self.buffer, Vec::with_capacity(self.block_size));
+    self.pool.send(block);
+    Ok(())
+  }
+
+  /// Wait for the oldest block in flight and write its chunks
+  fn write_next(&mut self) -> io::Result<()> {
+    let result = self.pool.next_result().and_then(|(chunks, stats)| {
+      self.write_identifier()?;
+      self.inner.as_mut().unwrap().write_all(&chunks)?;
+      Ok(stats)
+    });
+    let stats = result.inspect_err(|_| self.failed = true)?;
+    self.stats.compressed_blocks += stats.compressed_blocks;
+    self.stats.stored_blocks += stats.stored_blocks;
+    self.stats.uncompressed_len += stats.uncompressed_len;
+    self.stats.stream_len += stats.stream_len;
+    Ok(())
+  }
+
+  /// Write stream identifier if not yet
+  fn write_identifier(&mut self) -> io::Result<()> {
+    if self.stats.stream_len == 0 {
+      self.inner.as_mut().unwrap().write_all(STREAM_IDENTIFIER)?;
+      self.stats.stream_len = STREAM_IDENTIFIER.len() as u64;
+    }
+    Ok(())
+  }
+
+  /// Compress and write all input so far, ending the current block early, then flush the inner writer
+  pub fn sync_flush(&mut self) -> io::Result<()> {
+    self.send_buffer()?;
+    while self.pool.in_flight() != 0 { self.write_next()? }
+    self.write_identifier()?;
+    self.inner.as_mut().unwrap().flush()
+  }
+
+  /// Counts of the data written so far, blocks in flight excluded
+  pub fn stats(&self) -> EncoderStats { self.stats }
+
+  /// Write all input and get back the inner writer
+  pub fn finish(mut self) -> io::Result<W> {
+    self.sync_flush()?;
+    Ok(self.inner.take().unwrap())
+  }
+
+  pub fn get_ref(&self) -> &W { self.inner.as_ref().unwrap() }
+  pub fn get_mut(&mut self) -> &mut W { self.inner.as_mut().unwrap() }
+}
+
+impl<W: Write> Write for ParallelFrameEncoder<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let len = buf.len().min(self.block_size - self.buffer.len());
+    self.buffer.extend_from_slice(&buf[..len]);
+    if self.buffer.len() == self.block_size { self.send_buffer()? }
+    Ok(len)
+  }
+
+  /// Ends the current block early, see `sync_flush`
+  fn flush(&mut self) -> io::Result<()> { self.sync_flush() }
+}
+
+impl<W: Write> Drop for ParallelFrameEncoder<W> {
+  fn drop(&mut self) {
+    if self.inner.is_some() && !self.failed { let _ = self.sync_flush(); }
+  }
+}
+
+/// Decompressing reader of a framed stream, chunks decompressed and checksums verified by worker threads
+///
+/// Chunks are read ahead on the calling thread, like `Frames` does; concatenated streams are read as one.
+/// Errors come in stream order, after all data before them; the decoder refuses all further reads after one.
+/// Of `DecoderOptions`, only `parallel`, `max_output_size` and `codec` apply: checksums are always verified, and
+/// `copy_decompress` takes a `FrameDecoder` instead when the others would change what is decoded.
+/// The output limit is checked on the length each chunk claims before it goes to a worker; chunks of a custom codec
+/// have none to claim, and are at most `MAX_BLOCK_SIZE` once decompressed.
+pub struct ParallelFrameDecoder<R: Read> {
+  frames: Frames<R>,
+  pool: Pool<Frame, Result<Vec<u8>, SnappyError>>,
+  block: Vec<u8>,
+  /// Position in `block`
+  pos: usize,
+  /// Error reading ahead, given once the blocks before it are read
+  error: Option<io::Error>,
+  ended: bool,
+  failed: bool,
+  options: DecoderOptions,
+  /// Length of data decoded so far
+  output_len: u64,
+  /// Length of data the chunks read ahead claim
+  claimed_len: u64,
+}
+
+impl<R: Read> ParallelFrameDecoder<R> {
+  /// Decoder with a thread per CPU
+  pub fn new(inner: R) -> Self { ParallelFrameDecoder::with_options(inner, DecoderOptions::new().parallel(default_threads())) }
+
+  /// Decoder with `options`, as many workers as `DecoderOptions::parallel` says
+  pub fn with_options(inner: R, options: DecoderOptions) -> Self {
+    let codec = options.custom_codec();
+    let pool = Pool::new(options.threads(), || move |frame: Frame| match codec {
+      Some(codec) => frame.decompress_with(codec),
+      None => frame.decompress(),
+    });
+    let frames = match codec { Some(codec) => Frames::with_codec(inner, codec), None => Frames::new(inner) };
+    ParallelFrameDecoder {
+      frames, pool, block: Vec::new(), pos: 0, error: None, ended: false, failed: false, options, output_len: 0, claimed_len: 0,
+    }
+  }
+
+  /// Read data chunks ahead until the pool is full or the stream ends
+  fn read_ahead(&mut self) {
+    while !self.ended && !self.pool.is_full() {
+      match self.frames.next() {
+        Some(Ok(frame)) if frame.is_data() => {
+          self.claimed_len += frame.uncompressed_len.unwrap_or(0) as u64;
+          match self.options.check_output_len(self.claimed_len) {
+            Ok(()) => self.pool.send(frame),
+            Err(e) => { self.error = Some(invalid(e)); self.ended = true },
+          }
+        },
+        Some(Ok(_)) => (),
+        Some(Err(e)) => { self.error = Some(e); self.ended = true },
+        None => self.ended = true,
+      }
+    }
+  }
+
+  /// Take the next block, `false` at the end of stream
+  fn next_block(&mut self) -> io::Result<bool> {
+    if self.failed { return Err(io::Error::other("parallel decoder failed before")) }
+    self.read_ahead();
+    if self.pool.in_flight() == 0 {
+      return match self.error.take() { Some(e) => { self.failed = true; Err(e) }, None => Ok(false) }
+    }
+    match self.pool.next_result() {
+      Ok(block) => {
+        self.output_len += block.len() as u64;
+        if let Err(e) = self.options.check_output_len(self.output_len) { self.failed = true; return Err(invalid(e)) }
+        self.block = block;
+        self.pos = 0;
+        Ok(true)
+      },
+      Err(e) => { self.failed = true; Err(invalid(e)) },
+    }
+  }
+}
+
+impl<R: Read> Read for ParallelFrameDecoder<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    while self.pos == self.block.len() {
+      if !self.next_block()? { return Ok(0) }
+    }
+
+    let block = &self.block[self.pos..];
+    let len = block.len().min(buf.len());
+    buf[..len].copy_from_slice(&block[..len]);
+    self.pos += len;
+    Ok(len)
+  }
+}
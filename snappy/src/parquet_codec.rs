@@ -0,0 +1,33 @@
+//! Snappy codec of Parquet pages, with the `parquet` feature
+//!
+//! `SnappyCodec` implements `Codec` of the `parquet` crate over the raw block functions of this crate, so Arrow and
+//! Parquet code compressing pages itself can share the libsnappy, or pure Rust backend, already linked here.
+
+use parquet::compression::Codec;
+use parquet::errors::{ParquetError, Result};
+
+use crate::{compress_to_spare, decompress_len, decompress_to_spare};
+
+/// Parquet `SNAPPY` codec: each page is one raw block
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SnappyCodec;
+
+impl SnappyCodec {
+  pub fn new() -> Self { SnappyCodec }
+}
+
+impl Codec for SnappyCodec {
+  fn compress(&mut self, input_buf: &[u8], output_buf: &mut Vec<u8>) -> Result<()> {
+    compress_to_spare(input_buf, output_buf);
+    Ok(())
+  }
+
+  /// `uncompress_size`, when known from the page header, must be the length stored in the block
+  fn decompress(&mut self, input_buf: &[u8], output_buf: &mut Vec<u8>, uncompress_size: Option<usize>) -> Result<usize> {
+    let len = decompress_len(input_buf).map_err(|e| ParquetError::External(Box::new(e)))?;
+    match uncompress_size {
+      Some(size) if size != len => Err(ParquetError::General(format!("snappy page of {} bytes, expected {}", len, size))),
+      _ => decompress_to_spare(input_buf, output_buf).map_err(|e| ParquetError::External(Box::new(e))),
+    }
+  }
+}
@@ -0,0 +1,114 @@
+//! One-shot compression and decompression into buffers from a thread-local pool
+//!
+//! Like `compress` and `decompress`, but output comes in a `PooledBuf` going back to the pool of its thread on drop,
+//! so a service calling them per request does not allocate once the pool is warm. Each thread keeps at most
+//! 4 buffers of up to 1 MiB by default, see `set_limits`.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::Deref;
+
+use crate::{snappy_compress, snappy_max_compressed_length, snappy_uncompress, snappy_uncompressed_length, status, SnappyError};
+
+struct Pool {
+  buffers: Vec<Vec<u8>>,
+  max_buffers: usize,
+  /// Larger buffers are freed instead of kept
+  max_len: usize,
+}
+
+thread_local! {
+  static POOL: RefCell<Pool> = const { RefCell::new(Pool { buffers: Vec::new(), max_buffers: 4, max_len: 1 << 20 }) };
+}
+
+/// Keep at most `max_buffers` buffers of up to `max_len` bytes in the pool of the current thread
+///
+/// Buffers over the new limits are freed now.
+pub fn set_limits(max_buffers: usize, max_len: usize) {
+  POOL.with(|pool| {
+    let mut pool = pool.borrow_mut();
+    pool.max_buffers = max_buffers;
+    pool.max_len = max_len;
+    pool.buffers.retain(|buffer| buffer.capacity() <= max_len);
+    pool.buffers.truncate(max_buffers);
+  })
+}
+
+/// Free the buffers in the pool of the current thread
+pub fn clear() { POOL.with(|pool| pool.borrow_mut().buffers.clear()) }
+
+/// Empty buffer of at least `len` bytes capacity, from the pool if it has one
+fn take(len: usize) -> Vec<u8> {
+  let buffer = POOL.with(|pool| pool.borrow_mut().buffers.pop());
+  let mut buffer = buffer.unwrap_or_default();
+  buffer.reserve(len);
+  buffer
+}
+
+/// Output buffer lent from the pool of the thread that made it, given back on drop
+///
+/// Dropped on another thread, it goes to the pool of that thread.
+pub struct PooledBuf {
+  buffer: Vec<u8>,
+}
+
+impl PooledBuf {
+  /// Take the buffer out of the pool for good
+  pub fn into_vec(mut self) -> Vec<u8> { std::mem::take(&mut self.buffer) }
+}
+
+impl Deref for PooledBuf {
+  type Target = [u8];
+
+  fn deref(&self) -> &[u8] { &self.buffer }
+}
+
+impl AsRef<[u8]> for PooledBuf {
+  fn as_ref(&self) -> &[u8] { &self.buffer }
+}
+
+impl fmt::Debug for PooledBuf {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.debug_tuple("PooledBuf").field(&&self.buffer[..]).finish() }
+}
+
+impl Drop for PooledBuf {
+  fn drop(&mut self) {
+    let mut buffer = std::mem::take(&mut self.buffer);
+    if buffer.capacity() == 0 { return }
+    buffer.clear();
+    // the pool may be gone already when thread-locals are destroyed
+    let _ = POOL.try_with(|pool| {
+      let mut pool = pool.borrow_mut();
+      if pool.buffers.len() < pool.max_buffers && buffer.capacity() <= pool.max_len { pool.buffers.push(buffer) }
+    });
+  }
+}
+
+/// Compress a byte slice, see `compress`
+pub fn compress(input: &[u8]) -> PooledBuf {
+  unsafe {
+    let mut output_len = snappy_max_compressed_length(input.len());
+    let mut output = take(output_len);
+
+    let result = status(snappy_compress(input.as_ptr(), input.len(), output.as_mut_ptr(), &mut output_len));
+    debug_assert!(result.is_ok(), "buffer of max compressed length is always enough");
+
+    output.set_len(output_len);
+    PooledBuf { buffer: output }
+  }
+}
+
+/// Decompress a byte slice, see `decompress`
+pub fn decompress(input: &[u8]) -> Result<PooledBuf, SnappyError> {
+  unsafe {
+    let mut output_len = 0;
+    status(snappy_uncompressed_length(input.as_ptr(), input.len(), &mut output_len))?;
+
+    // given back to the pool on error too
+    let mut output = PooledBuf { buffer: take(output_len) };
+    status(snappy_uncompress(input.as_ptr(), input.len(), output.buffer.as_mut_ptr(), &mut output_len))?;
+
+    output.buffer.set_len(output_len);
+    Ok(output)
+  }
+}
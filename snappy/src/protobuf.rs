@@ -0,0 +1,47 @@
+//! protobuf messages of prost in compressed message streams, with the `prost` feature
+//!
+//! Each message is encoded, then written by `MessageWriter` as a varint length and a raw block of it: protobuf
+//! delimited messages with snappy bodies, as event logs of several Go services are framed. `Protos` replays them.
+
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+use prost::Message;
+
+use crate::message::{MessageReader, MessageWriter};
+
+impl<W: Write> MessageWriter<W> {
+  /// Encode `message`, then compress and write it with its length prefix
+  pub fn write_proto<M: Message>(&mut self, message: &M) -> io::Result<()> { self.write_message(&message.encode_to_vec()) }
+}
+
+impl<R: Read> MessageReader<R> {
+  /// Read, decompress and decode the next message, `None` at EOF between messages
+  ///
+  /// A body not decoding as `M` gives `InvalidData`.
+  pub fn read_proto<M: Message + Default>(&mut self) -> io::Result<Option<M>> {
+    match self.read_message()? {
+      Some(body) => M::decode(&body[..]).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+      None => Ok(None),
+    }
+  }
+
+  /// Iterator over the messages left, decoded as `M`
+  pub fn protos<M: Message + Default>(self) -> Protos<R, M> { Protos { reader: self, message: PhantomData } }
+}
+
+/// Iterator over the protobuf messages of a `MessageReader`, see `MessageReader::protos`
+pub struct Protos<R: Read, M> {
+  reader: MessageReader<R>,
+  message: PhantomData<fn() -> M>,
+}
+
+impl<R: Read, M> Protos<R, M> {
+  pub fn into_inner(self) -> MessageReader<R> { self.reader }
+}
+
+impl<R: Read, M: Message + Default> Iterator for Protos<R, M> {
+  type Item = io::Result<M>;
+
+  fn next(&mut self) -> Option<io::Result<M>> { self.reader.read_proto().transpose() }
+}
@@ -0,0 +1,508 @@
+//! Raw snappy block format in Rust, backend of the `pure-rust` feature and of wasm32 targets
+//!
+//! With the feature on, `snappy_compress` and the other functions of the C API at the crate root come from `ffi`
+//! here instead of libsnappy, so every part of the crate uses this backend; build with `default-features = false`
+//! to drop the `libsnappy` feature and need no C library at all, e.g. for wasm or cross-compiling, adding back `std`
+//! unless on `core` and `alloc` only.
+//!
+//! Output is valid snappy that any implementation reads, though not byte for byte the output of libsnappy.
+//! Output buffers are `MaybeUninit` so callers need not zero-fill them; only written bytes are ever read back.
+//!
+//! Copies of the decoder and match extension of the encoder use SIMD on x86_64 and aarch64, and on wasm32 with
+//! `simd128` enabled.
+
+use core::mem::MaybeUninit;
+
+use crate::SnappyError;
+
+/// Input is compressed in blocks of this size, so positions in a block fit `u16`
+const BLOCK_SIZE: usize = 1 << 16;
+/// Entries of the hash table of `compress`, the most `compress_with_table` uses
+pub const MAX_TABLE_SIZE: usize = 1 << 14;
+/// Fewest entries of a hash table of `compress_with_table`
+pub const MIN_TABLE_SIZE: usize = 1 << 8;
+
+/// Max length of the compressed form of `len` bytes, same bound as libsnappy
+///
+/// `const`, so fixed output buffers can be sized at compile time.
+pub const fn max_compressed_length(len: usize) -> usize { 32 + len + len / 6 }
+
+/// Compress `input` into `output`, returns the compressed length
+///
+/// `output` must hold at least `max_compressed_length(input.len())` bytes, or `InsufficientBuffer` is returned.
+/// Nothing is allocated, the hash table of `MAX_TABLE_SIZE` entries is on the stack.
+pub fn compress(input: &[u8], output: &mut [MaybeUninit<u8>]) -> Result<usize, SnappyError> {
+  compress_uninit_with_table(input, &mut [0; MAX_TABLE_SIZE], output, 1)
+}
+
+/// Same as `compress` at compression `level`: 1 is the fastest, 2 or over a little slower and denser
+///
+/// Level 2 looks for a match at every position instead of stepping over incompressible data, and remembers the
+/// positions in matches too.
+pub fn compress_level(input: &[u8], output: &mut [MaybeUninit<u8>], level: u8) -> Result<usize, SnappyError> {
+  compress_uninit_with_table(input, &mut [0; MAX_TABLE_SIZE], output, level)
+}
+
+/// Compress `input` into `output` with the caller's hash `table`, returns the compressed length
+///
+/// For targets without heap or much stack: both buffers may be static. `table` length must be a power of two from
+/// `MIN_TABLE_SIZE` to `MAX_TABLE_SIZE`, else `InvalidInput`; smaller tables find fewer matches. Its contents do
+/// not matter and are overwritten. `decompress_into` at the crate root is the heap-free way back.
+pub fn compress_with_table(input: &[u8], table: &mut [u16], output: &mut [u8]) -> Result<usize, SnappyError> {
+  // only initialized bytes are ever written to it
+  let output = unsafe { &mut *(output as *mut [u8] as *mut [MaybeUninit<u8>]) };
+  compress_uninit_with_table(input, table, output, 1)
+}
+
+fn compress_uninit_with_table(input: &[u8], table: &mut [u16], output: &mut [MaybeUninit<u8>], level: u8) -> Result<usize, SnappyError> {
+  if !table.len().is_power_of_two() || !(MIN_TABLE_SIZE..=MAX_TABLE_SIZE).contains(&table.len()) { return Err(SnappyError::InvalidInput) }
+  if output.len() < max_compressed_length(input.len()) { return Err(SnappyError::InsufficientBuffer) }
+  if input.len() > u32::MAX as usize { return Err(SnappyError::InvalidInput) }
+
+  let mut output = Output { buf: output, pos: 0 };
+  let mut len = input.len();
+  while len >= 0x80 {
+    output.push(len as u8 | 0x80);
+    len >>= 7;
+  }
+  output.push(len as u8);
+
+  for block in input.chunks(BLOCK_SIZE) { compress_block(block, table, &mut output, level >= 2) }
+  Ok(output.pos)
+}
+
+fn load32(data: &[u8], pos: usize) -> u32 { u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) }
+
+/// Slot of 4 bytes in a table of `1 << bits` entries
+fn hash(bytes: u32, bits: u32) -> usize { (bytes.wrapping_mul(0x1e35_a7bd) >> (32 - bits)) as usize }
+
+/// Compress one block: literals until a 4-byte match is found through the hash table, then a copy of the whole match
+///
+/// Like libsnappy, the search steps further the longer no match is found, so incompressible data goes fast;
+/// `dense` searches every position and also hashes the last ones of each match.
+fn compress_block(block: &[u8], table: &mut [u16], output: &mut Output, dense: bool) {
+  table.fill(0);
+  let bits = table.len().trailing_zeros();
+  let mut literal_start = 0;
+  let mut pos = 1;
+  let mut skip = 32;
+  while pos + 4 <= block.len() {
+    let bytes = load32(block, pos);
+    let slot = &mut table[hash(bytes, bits)];
+    let candidate = *slot as usize;
+    *slot = pos as u16;
+    if candidate >= pos || load32(block, candidate) != bytes {
+      pos += skip >> 5;
+      if !dense { skip += 1 }
+      continue
+    }
+
+    let len = 4 + simd::common_prefix(&block[candidate + 4..], &block[pos + 4..]);
+    output.literal(&block[literal_start..pos]);
+    output.copy(pos - candidate, len);
+    pos += len;
+    literal_start = pos;
+    skip = 32;
+    if dense {
+      for matched in pos - 2..pos {
+        if matched + 4 <= block.len() { table[hash(load32(block, matched), bits)] = matched as u16 }
+      }
+    }
+  }
+  output.literal(&block[literal_start..]);
+}
+
+/// Compressed output written so far
+struct Output<'a> {
+  buf: &'a mut [MaybeUninit<u8>],
+  pos: usize,
+}
+
+impl Output<'_> {
+  fn push(&mut self, byte: u8) {
+    self.buf[self.pos].write(byte);
+    self.pos += 1;
+  }
+
+  fn extend(&mut self, data: &[u8]) {
+    for (slot, &byte) in self.buf[self.pos..self.pos + data.len()].iter_mut().zip(data) { slot.write(byte); }
+    self.pos += data.len();
+  }
+
+  fn literal(&mut self, data: &[u8]) {
+    if data.is_empty() { return }
+    let n = data.len() - 1;
+    if n < 60 {
+      self.push((n as u8) << 2);
+    } else {
+      let bytes = (usize::BITS - n.leading_zeros()).div_ceil(8) as usize;
+      self.push((59 + bytes as u8) << 2);
+      self.extend(&n.to_le_bytes()[..bytes]);
+    }
+    self.extend(data);
+  }
+
+  /// Copies of up to 64 bytes, the last one at least 4 long
+  fn copy(&mut self, offset: usize, mut len: usize) {
+    while len >= 68 {
+      self.copy_one(offset, 64);
+      len -= 64;
+    }
+    if len > 64 {
+      self.copy_one(offset, 60);
+      len -= 60;
+    }
+    self.copy_one(offset, len);
+  }
+
+  fn copy_one(&mut self, offset: usize, len: usize) {
+    if len < 12 && offset < 2048 {
+      self.push(1 | ((len - 4) << 2) as u8 | ((offset >> 8) << 5) as u8);
+      self.push(offset as u8);
+    } else {
+      self.push(2 | ((len - 1) << 2) as u8);
+      self.extend(&(offset as u16).to_le_bytes());
+    }
+  }
+}
+
+/// Length stored in the header of compressed `input`, and the header length
+fn header(input: &[u8]) -> Result<(usize, usize), SnappyError> {
+  let mut len = 0u64;
+  for (i, &byte) in input.iter().take(5).enumerate() {
+    len |= u64::from(byte & 0x7f) << (7 * i);
+    if byte & 0x80 == 0 {
+      return if len > u64::from(u32::MAX) { Err(SnappyError::InvalidInput) } else { Ok((len as usize, i + 1)) }
+    }
+  }
+  Err(SnappyError::InvalidInput)
+}
+
+/// Uncompressed length stored in the header of compressed `input`
+pub fn uncompressed_length(input: &[u8]) -> Result<usize, SnappyError> { header(input).map(|(len, _)| len) }
+
+/// Decompress `input` into `output`, returns the uncompressed length
+///
+/// `output` must hold at least `uncompressed_length(input)` bytes, or `InsufficientBuffer` is returned.
+pub fn decompress(input: &[u8], output: &mut [MaybeUninit<u8>]) -> Result<usize, SnappyError> {
+  let len = uncompressed_length(input)?;
+  if len > output.len() { return Err(SnappyError::InsufficientBuffer) }
+  let mut sink = Writer { buf: &mut output[..len], pos: 0 };
+  parse(input, &mut sink)?;
+  Ok(len)
+}
+
+/// Whether `input` decompresses without error, checked without writing anything
+pub fn validate(input: &[u8]) -> bool { parse(input, &mut Counter).is_ok() }
+
+/// Destination of the elements of compressed data, bounds already checked by `parse`
+trait Sink {
+  fn literal(&mut self, data: &[u8]);
+  fn copy(&mut self, pos: usize, offset: usize, len: usize);
+}
+
+/// Sink of `validate`, length is counted by `parse`
+struct Counter;
+
+impl Sink for Counter {
+  fn literal(&mut self, _: &[u8]) {}
+  fn copy(&mut self, _: usize, _: usize, _: usize) {}
+}
+
+struct Writer<'a> {
+  buf: &'a mut [MaybeUninit<u8>],
+  pos: usize,
+}
+
+impl Sink for Writer<'_> {
+  fn literal(&mut self, data: &[u8]) {
+    let dst = &mut self.buf[self.pos..self.pos + data.len()];
+    unsafe { simd::copy(data.as_ptr(), dst.as_mut_ptr() as *mut u8, data.len(), usize::MAX) }
+    self.pos += data.len();
+  }
+
+  fn copy(&mut self, pos: usize, offset: usize, len: usize) {
+    // written earlier, as `parse` checks offsets against the output length so far
+    assert!(offset <= pos && pos + len <= self.buf.len());
+    // both pointers from one borrow, as the ranges may overlap
+    let base = self.buf.as_mut_ptr() as *mut u8;
+    unsafe { simd::copy(base.add(pos - offset), base.add(pos), len, offset) }
+    self.pos += len;
+  }
+}
+
+/// Copy loops of the decoder and match extension of the encoder, with SIMD where the target has it
+///
+/// x86_64 has SSE2 always and AVX2 when detected at runtime, or enabled at compile time without `std`;
+/// wasm32 has simd128 when enabled at compile time.
+mod simd {
+  /// Copy `len` bytes from `src` to `dst` in order, `distance` bytes ahead of `src`
+  ///
+  /// Each byte is read after those before it are written, so a copy closer than its length repeats a pattern:
+  /// wide loads are only used when `distance` is at least their width.
+  ///
+  /// # Safety
+  ///
+  /// `src` must be valid for reads and `dst` for writes of `len` bytes, `dst` being `distance` bytes after `src`
+  /// if they overlap.
+  pub(super) unsafe fn copy(src: *const u8, dst: *mut u8, len: usize, distance: usize) {
+    #[cfg(target_arch = "x86_64")]
+    let done = if distance >= 32 && has_avx2() { x86::copy_avx2(src, dst, len) } else if distance >= 16 { x86::copy_sse2(src, dst, len) } else { 0 };
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    let done = if distance >= 16 { neon::copy(src, dst, len) } else { 0 };
+    #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+    let done = if distance >= 16 { wasm::copy(src, dst, len) } else { 0 };
+    #[cfg(not(any(target_arch = "x86_64", all(target_arch = "aarch64", target_feature = "neon"), all(target_arch = "wasm32", target_feature = "simd128"))))]
+    let done = { let _ = distance; 0 };
+
+    for i in done..len { *dst.add(i) = *src.add(i) }
+  }
+
+  /// Length of the common prefix of `a` and `b`
+  pub(super) fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    let len = a.len().min(b.len());
+    #[cfg(target_arch = "x86_64")]
+    let (done, found) = unsafe { if has_avx2() { x86::common_prefix_avx2(a, b, len) } else { x86::common_prefix_sse2(a, b, len) } };
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    let (done, found) = unsafe { neon::common_prefix(a, b, len) };
+    #[cfg(not(any(target_arch = "x86_64", all(target_arch = "aarch64", target_feature = "neon"))))]
+    let (done, found) = (0, false);
+
+    if found { return done }
+    done + a[done..len].iter().zip(&b[done..len]).take_while(|(a, b)| a == b).count()
+  }
+
+  #[cfg(all(target_arch = "x86_64", feature = "std"))]
+  fn has_avx2() -> bool { std::is_x86_feature_detected!("avx2") }
+  #[cfg(all(target_arch = "x86_64", not(feature = "std")))]
+  fn has_avx2() -> bool { cfg!(target_feature = "avx2") }
+
+  #[cfg(target_arch = "x86_64")]
+  mod x86 {
+    use core::arch::x86_64::*;
+
+    /// Copy whole 16-byte chunks of `copy`, returns the length done
+    pub(super) unsafe fn copy_sse2(src: *const u8, dst: *mut u8, len: usize) -> usize {
+      let mut i = 0;
+      while i + 16 <= len {
+        _mm_storeu_si128(dst.add(i) as *mut __m128i, _mm_loadu_si128(src.add(i) as *const __m128i));
+        i += 16;
+      }
+      i
+    }
+
+    /// Copy whole 32-byte chunks of `copy`, returns the length done
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn copy_avx2(src: *const u8, dst: *mut u8, len: usize) -> usize {
+      let mut i = 0;
+      while i + 32 <= len {
+        _mm256_storeu_si256(dst.add(i) as *mut __m256i, _mm256_loadu_si256(src.add(i) as *const __m256i));
+        i += 32;
+      }
+      i
+    }
+
+    /// Compare whole 16-byte chunks of the first `len` bytes, returns the length equal and whether a difference is found
+    pub(super) unsafe fn common_prefix_sse2(a: &[u8], b: &[u8], len: usize) -> (usize, bool) {
+      let mut i = 0;
+      while i + 16 <= len {
+        let equal = _mm_cmpeq_epi8(_mm_loadu_si128(a.as_ptr().add(i) as *const __m128i), _mm_loadu_si128(b.as_ptr().add(i) as *const __m128i));
+        let mask = _mm_movemask_epi8(equal) as u32;
+        if mask != 0xffff { return (i + (!mask).trailing_zeros() as usize, true) }
+        i += 16;
+      }
+      (i, false)
+    }
+
+    /// `common_prefix_sse2` by 32-byte chunks
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn common_prefix_avx2(a: &[u8], b: &[u8], len: usize) -> (usize, bool) {
+      let mut i = 0;
+      while i + 32 <= len {
+        let equal = _mm256_cmpeq_epi8(_mm256_loadu_si256(a.as_ptr().add(i) as *const __m256i), _mm256_loadu_si256(b.as_ptr().add(i) as *const __m256i));
+        let mask = _mm256_movemask_epi8(equal) as u32;
+        if mask != u32::MAX { return (i + (!mask).trailing_zeros() as usize, true) }
+        i += 32;
+      }
+      (i, false)
+    }
+  }
+
+  #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+  mod neon {
+    use core::arch::aarch64::*;
+
+    /// Copy whole 16-byte chunks of `copy`, returns the length done
+    pub(super) unsafe fn copy(src: *const u8, dst: *mut u8, len: usize) -> usize {
+      let mut i = 0;
+      while i + 16 <= len {
+        vst1q_u8(dst.add(i), vld1q_u8(src.add(i)));
+        i += 16;
+      }
+      i
+    }
+
+    /// Compare whole 16-byte chunks of the first `len` bytes, returns the length equal and whether a difference is found
+    pub(super) unsafe fn common_prefix(a: &[u8], b: &[u8], len: usize) -> (usize, bool) {
+      let mut i = 0;
+      while i + 16 <= len {
+        let equal = vreinterpretq_u64_u8(vceqq_u8(vld1q_u8(a.as_ptr().add(i)), vld1q_u8(b.as_ptr().add(i))));
+        // lanes of all ones where equal, the first byte of zeros is the first difference
+        let (low, high) = (vgetq_lane_u64::<0>(equal), vgetq_lane_u64::<1>(equal));
+        if low != u64::MAX { return (i + (!low).trailing_zeros() as usize / 8, true) }
+        if high != u64::MAX { return (i + 8 + (!high).trailing_zeros() as usize / 8, true) }
+        i += 16;
+      }
+      (i, false)
+    }
+  }
+
+  #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+  mod wasm {
+    use core::arch::wasm32::{v128, v128_load, v128_store};
+
+    /// Copy whole 16-byte chunks of `copy`, returns the length done
+    pub(super) unsafe fn copy(src: *const u8, dst: *mut u8, len: usize) -> usize {
+      let mut i = 0;
+      while i + 16 <= len {
+        v128_store(dst.add(i) as *mut v128, v128_load(src.add(i) as *const v128));
+        i += 16;
+      }
+      i
+    }
+  }
+}
+
+/// Walk the elements of compressed `input` into `sink`, checking each stays in the input and in the stored length
+fn parse(input: &[u8], sink: &mut impl Sink) -> Result<(), SnappyError> {
+  let (len, mut pos) = header(input)?;
+  let mut written = 0;
+  let take = |pos: &mut usize, n: usize| -> Result<usize, SnappyError> {
+    let bytes = input.get(*pos..*pos + n).ok_or(SnappyError::InvalidInput)?;
+    *pos += n;
+    Ok(bytes.iter().rev().fold(0, |value, &byte| value << 8 | byte as usize))
+  };
+
+  while pos < input.len() {
+    let tag = input[pos];
+    pos += 1;
+    let (offset, n) = match tag & 3 {
+      0 => {
+        let mut n = (tag >> 2) as usize;
+        if n >= 60 { n = take(&mut pos, n - 59)? }
+        let end = pos.checked_add(n).and_then(|end| end.checked_add(1)).ok_or(SnappyError::InvalidInput)?;
+        let data = input.get(pos..end).ok_or(SnappyError::InvalidInput)?;
+        if written + data.len() > len { return Err(SnappyError::InvalidInput) }
+        sink.literal(data);
+        pos += data.len();
+        written += data.len();
+        continue
+      },
+      1 => (((tag as usize >> 5) << 8) | take(&mut pos, 1)?, 4 + ((tag >> 2) & 7) as usize),
+      2 => (take(&mut pos, 2)?, (tag >> 2) as usize + 1),
+      _ => (take(&mut pos, 4)?, (tag >> 2) as usize + 1),
+    };
+    if offset == 0 || offset > written || written + n > len { return Err(SnappyError::InvalidInput) }
+    sink.copy(written, offset, n);
+    written += n;
+  }
+  if written != len { return Err(SnappyError::InvalidInput) }
+  Ok(())
+}
+
+/// The libsnappy C API over this backend, same names and contracts as in `snappy-sys`
+///
+/// Re-exported at the crate root with the `pure-rust` feature, in place of `raw`.
+pub mod ffi {
+  use core::ffi::c_int;
+  use core::mem::MaybeUninit;
+  use core::slice;
+
+  use crate::SnappyError;
+
+  /// Operation succeed, no exception
+  pub const SNAPPY_OK: c_int = 0;
+  /// Bad input buffer given
+  pub const SNAPPY_INVALID_INPUT: c_int = 1;
+  /// Allocated buffer too small
+  pub const SNAPPY_BUFFER_TOO_SMALL: c_int = 2;
+  /// Highest level `snappy_compress_level` tells apart
+  pub const SNAPPY_MAX_LEVEL: c_int = 2;
+
+  /// C `size_t`, same as `usize` on every platform Rust supports
+  #[allow(non_camel_case_types)]
+  pub type size_t = usize;
+
+  fn code(result: Result<(), SnappyError>) -> c_int {
+    match result {
+      Ok(()) => SNAPPY_OK,
+      Err(SnappyError::InsufficientBuffer) => SNAPPY_BUFFER_TOO_SMALL,
+      Err(_) => SNAPPY_INVALID_INPUT,
+    }
+  }
+
+  /// Slice of `len` bytes at `ptr`, which may be null when `len` is 0
+  unsafe fn input<'a>(ptr: *const u8, len: size_t) -> &'a [u8] {
+    if len == 0 { &[] } else { slice::from_raw_parts(ptr, len) }
+  }
+
+  unsafe fn output<'a>(ptr: *mut u8, len: size_t) -> &'a mut [MaybeUninit<u8>] {
+    if len == 0 { &mut [] } else { slice::from_raw_parts_mut(ptr as *mut MaybeUninit<u8>, len) }
+  }
+
+  /// See `snappy_sys::snappy_compress`
+  ///
+  /// # Safety
+  ///
+  /// `input` must be valid for `length` bytes, `compressed` valid for writes of `*compressed_length` bytes.
+  pub unsafe fn snappy_compress(input: *const u8, length: size_t, compressed: *mut u8, compressed_length: *mut size_t) -> c_int {
+    let result = super::compress(self::input(input, length), output(compressed, *compressed_length));
+    code(result.map(|len| *compressed_length = len))
+  }
+
+  /// See `snappy_sys::snappy_compress_level`
+  ///
+  /// # Safety
+  ///
+  /// Same as `snappy_compress`.
+  pub unsafe fn snappy_compress_level(input: *const u8, length: size_t, compressed: *mut u8, compressed_length: *mut size_t, level: c_int) -> c_int {
+    let result = super::compress_level(self::input(input, length), output(compressed, *compressed_length), level.clamp(1, SNAPPY_MAX_LEVEL) as u8);
+    code(result.map(|len| *compressed_length = len))
+  }
+
+  /// See `snappy_sys::snappy_uncompress`
+  ///
+  /// # Safety
+  ///
+  /// `compressed` must be valid for `compressed_length` bytes, `uncompressed` valid for writes of
+  /// `*uncompressed_length` bytes.
+  pub unsafe fn snappy_uncompress(compressed: *const u8, compressed_length: size_t, uncompressed: *mut u8, uncompressed_length: *mut size_t) -> c_int {
+    let result = super::decompress(input(compressed, compressed_length), output(uncompressed, *uncompressed_length));
+    code(result.map(|len| *uncompressed_length = len))
+  }
+
+  /// See `snappy_sys::snappy_max_compressed_length`
+  ///
+  /// # Safety
+  ///
+  /// Always safe, `unsafe` only to match the C API.
+  pub unsafe fn snappy_max_compressed_length(source_length: size_t) -> size_t { super::max_compressed_length(source_length) }
+
+  /// See `snappy_sys::snappy_uncompressed_length`
+  ///
+  /// # Safety
+  ///
+  /// `compressed` must be valid for `compressed_length` bytes.
+  pub unsafe fn snappy_uncompressed_length(compressed: *const u8, compressed_length: size_t, result: *mut size_t) -> c_int {
+    code(super::uncompressed_length(input(compressed, compressed_length)).map(|len| *result = len))
+  }
+
+  /// See `snappy_sys::snappy_validate_compressed_buffer`
+  ///
+  /// # Safety
+  ///
+  /// `compressed` must be valid for `compressed_length` bytes.
+  pub unsafe fn snappy_validate_compressed_buffer(compressed: *const u8, compressed_length: size_t) -> c_int {
+    if super::validate(input(compressed, compressed_length)) { SNAPPY_OK } else { SNAPPY_INVALID_INPUT }
+  }
+}
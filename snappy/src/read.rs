@@ -0,0 +1,50 @@
+//! Streaming decompression through `io::Read`
+
+use std::io::{self, Read};
+use std::ops::Range;
+
+use crate::frame::{DecoderOptions, FrameDecoder, Metadata};
+
+/// Decompressing reader of a framed stream
+///
+/// Chunks are decoded one at a time when needed, so streams of any size can be read.
+#[derive(Clone)]
+pub struct SnappyReader<R: Read> {
+  decoder: FrameDecoder<R>,
+  /// Position in the current block
+  pos: usize,
+}
+
+impl<R: Read> SnappyReader<R> {
+  pub fn new(inner: R) -> Self { SnappyReader::with_options(inner, DecoderOptions::new()) }
+
+  /// Reader with decoder `options`
+  pub fn with_options(inner: R, options: DecoderOptions) -> Self {
+    SnappyReader { decoder: FrameDecoder::with_options(inner, options), pos: 0 }
+  }
+
+  /// Take metadata chunks met so far, see `FrameDecoder::metadata`
+  pub fn metadata(&mut self) -> impl Iterator<Item = Metadata> + '_ { self.decoder.metadata() }
+
+  /// Take byte ranges skipped as corrupted so far, see `FrameDecoder::skipped`
+  pub fn skipped(&mut self) -> impl Iterator<Item = Range<u64>> + '_ { self.decoder.skipped() }
+
+  pub fn get_ref(&self) -> &R { self.decoder.get_ref() }
+  pub fn get_mut(&mut self) -> &mut R { self.decoder.get_mut() }
+  pub fn into_inner(self) -> R { self.decoder.into_inner() }
+}
+
+impl<R: Read> Read for SnappyReader<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    while self.pos == self.decoder.current_block().len() {
+      if self.decoder.read_block()?.is_none() { return Ok(0) }
+      self.pos = 0;
+    }
+
+    let block = &self.decoder.current_block()[self.pos..];
+    let len = block.len().min(buf.len());
+    buf[..len].copy_from_slice(&block[..len]);
+    self.pos += len;
+    Ok(len)
+  }
+}
@@ -0,0 +1,188 @@
+//! Random access into framed streams through `io::Seek`
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::block::{Codec, Snappy};
+use crate::frame::{check_chunk_header, decode_chunk, invalid, max_compressed_chunk_len, parse_chunk_header, read_full, truncated, ChunkPos,
+  ChunkRead, CHUNK_COMPRESSED, CHUNK_HEADER_SIZE, CHUNK_STREAM_IDENTIFIER, CHUNK_UNCOMPRESSED, MAX_BLOCK_SIZE};
+use crate::{snappy_uncompressed_length, status, SnappyError};
+
+/// Position of one data chunk, in the framed stream and in the uncompressed data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexEntry {
+  /// Offset of the chunk header
+  pub compressed_offset: u64,
+  pub uncompressed_offset: u64,
+  pub uncompressed_len: usize,
+}
+
+/// Map of data chunks to uncompressed positions, for `SeekableDecoder`
+///
+/// Built by `FrameEncoder` with `EncoderOptions::index`, or by scanning a stream with `FrameIndex::scan`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrameIndex {
+  entries: Vec<IndexEntry>,
+  uncompressed_len: u64,
+}
+
+impl FrameIndex {
+  pub fn new() -> Self { FrameIndex::default() }
+
+  /// Add a data chunk at `compressed_offset` holding `len` bytes of data, after the ones added before
+  pub(crate) fn push(&mut self, compressed_offset: u64, len: usize) {
+    // empty chunks can't contain any position
+    if len != 0 {
+      self.entries.push(IndexEntry { compressed_offset, uncompressed_offset: self.uncompressed_len, uncompressed_len: len });
+    }
+    self.uncompressed_len += len as u64;
+  }
+
+  /// Index the framed stream in `reader` from its current position up to its end, chunk offsets are positions in `reader`
+  ///
+  /// Only chunk headers and the length preamble of compressed data are read, chunk bodies are seeked over;
+  /// checksums are not verified here.
+  pub fn scan<R: Read + Seek>(reader: &mut R) -> io::Result<FrameIndex> {
+    let mut offset = reader.stream_position()?;
+    let end = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(offset))?;
+
+    let mut index = FrameIndex::new();
+    let mut header_read = false;
+    while offset < end {
+      let mut header = [0u8; CHUNK_HEADER_SIZE];
+      let n = read_full(reader, &mut header)?;
+      if n != CHUNK_HEADER_SIZE { return Err(invalid(truncated(n, CHUNK_HEADER_SIZE))) }
+      let (kind, len) = parse_chunk_header(&header);
+      check_chunk_header(kind, len, header_read, false, offset).map_err(invalid)?;
+      let body_offset = offset + CHUNK_HEADER_SIZE as u64;
+      if body_offset + len as u64 > end { return Err(invalid(SnappyError::TruncatedStream { missing: body_offset + len as u64 - end })) }
+
+      match kind {
+        CHUNK_STREAM_IDENTIFIER => header_read = true,
+        CHUNK_COMPRESSED => {
+          // checksum and varint of uncompressed length
+          let mut preamble = [0u8; 4 + 5];
+          let preamble_len = read_full(reader, &mut preamble[..len.min(9)])?;
+          let mut block_len = 0;
+          unsafe { status(snappy_uncompressed_length(preamble[4..].as_ptr(), preamble_len - 4, &mut block_len)) }.map_err(invalid)?;
+          if block_len > MAX_BLOCK_SIZE { return Err(invalid(SnappyError::BadChunkHeader { offset })) }
+          index.push(offset, block_len);
+        },
+        CHUNK_UNCOMPRESSED => index.push(offset, len - 4),
+        _ => (),
+      }
+
+      offset = body_offset + len as u64;
+      reader.seek(SeekFrom::Start(offset))?;
+    }
+    Ok(index)
+  }
+
+  /// Data chunks in stream order
+  pub fn entries(&self) -> &[IndexEntry] { &self.entries }
+
+  /// Total length of the uncompressed data
+  pub fn uncompressed_len(&self) -> u64 { self.uncompressed_len }
+
+  /// Entry of the chunk holding uncompressed position `pos`
+  pub fn find(&self, pos: u64) -> Option<usize> {
+    if pos >= self.uncompressed_len { return None }
+    Some(self.entries.partition_point(|entry| entry.uncompressed_offset <= pos) - 1)
+  }
+}
+
+/// Decompressing reader of a framed stream, with random access through `Seek`
+///
+/// Seeking only moves the position; the one chunk holding it is decoded on the next read,
+/// so nothing before it needs to be decompressed. Checksums are always verified.
+pub struct SeekableDecoder<R: Read + Seek> {
+  inner: R,
+  index: FrameIndex,
+  pos: u64,
+  /// Entry of the block decoded in `block`
+  current: Option<usize>,
+  chunk: Vec<u8>,
+  block: Vec<u8>,
+  codec: &'static dyn Codec,
+}
+
+impl<R: Read + Seek> SeekableDecoder<R> {
+  /// Decoder over `inner`, indexed by `FrameIndex::scan` from its current position
+  pub fn new(mut inner: R) -> io::Result<Self> {
+    let index = FrameIndex::scan(&mut inner)?;
+    Ok(SeekableDecoder::with_index(inner, index))
+  }
+
+  /// Decoder over `inner` with an index built before, chunk offsets must be positions in `inner`
+  pub fn with_index(inner: R, index: FrameIndex) -> Self {
+    SeekableDecoder {
+      inner, index, pos: 0, current: None, chunk: vec![0; max_compressed_chunk_len()], block: vec![0; MAX_BLOCK_SIZE], codec: &Snappy,
+    }
+  }
+
+  /// Decompress chunks with `codec` instead of snappy, see `EncoderOptions::codec`
+  ///
+  /// `scan` reads the snappy length preamble: index such streams with `EncoderOptions::index` and `with_index`.
+  pub fn codec(mut self, codec: &'static dyn Codec) -> Self { self.codec = codec; self }
+
+  pub fn index(&self) -> &FrameIndex { &self.index }
+
+  /// Decode the chunk of index entry `i` into `block`
+  fn load(&mut self, i: usize) -> io::Result<()> {
+    self.current = None;
+    let entry = self.index.entries[i];
+    self.inner.seek(SeekFrom::Start(entry.compressed_offset))?;
+
+    let pos = ChunkPos { offset: entry.compressed_offset, data_index: i as u64 };
+    let bad_header = || invalid(SnappyError::BadChunkHeader { offset: pos.offset });
+    let mut header = [0u8; CHUNK_HEADER_SIZE];
+    let n = read_full(&mut self.inner, &mut header)?;
+    if n != CHUNK_HEADER_SIZE { return Err(invalid(truncated(n, CHUNK_HEADER_SIZE))) }
+    let (kind, len) = parse_chunk_header(&header);
+    if kind != CHUNK_COMPRESSED && kind != CHUNK_UNCOMPRESSED { return Err(bad_header()) }
+    check_chunk_header(kind, len, true, false, pos.offset).map_err(invalid)?;
+    let n = read_full(&mut self.inner, &mut self.chunk[..len])?;
+    if n != len { return Err(invalid(truncated(n, len))) }
+
+    match decode_chunk(kind, &self.chunk[..len], &mut self.block, true, self.codec, pos).map_err(invalid)? {
+      ChunkRead::Block(block_len) if block_len == entry.uncompressed_len => (),
+      _ => return Err(bad_header()),
+    }
+    self.current = Some(i);
+    Ok(())
+  }
+
+  pub fn get_ref(&self) -> &R { &self.inner }
+  pub fn get_mut(&mut self) -> &mut R { &mut self.inner }
+  pub fn into_inner(self) -> R { self.inner }
+}
+
+impl<R: Read + Seek> Read for SeekableDecoder<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let i = match self.index.find(self.pos) { Some(i) => i, None => return Ok(0) };
+    if self.current != Some(i) { self.load(i)? }
+
+    let entry = self.index.entries[i];
+    let start = (self.pos - entry.uncompressed_offset) as usize;
+    let block = &self.block[start..entry.uncompressed_len];
+    let len = block.len().min(buf.len());
+    buf[..len].copy_from_slice(&block[..len]);
+    self.pos += len as u64;
+    Ok(len)
+  }
+}
+
+/// Positions are in the uncompressed data, seeking past its end is allowed and reads nothing
+impl<R: Read + Seek> Seek for SeekableDecoder<R> {
+  fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+    let (base, delta) = match pos {
+      SeekFrom::Start(pos) => { self.pos = pos; return Ok(pos) },
+      SeekFrom::End(delta) => (self.index.uncompressed_len, delta),
+      SeekFrom::Current(delta) => (self.pos, delta),
+    };
+    match base.checked_add_signed(delta) {
+      Some(pos) => { self.pos = pos; Ok(pos) },
+      None => Err(io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative or overflowing position")),
+    }
+  }
+}
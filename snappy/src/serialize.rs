@@ -0,0 +1,124 @@
+//! Values of serde stored compressed, with the `serde` feature
+//!
+//! `Compressed<T>` serializes its value with an inner format, JSON by default, compresses that as one raw block and
+//! serializes the block as bytes; deserializing reverses it. Handy for large blobs in JSON documents or DB columns.
+//! `to_compressed_bincode` and `from_compressed_bincode` are the same with bincode, minus the outer format,
+//! for RPC and cache entries.
+//!
+//! ```
+//! # use snappy::Compressed;
+//! let blob = Compressed::<Vec<String>>::new(vec!["snappy".repeat(100); 100]);
+//! let json = serde_json::to_string(&blob).unwrap();
+//! let back: Compressed<Vec<String>> = serde_json::from_str(&json).unwrap();
+//! assert_eq!(back.into_inner(), blob.into_inner());
+//! ```
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use serde::de::{self, DeserializeOwned, SeqAccess, Visitor};
+use serde::{ser, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{compress, decompress};
+
+/// Format values are serialized with before compression
+pub trait InnerFormat {
+  type Error: fmt::Display;
+
+  fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Self::Error>;
+  fn from_slice<T: DeserializeOwned>(data: &[u8]) -> Result<T, Self::Error>;
+}
+
+/// JSON of `serde_json`, the default inner format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Json;
+
+impl InnerFormat for Json {
+  type Error = serde_json::Error;
+
+  fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Self::Error> { serde_json::to_vec(value) }
+  fn from_slice<T: DeserializeOwned>(data: &[u8]) -> Result<T, Self::Error> { serde_json::from_slice(data) }
+}
+
+/// bincode with its default options
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Bincode;
+
+impl InnerFormat for Bincode {
+  type Error = bincode::Error;
+
+  fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Self::Error> { bincode::serialize(value) }
+  fn from_slice<T: DeserializeOwned>(data: &[u8]) -> Result<T, Self::Error> { bincode::deserialize(data) }
+}
+
+/// Raw block of the bincode of `value`
+///
+/// Fails only for what bincode cannot encode, e.g. sequences of unknown length.
+pub fn to_compressed_bincode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, bincode::Error> {
+  Ok(compress(&bincode::serialize(value)?))
+}
+
+/// Value of the bincode in raw block `input`; a corrupted block gives an `Io` error of kind `InvalidData`
+pub fn from_compressed_bincode<T: DeserializeOwned>(input: &[u8]) -> Result<T, bincode::Error> {
+  let data = decompress(input).map_err(std::io::Error::from)?;
+  bincode::deserialize(&data)
+}
+
+/// Value serialized with inner format `F`, then compressed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Compressed<T, F = Json> {
+  value: T,
+  format: PhantomData<fn() -> F>,
+}
+
+impl<T, F> Compressed<T, F> {
+  pub fn new(value: T) -> Self { Compressed { value, format: PhantomData } }
+  pub fn into_inner(self) -> T { self.value }
+}
+
+impl<T, F> From<T> for Compressed<T, F> {
+  fn from(value: T) -> Self { Compressed::new(value) }
+}
+
+impl<T, F> Deref for Compressed<T, F> {
+  type Target = T;
+  fn deref(&self) -> &T { &self.value }
+}
+
+impl<T, F> DerefMut for Compressed<T, F> {
+  fn deref_mut(&mut self) -> &mut T { &mut self.value }
+}
+
+impl<T: Serialize, F: InnerFormat> Serialize for Compressed<T, F> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let data = F::to_vec(&self.value).map_err(ser::Error::custom)?;
+    serializer.serialize_bytes(&compress(&data))
+  }
+}
+
+impl<'de, T: DeserializeOwned, F: InnerFormat> Deserialize<'de> for Compressed<T, F> {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let block = deserializer.deserialize_byte_buf(BytesVisitor)?;
+    let data = decompress(&block).map_err(de::Error::custom)?;
+    F::from_slice(&data).map(Compressed::new).map_err(de::Error::custom)
+  }
+}
+
+/// Bytes as any format gives them, e.g. an array of numbers in JSON
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+  type Value = Vec<u8>;
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("a compressed block of bytes") }
+
+  fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> { Ok(v.to_vec()) }
+  fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> { Ok(v) }
+
+  fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+    let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(1 << 16));
+    while let Some(byte) = seq.next_element()? { bytes.push(byte) }
+    Ok(bytes)
+  }
+}
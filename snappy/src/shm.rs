@@ -0,0 +1,128 @@
+//! Compressed data in a shared memory segment, mapped and decompressed lazily by other processes, with the `mmap` feature
+//!
+//! `write_segment` compresses data into blocks in a file, e.g. a memfd or a file in `/dev/shm` handed to a sidecar
+//! or plugin; `Segment` maps it read-only and decompresses only the blocks read.
+//!
+//! Layout, integers little-endian: `SEGMENT_MAGIC`, block size u32, block count u32, data length u64; an entry per
+//! block of its offset u64, compressed length u32 and masked CRC-32C of its data u32; then the compressed blocks.
+
+use std::convert::{TryFrom, TryInto};
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+
+use memmap2::{Mmap, MmapMut};
+
+use crate::crc32c::masked_crc32c;
+use crate::frame::{invalid, MAX_BLOCK_SIZE};
+use crate::{compress_into, decompress_into, max_compress_len, SnappyError};
+
+/// First bytes of a segment
+pub const SEGMENT_MAGIC: &[u8; 8] = b"sNaPsHm\x01";
+
+const HEADER_LEN: usize = 24;
+const ENTRY_LEN: usize = 16;
+
+/// Compress `data` into `file` as a segment, in blocks of `MAX_BLOCK_SIZE`, returns the segment length
+///
+/// `file` is sized for the worst case, written in place through a map, then truncated to the segment.
+pub fn write_segment(file: &File, data: &[u8]) -> io::Result<u64> {
+  let blocks = data.len().div_ceil(MAX_BLOCK_SIZE);
+  let block_count = u32::try_from(blocks).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "data too long for a segment"))?;
+  let start = HEADER_LEN + blocks * ENTRY_LEN;
+  file.set_len((start + blocks * max_compress_len(MAX_BLOCK_SIZE)) as u64)?;
+  let mut map = unsafe { MmapMut::map_mut(file)? };
+
+  map[..8].copy_from_slice(SEGMENT_MAGIC);
+  map[8..12].copy_from_slice(&(MAX_BLOCK_SIZE as u32).to_le_bytes());
+  map[12..16].copy_from_slice(&block_count.to_le_bytes());
+  map[16..24].copy_from_slice(&(data.len() as u64).to_le_bytes());
+  let mut offset = start;
+  for (i, block) in data.chunks(MAX_BLOCK_SIZE).enumerate() {
+    let len = compress_into(block, &mut map[offset..]).expect("segment sized for max compressed length is always enough");
+    let entry = HEADER_LEN + i * ENTRY_LEN;
+    map[entry..entry + 8].copy_from_slice(&(offset as u64).to_le_bytes());
+    map[entry + 8..entry + 12].copy_from_slice(&(len as u32).to_le_bytes());
+    map[entry + 12..entry + 16].copy_from_slice(&masked_crc32c(block).to_le_bytes());
+    offset += len;
+  }
+
+  map.flush()?;
+  drop(map);
+  file.set_len(offset as u64)?;
+  Ok(offset as u64)
+}
+
+/// Segment of `write_segment` mapped read-only, blocks decompressed as they are read
+///
+/// Whoever wrote it must not change the file while mapped: a mapped file truncated under us is undefined behavior.
+/// The header and block entries are checked by `open`, block data and checksums on each read.
+#[derive(Debug)]
+pub struct Segment {
+  map: Mmap,
+  block_count: usize,
+  len: u64,
+}
+
+impl Segment {
+  /// Map the segment in `file`; a malformed header or entries give `InvalidData`
+  pub fn open(file: &File) -> io::Result<Segment> {
+    let map = unsafe { Mmap::map(file)? };
+    let bad = || invalid(SnappyError::InvalidInput);
+    if map.len() < HEADER_LEN || &map[..8] != SEGMENT_MAGIC { return Err(bad()) }
+    let block_size = u32::from_le_bytes(map[8..12].try_into().unwrap()) as usize;
+    let block_count = u32::from_le_bytes(map[12..16].try_into().unwrap()) as usize;
+    let len = u64::from_le_bytes(map[16..24].try_into().unwrap());
+    if block_size != MAX_BLOCK_SIZE || len.div_ceil(MAX_BLOCK_SIZE as u64) != block_count as u64 { return Err(bad()) }
+    if map.len() < HEADER_LEN + block_count * ENTRY_LEN { return Err(bad()) }
+
+    let segment = Segment { map, block_count, len };
+    if (0..block_count).any(|i| segment.entry(i).0.end > segment.map.len()) { return Err(bad()) }
+    Ok(segment)
+  }
+
+  /// Length of the data held
+  pub fn len(&self) -> u64 { self.len }
+
+  pub fn is_empty(&self) -> bool { self.len == 0 }
+
+  pub fn block_count(&self) -> usize { self.block_count }
+
+  /// Range of block `i` in the map and its checksum
+  fn entry(&self, i: usize) -> (Range<usize>, u32) {
+    let entry = &self.map[HEADER_LEN + i * ENTRY_LEN..][..ENTRY_LEN];
+    let offset = u64::from_le_bytes(entry[..8].try_into().unwrap()) as usize;
+    let len = u32::from_le_bytes(entry[8..12].try_into().unwrap()) as usize;
+    (offset..offset.saturating_add(len), u32::from_le_bytes(entry[12..].try_into().unwrap()))
+  }
+
+  /// Data of block `i`, checksum verified
+  ///
+  /// # Panics
+  ///
+  /// If `i` is not below `block_count`.
+  pub fn block(&self, i: usize) -> Result<Vec<u8>, SnappyError> {
+    assert!(i < self.block_count, "block {} of {}", i, self.block_count);
+    let (range, expected) = self.entry(i);
+    let expected_len = (self.len - (i * MAX_BLOCK_SIZE) as u64).min(MAX_BLOCK_SIZE as u64) as usize;
+    let mut block = vec![0; expected_len];
+    if decompress_into(&self.map[range], &mut block)? != expected_len { return Err(SnappyError::InvalidInput) }
+    let actual = masked_crc32c(&block);
+    if actual != expected { return Err(SnappyError::ChecksumMismatch { chunk_index: i as u64, expected, actual }) }
+    Ok(block)
+  }
+
+  /// Copy data from `offset` into `buf`, decompressing the blocks it spans; short of `buf.len()` only at the end
+  pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, SnappyError> {
+    let mut read = 0;
+    while read < buf.len() && offset + (read as u64) < self.len {
+      let pos = offset + read as u64;
+      let block = self.block((pos / MAX_BLOCK_SIZE as u64) as usize)?;
+      let start = (pos % MAX_BLOCK_SIZE as u64) as usize;
+      let len = (block.len() - start).min(buf.len() - read);
+      buf[read..read + len].copy_from_slice(&block[start..start + len]);
+      read += len;
+    }
+    Ok(read)
+  }
+}
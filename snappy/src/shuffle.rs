@@ -0,0 +1,68 @@
+//! Byte shuffle filter for arrays of numbers, as in Blosc
+//!
+//! Snappy finds few matches in float or wide integer arrays: neighbouring elements rarely share whole bytes, though
+//! their high bytes are often alike. `shuffle` transposes the bytes by element width, first bytes of all elements,
+//! then second bytes and so on, so alike bytes end up next to each other; `unshuffle` puts them back.
+//! `compress_shuffled` records the width in a metadata chunk of the framed stream, for `decompress_shuffled`.
+
+use std::convert::TryFrom;
+use std::io;
+
+use crate::frame::{DecoderOptions, EncoderOptions, FrameDecoder, FrameEncoder};
+
+/// Metadata chunk type of the element width of shuffled streams
+pub const SHUFFLE_TAG: u8 = 0x90;
+
+/// Bytes of `data` transposed by elements of `width` bytes; a partial element at the end is kept as is
+pub fn shuffle(data: &[u8], width: usize) -> Vec<u8> {
+  let mut output = vec![0; data.len()];
+  let (elements, whole) = split(data.len(), width);
+  for (i, element) in data[..whole].chunks_exact(width.max(1)).enumerate() {
+    for (j, &byte) in element.iter().enumerate() { output[j * elements + i] = byte }
+  }
+  output[whole..].copy_from_slice(&data[whole..]);
+  output
+}
+
+/// Bytes of `data` shuffled by elements of `width` bytes put back in place
+pub fn unshuffle(data: &[u8], width: usize) -> Vec<u8> {
+  let mut output = vec![0; data.len()];
+  let (elements, whole) = split(data.len(), width);
+  for (i, element) in output[..whole].chunks_exact_mut(width.max(1)).enumerate() {
+    for (j, byte) in element.iter_mut().enumerate() { *byte = data[j * elements + i] }
+  }
+  output[whole..].copy_from_slice(&data[whole..]);
+  output
+}
+
+/// Count of whole elements of `width` in `len` bytes, and their length
+fn split(len: usize, width: usize) -> (usize, usize) {
+  if width == 0 { return (0, 0) }
+  (len / width, len - len % width)
+}
+
+/// Framed stream of `data` shuffled by elements of `width` bytes, the width in a `SHUFFLE_TAG` metadata chunk first
+pub fn compress_shuffled(data: &[u8], width: usize, options: EncoderOptions) -> io::Result<Vec<u8>> {
+  let mut encoder = FrameEncoder::with_options(Vec::new(), options)?;
+  encoder.write_metadata(SHUFFLE_TAG, &(width as u32).to_le_bytes())?;
+  encoder.write_data(&shuffle(data, width))?;
+  encoder.finish()
+}
+
+/// Data of framed stream `input`, unshuffled if it has a `SHUFFLE_TAG` metadata chunk
+///
+/// Other streams are decompressed as they are; a malformed width gives `InvalidData`.
+pub fn decompress_shuffled(input: &[u8], options: DecoderOptions) -> io::Result<Vec<u8>> {
+  let mut decoder = FrameDecoder::with_options(input, options.read_metadata(true));
+  let mut data = Vec::new();
+  decoder.read_to_end(&mut data)?;
+
+  let width = decoder.metadata().filter(|metadata| metadata.tag == SHUFFLE_TAG).last();
+  match width {
+    Some(width) => match <[u8; 4]>::try_from(&width.data[..]) {
+      Ok(width) => Ok(unshuffle(&data, u32::from_le_bytes(width) as usize)),
+      Err(_) => Err(io::Error::new(io::ErrorKind::InvalidData, "malformed shuffle width")),
+    },
+    None => Ok(data),
+  }
+}
@@ -0,0 +1,120 @@
+//! Append-only data kept compressed in memory, read back at any offset
+//!
+//! `CompressedBuf` compresses data in blocks of `BLOCK_SIZE` as it fills up, each on its own, so `read_at` only
+//! decompresses the blocks a range touches; e.g. a backing store of caches holding more than fits in RAM uncompressed.
+
+use std::io::{self, Write};
+use std::ops::Range;
+
+use crate::{compress_into, decompress_into, max_compress_len};
+
+/// Uncompressed length of each block
+pub const BLOCK_SIZE: usize = 65536;
+
+/// Data compressed in blocks of `BLOCK_SIZE`, appended to and read at any offset
+///
+/// The last block stays uncompressed until full.
+#[derive(Debug, Clone, Default)]
+pub struct CompressedBuf {
+  /// Compressed blocks, back to back
+  blocks: Vec<u8>,
+  /// End of each block in `blocks`
+  ends: Vec<usize>,
+  /// Data of the last block, not full yet
+  tail: Vec<u8>,
+}
+
+impl CompressedBuf {
+  pub fn new() -> Self { CompressedBuf::default() }
+
+  /// Length of the data held
+  pub fn len(&self) -> usize { self.ends.len() * BLOCK_SIZE + self.tail.len() }
+
+  pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+  /// Memory taken by the data: compressed blocks and the last, uncompressed one
+  pub fn compressed_len(&self) -> usize { self.blocks.len() + self.tail.len() }
+
+  /// Append `data`, compressing each block it fills
+  pub fn extend_from_slice(&mut self, mut data: &[u8]) {
+    while !data.is_empty() {
+      let (head, rest) = data.split_at(data.len().min(BLOCK_SIZE - self.tail.len()));
+      if self.tail.is_empty() && head.len() == BLOCK_SIZE {
+        self.push_block(head);
+      } else {
+        self.tail.extend_from_slice(head);
+        if self.tail.len() == BLOCK_SIZE {
+          let tail = std::mem::take(&mut self.tail);
+          self.push_block(&tail);
+          self.tail = tail;
+          self.tail.clear();
+        }
+      }
+      data = rest;
+    }
+  }
+
+  fn push_block(&mut self, block: &[u8]) {
+    let start = self.blocks.len();
+    self.blocks.resize(start + max_compress_len(block.len()), 0);
+    let len = compress_into(block, &mut self.blocks[start..]).expect("buffer of max compressed length is always enough");
+    self.blocks.truncate(start + len);
+    self.ends.push(self.blocks.len());
+  }
+
+  /// Up to `len` bytes of data from `offset`, fewer at the end of data
+  pub fn read_at(&self, offset: usize, len: usize) -> Vec<u8> {
+    let mut buf = vec![0; len.min(self.len().saturating_sub(offset))];
+    let read = self.read_into(offset, &mut buf);
+    debug_assert_eq!(read, buf.len());
+    buf
+  }
+
+  /// Copy data from `offset` into `buf`, returns the length copied, short of `buf.len()` only at the end of data
+  pub fn read_into(&self, offset: usize, buf: &mut [u8]) -> usize {
+    let mut block = Vec::new();
+    let mut read = 0;
+    while read < buf.len() && offset + read < self.len() {
+      let pos = offset + read;
+      let (index, start) = (pos / BLOCK_SIZE, pos % BLOCK_SIZE);
+      let data = if index < self.ends.len() {
+        block.resize(BLOCK_SIZE, 0);
+        decompress_into(&self.blocks[self.block_range(index)], &mut block).expect("blocks are compressed by the buffer itself");
+        &block[..]
+      } else {
+        &self.tail[..]
+      };
+      let len = (data.len() - start).min(buf.len() - read);
+      buf[read..read + len].copy_from_slice(&data[start..start + len]);
+      read += len;
+    }
+    read
+  }
+
+  fn block_range(&self, index: usize) -> Range<usize> {
+    let start = if index == 0 { 0 } else { self.ends[index - 1] };
+    start..self.ends[index]
+  }
+
+  /// Drop all data
+  pub fn clear(&mut self) {
+    self.blocks.clear();
+    self.ends.clear();
+    self.tail.clear();
+  }
+
+  /// Free the spare capacity of the compressed blocks
+  pub fn shrink_to_fit(&mut self) {
+    self.blocks.shrink_to_fit();
+    self.ends.shrink_to_fit();
+  }
+}
+
+impl Write for CompressedBuf {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
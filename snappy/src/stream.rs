@@ -0,0 +1,122 @@
+//! Framed stream compression over futures `Stream`s of `Bytes`, with the `stream` feature
+//!
+//! For async pipelines like hyper bodies or gRPC streams, without `AsyncRead`/`AsyncWrite` glue.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use bytes::Bytes;
+use futures_core::Stream;
+
+use crate::frame::{DecoderOptions, EncoderOptions, FrameEncoder, PushDecoder};
+use crate::SnappyError;
+
+/// Compress a stream of data pieces into a framed stream
+///
+/// Pieces are gathered into whole blocks, each output item holds the chunks of the blocks completed by one input item;
+/// the rest is written when the input ends.
+pub fn compress_stream<S: Stream<Item = Bytes> + Unpin>(input: S) -> CompressStream<S> {
+  CompressStream::with_options(input, EncoderOptions::new()).unwrap()
+}
+
+/// Decompress a framed stream coming in pieces, each output item holds one block
+pub fn decompress_stream<S: Stream<Item = Bytes> + Unpin>(input: S) -> DecompressStream<S> {
+  DecompressStream::with_options(input, DecoderOptions::new())
+}
+
+/// Stream of compressed `Bytes`, see `compress_stream`
+pub struct CompressStream<S> {
+  input: S,
+  /// Encodes into its `Vec`, taken as output items
+  encoder: FrameEncoder<Vec<u8>>,
+  buffer: Vec<u8>,
+  done: bool,
+}
+
+impl<S: Stream<Item = Bytes> + Unpin> CompressStream<S> {
+  /// Stream with encoder `options`, which are validated first
+  pub fn with_options(input: S, options: EncoderOptions) -> Result<Self, SnappyError> {
+    let encoder = FrameEncoder::with_options(Vec::new(), options)?;
+    Ok(CompressStream { input, encoder, buffer: Vec::new(), done: false })
+  }
+
+  /// Take the encoded data as an item, `None` if empty
+  fn take_output(&mut self) -> Option<Bytes> {
+    let output = std::mem::take(self.encoder.get_mut());
+    if output.is_empty() { None } else { Some(Bytes::from(output)) }
+  }
+
+  pub fn into_inner(self) -> S { self.input }
+}
+
+impl<S: Stream<Item = Bytes> + Unpin> Stream for CompressStream<S> {
+  type Item = Bytes;
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Bytes>> {
+    let this = self.get_mut();
+    while !this.done {
+      match ready!(Pin::new(&mut this.input).poll_next(cx)) {
+        Some(piece) => {
+          this.buffer.extend_from_slice(&piece);
+          let block_size = this.encoder.block_size();
+          let whole = this.buffer.len() - this.buffer.len() % block_size;
+          if whole == 0 { continue }
+          this.encoder.write_data(&this.buffer[..whole]).expect("writing to Vec never fails");
+          this.buffer.drain(..whole);
+        },
+        None => {
+          this.encoder.write_data(&this.buffer).expect("writing to Vec never fails");
+          this.encoder.sync_flush().expect("writing to Vec never fails");
+          this.buffer = Vec::new();
+          this.done = true;
+        },
+      }
+      if let Some(output) = this.take_output() { return Poll::Ready(Some(output)) }
+    }
+    Poll::Ready(None)
+  }
+}
+
+/// Stream of decompressed blocks, see `decompress_stream`
+///
+/// Corrupted or truncated input gives an `InvalidData` error item, then the stream ends.
+pub struct DecompressStream<S> {
+  input: S,
+  decoder: PushDecoder,
+  done: bool,
+}
+
+impl<S: Stream<Item = Bytes> + Unpin> DecompressStream<S> {
+  /// Stream with decoder `options`
+  pub fn with_options(input: S, options: DecoderOptions) -> Self {
+    DecompressStream { input, decoder: PushDecoder::with_options(options), done: false }
+  }
+
+  pub fn into_inner(self) -> S { self.input }
+}
+
+impl<S: Stream<Item = Bytes> + Unpin> Stream for DecompressStream<S> {
+  type Item = io::Result<Bytes>;
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<io::Result<Bytes>>> {
+    let this = self.get_mut();
+    while !this.done {
+      match this.decoder.next_block() {
+        Ok(Some([])) => continue,
+        Ok(Some(block)) => return Poll::Ready(Some(Ok(Bytes::copy_from_slice(block)))),
+        Ok(None) => (),
+        Err(e) => { this.done = true; return Poll::Ready(Some(Err(e))) },
+      }
+
+      match ready!(Pin::new(&mut this.input).poll_next(cx)) {
+        Some(piece) => this.decoder.push(&piece),
+        None => {
+          this.done = true;
+          if let Err(e) = this.decoder.finish() { return Poll::Ready(Some(Err(e))) }
+        },
+      }
+    }
+    Poll::Ready(None)
+  }
+}
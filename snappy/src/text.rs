@@ -0,0 +1,20 @@
+//! Compressed blobs as base64 text, with the `base64` feature
+//!
+//! For JSON, YAML or environment variables, e.g. config and tracing payloads: a raw block in standard,
+//! padded base64.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::{compress, decompress, SnappyError};
+
+/// Compress `input`, then encode it as base64
+pub fn compress_to_base64(input: &[u8]) -> String { STANDARD.encode(compress(input)) }
+
+/// Decode base64 `input`, then decompress it; text not base64 gives `InvalidInput`
+///
+/// Whitespace around the text, e.g. a trailing newline of a file, is ignored.
+pub fn decompress_from_base64(input: &str) -> Result<Vec<u8>, SnappyError> {
+  let block = STANDARD.decode(input.trim()).map_err(|_| SnappyError::InvalidInput)?;
+  decompress(&block)
+}
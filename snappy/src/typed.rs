@@ -0,0 +1,28 @@
+//! Compression of slices of plain numbers and structs, with the `bytemuck` feature
+//!
+//! `&[f64]` or `&[u32]` buffers go in without casting by hand, and come out as a `Vec` of the element type,
+//! properly aligned. Elements are stored in native byte order, so data is only portable between machines of the same
+//! endianness; all common targets are little-endian.
+
+use std::mem;
+
+use bytemuck::Pod;
+
+use crate::{compress, decompress_into, decompress_len, SnappyError};
+
+/// Compress the bytes of `data`
+pub fn compress_slice<T: Pod>(data: &[T]) -> Vec<u8> { compress(bytemuck::cast_slice(data)) }
+
+/// Decompress `input` into elements of `T`
+///
+/// Data not a whole number of elements gives `InvalidInput`. Panics if `T` is zero-sized.
+pub fn decompress_slice<T: Pod>(input: &[u8]) -> Result<Vec<T>, SnappyError> {
+  let size = mem::size_of::<T>();
+  assert!(size != 0, "zero-sized elements");
+  let len = decompress_len(input)?;
+  if len % size != 0 { return Err(SnappyError::InvalidInput) }
+
+  let mut output = vec![T::zeroed(); len / size];
+  decompress_into(input, bytemuck::cast_slice_mut(&mut output))?;
+  Ok(output)
+}
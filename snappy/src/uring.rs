@@ -0,0 +1,163 @@
+//! io_uring file compression pipeline on Linux, with the `io-uring` feature
+//!
+//! Reads of the next blocks and writes of compressed chunks stay in flight while a block is compressed,
+//! so disk and CPU work overlap instead of taking turns like in `compress_file`.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use io_uring::{opcode, types, IoUring};
+
+use crate::file::{compress_file, FileOptions, FileStats, Output};
+use crate::format::Format;
+use crate::frame::{EncoderOptions, FrameEncoder};
+
+/// Blocks read ahead of the one being compressed, also the max of chunk writes in flight
+const DEPTH: usize = 8;
+
+/// `compress_file` through io_uring, same output
+///
+/// Falls back to `compress_file` when the kernel has no io_uring or it is denied, e.g. by a seccomp filter,
+/// and for other formats than framed or with `EncoderOptions::parallel`.
+pub fn compress_file_uring<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q, options: EncoderOptions, file: FileOptions) -> io::Result<FileStats> {
+  if options.output_format() != Format::Framed || options.threads() > 1 { return compress_file(src, dst, options, file) }
+  let ring = match IoUring::new(2 * DEPTH as u32) {
+    Ok(ring) => ring,
+    Err(e) if matches!(e.kind(), io::ErrorKind::Unsupported | io::ErrorKind::PermissionDenied) => return compress_file(src, dst, options, file),
+    Err(e) => return Err(e),
+  };
+  let encoder = FrameEncoder::with_options(Vec::new(), options)?;
+  let input = File::open(src.as_ref())?;
+  let output = Output::create(dst.as_ref(), file)?;
+
+  let len = input.metadata()?.len();
+  let mut pipeline = Pipeline { ring, input, output: &output.file, ops: (0..2 * DEPTH).map(|_| None).collect(), in_flight: 0 };
+  let stats = pipeline.run(encoder, len)?;
+  drop(pipeline);
+  output.commit(src.as_ref())?;
+  Ok(stats)
+}
+
+/// A read of a block or a write of chunks, until all of `buf` is done
+struct Op {
+  buf: Vec<u8>,
+  write: bool,
+  /// File offset of `buf`
+  offset: u64,
+  /// Length of `buf` done so far
+  done: usize,
+}
+
+struct Pipeline<'a> {
+  ring: IoUring,
+  input: File,
+  output: &'a File,
+  /// Ops by their `user_data`, first `DEPTH` for reads; buffers must stay put while in flight
+  ops: Vec<Option<Op>>,
+  in_flight: usize,
+}
+
+impl Pipeline<'_> {
+  fn run(&mut self, mut encoder: FrameEncoder<Vec<u8>>, len: u64) -> io::Result<FileStats> {
+    let block_size = encoder.block_size() as u64;
+    let blocks = len.div_ceil(block_size);
+    let block_len = |block: u64| (len - block * block_size).min(block_size) as usize;
+
+    for block in 0..blocks.min(DEPTH as u64) {
+      self.submit(block as usize, Op { buf: vec![0; block_len(block)], write: false, offset: block * block_size, done: 0 })?;
+    }
+
+    let mut output_offset = 0;
+    for block in 0..blocks {
+      let slot = block as usize % DEPTH;
+      while self.ops[slot].as_ref().is_none_or(|op| op.done < op.buf.len()) { self.wait()? }
+      let mut op = self.ops[slot].take().unwrap();
+
+      encoder.write_data(&op.buf)?;
+      if block + 1 == blocks { encoder.sync_flush()? }
+      let chunks = std::mem::take(encoder.get_mut());
+      output_offset += self.write(chunks, output_offset)?;
+
+      let next = block + DEPTH as u64;
+      if next < blocks {
+        op.buf.resize(block_len(next), 0);
+        self.submit(slot, Op { offset: next * block_size, done: 0, ..op })?;
+      }
+    }
+    if blocks == 0 {
+      encoder.sync_flush()?;
+      let identifier = std::mem::take(encoder.get_mut());
+      self.write(identifier, 0)?;
+    }
+
+    while self.in_flight != 0 { self.wait()? }
+    let stats = encoder.stats();
+    Ok(FileStats { input_len: stats.uncompressed_len, output_len: stats.stream_len })
+  }
+
+  /// Submit a write of `buf` at `offset` in a free slot, returns its length
+  fn write(&mut self, buf: Vec<u8>, offset: u64) -> io::Result<u64> {
+    let len = buf.len() as u64;
+    loop {
+      if let Some(slot) = (DEPTH..2 * DEPTH).find(|&slot| self.ops[slot].is_none()) {
+        self.submit(slot, Op { buf, write: true, offset, done: 0 })?;
+        return Ok(len)
+      }
+      self.wait()?;
+    }
+  }
+
+  /// Queue the rest of `op` in `slot`
+  fn submit(&mut self, slot: usize, mut op: Op) -> io::Result<()> {
+    let len = (op.buf.len() - op.done).min(u32::MAX as usize) as u32;
+    let buf = unsafe { op.buf.as_mut_ptr().add(op.done) };
+    let offset = op.offset + op.done as u64;
+    let entry = if op.write {
+      opcode::Write::new(types::Fd(self.output.as_raw_fd()), buf, len).offset(offset).build()
+    } else {
+      opcode::Read::new(types::Fd(self.input.as_raw_fd()), buf, len).offset(offset).build()
+    };
+
+    self.ops[slot] = Some(op);
+    unsafe { self.ring.submission().push(&entry.user_data(slot as u64)) }.expect("ring has room for every slot");
+    self.in_flight += 1;
+    Ok(())
+  }
+
+  /// Wait for completions and handle them; reads done are left in their slot, writes done are freed
+  fn wait(&mut self) -> io::Result<()> {
+    self.ring.submit_and_wait(1)?;
+    let completions: Vec<(usize, i32)> = self.ring.completion().map(|cqe| (cqe.user_data() as usize, cqe.result())).collect();
+    let mut result = Ok(());
+    for (slot, n) in completions {
+      self.in_flight -= 1;
+      let op = self.ops[slot].as_mut().expect("completion of a submitted op");
+      if n < 0 { result = Err(io::Error::from_raw_os_error(-n)); continue }
+      if n == 0 && op.done < op.buf.len() {
+        result = Err(if op.write { io::ErrorKind::WriteZero.into() } else { io::ErrorKind::UnexpectedEof.into() });
+        continue
+      }
+
+      op.done += n as usize;
+      if op.done < op.buf.len() {
+        let op = self.ops[slot].take().unwrap();
+        self.submit(slot, op)?;
+      } else if op.write {
+        self.ops[slot] = None;
+      }
+    }
+    result
+  }
+}
+
+impl Drop for Pipeline<'_> {
+  /// Wait out the ops in flight after an error, the kernel may still use their buffers
+  fn drop(&mut self) {
+    while self.in_flight != 0 {
+      if self.ring.submit_and_wait(1).is_err() { break }
+      self.in_flight -= self.ring.completion().count();
+    }
+  }
+}
@@ -0,0 +1,170 @@
+//! Framed streams split into volume files of limited size, for media with file size limits
+//!
+//! `VolumeWriter` writes `name.000`, `name.001`, ... each a whole framed stream ending at a chunk boundary;
+//! `VolumeReader` reads them back in order as one stream, concatenated streams being valid.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::frame::{EncoderOptions, FrameEncoder, CHUNK_HEADER_SIZE, STREAM_IDENTIFIER};
+use crate::read::SnappyReader;
+use crate::snappy_max_compressed_length;
+
+/// Path of volume `n` of `base`: `base` with `.000`, `.001`, ... appended
+pub fn volume_path<P: AsRef<Path>>(base: P, n: usize) -> PathBuf {
+  let mut path = base.as_ref().as_os_str().to_owned();
+  path.push(format!(".{:03}", n));
+  path.into()
+}
+
+/// Compressing writer rolling over to a new volume file before one would grow past `volume_size`
+///
+/// Input is buffered until a block is full like in `SnappyWriter`, and written on drop; call `finish` to handle
+/// errors of it. `EncoderOptions::index` is ignored.
+pub struct VolumeWriter {
+  base: PathBuf,
+  volume_size: u64,
+  /// Chunks of a block are made here first, to know if they fit
+  encoder: FrameEncoder<Vec<u8>>,
+  buffer: Vec<u8>,
+  /// Current volume and its length so far
+  volume: Option<(BufWriter<File>, u64)>,
+  volumes: Vec<PathBuf>,
+}
+
+impl VolumeWriter {
+  pub fn new<P: AsRef<Path>>(base: P, volume_size: u64) -> io::Result<Self> {
+    VolumeWriter::with_options(base, volume_size, EncoderOptions::new())
+  }
+
+  /// Writer with encoder `options`, which are validated first
+  ///
+  /// `volume_size` must fit the stream identifier and the largest chunk of a block, else `InvalidInput`.
+  pub fn with_options<P: AsRef<Path>>(base: P, volume_size: u64, options: EncoderOptions) -> io::Result<Self> {
+    let encoder = FrameEncoder::mid_stream(Vec::new(), options.index(false))?;
+    let block_size = encoder.block_size();
+    let max_chunk_len = CHUNK_HEADER_SIZE + 4 + unsafe { snappy_max_compressed_length(block_size) };
+    if volume_size < (STREAM_IDENTIFIER.len() + max_chunk_len) as u64 {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, "volume size is smaller than a chunk"))
+    }
+    let buffer = Vec::with_capacity(block_size);
+    Ok(VolumeWriter { base: base.as_ref().to_owned(), volume_size, encoder, buffer, volume: None, volumes: Vec::new() })
+  }
+
+  /// Compress buffered input as a chunk, into the current volume if it fits, else into a new one
+  fn write_buffer(&mut self) -> io::Result<()> {
+    if self.buffer.is_empty() { return Ok(()) }
+    self.encoder.write_data(&self.buffer)?;
+    self.buffer.clear();
+    let chunks = std::mem::take(self.encoder.get_mut());
+
+    let fits = self.volume.as_ref().is_some_and(|(_, len)| len + chunks.len() as u64 <= self.volume_size);
+    if !fits { self.next_volume()? }
+    let (file, len) = self.volume.as_mut().unwrap();
+    file.write_all(&chunks)?;
+    *len += chunks.len() as u64;
+    Ok(())
+  }
+
+  /// End the current volume, start the next with a stream identifier
+  fn next_volume(&mut self) -> io::Result<()> {
+    if let Some((mut file, _)) = self.volume.take() { file.flush()? }
+    let path = volume_path(&self.base, self.volumes.len());
+    let mut file = BufWriter::new(File::create(&path)?);
+    file.write_all(STREAM_IDENTIFIER)?;
+    self.volumes.push(path);
+    self.volume = Some((file, STREAM_IDENTIFIER.len() as u64));
+    Ok(())
+  }
+
+  /// Volumes written so far
+  pub fn volumes(&self) -> &[PathBuf] { &self.volumes }
+
+  /// Write buffered input and close the last volume, get back the paths of all volumes
+  ///
+  /// Empty input still makes a volume, holding only the stream identifier.
+  pub fn finish(mut self) -> io::Result<Vec<PathBuf>> {
+    self.write_buffer()?;
+    if self.volume.is_none() { self.next_volume()? }
+    let (mut file, _) = self.volume.take().unwrap();
+    file.flush()?;
+    file.get_ref().sync_all()?;
+    Ok(std::mem::take(&mut self.volumes))
+  }
+}
+
+impl Write for VolumeWriter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let block_size = self.encoder.block_size();
+    let len = buf.len().min(block_size - self.buffer.len());
+    self.buffer.extend_from_slice(&buf[..len]);
+    if self.buffer.len() == block_size { self.write_buffer()? }
+    Ok(len)
+  }
+
+  /// Ends the current block early and flushes the current volume
+  fn flush(&mut self) -> io::Result<()> {
+    self.write_buffer()?;
+    match self.volume {
+      Some((ref mut file, _)) => file.flush(),
+      None => Ok(()),
+    }
+  }
+}
+
+impl Drop for VolumeWriter {
+  fn drop(&mut self) {
+    if self.volume.is_some() || !self.buffer.is_empty() { let _ = self.flush(); }
+  }
+}
+
+/// Decompressing reader of the volumes of `VolumeWriter`, in order
+///
+/// Volumes are found from `.000` on until one is missing, so a lost volume in the middle cuts the data short;
+/// a lost last volume cannot be told from the end.
+pub struct VolumeReader {
+  reader: SnappyReader<Volumes>,
+}
+
+impl VolumeReader {
+  /// Reader of the volumes of `base`, at least `.000` must exist
+  pub fn open<P: AsRef<Path>>(base: P) -> io::Result<Self> {
+    let mut paths = Vec::new();
+    while volume_path(&base, paths.len()).is_file() { paths.push(volume_path(&base, paths.len())) }
+    if paths.is_empty() { return Err(io::Error::new(io::ErrorKind::NotFound, "no volume found")) }
+    Ok(VolumeReader::from_paths(paths))
+  }
+
+  /// Reader of the volumes at `paths`, in that order
+  pub fn from_paths(paths: Vec<PathBuf>) -> Self {
+    VolumeReader { reader: SnappyReader::new(Volumes { paths: paths.into_iter(), current: None }) }
+  }
+}
+
+impl Read for VolumeReader {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.reader.read(buf) }
+}
+
+/// Contents of volume files one after another, opened when reached
+struct Volumes {
+  paths: std::vec::IntoIter<PathBuf>,
+  current: Option<BufReader<File>>,
+}
+
+impl Read for Volumes {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+      if let Some(ref mut file) = self.current {
+        match file.read(buf)? {
+          0 if !buf.is_empty() => self.current = None,
+          n => return Ok(n),
+        }
+      }
+      match self.paths.next() {
+        Some(path) => self.current = Some(BufReader::new(File::open(path)?)),
+        None => return Ok(0),
+      }
+    }
+  }
+}
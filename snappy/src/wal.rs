@@ -0,0 +1,190 @@
+//! Write-ahead log of records in a framed stream
+//!
+//! `WalWriter` writes each record as a `WAL_RECORD_TAG` metadata chunk, holding its sequence number and length, then
+//! its data chunks, and syncs the file before `append` returns. After a crash, `WalReader` reads the records back and
+//! stops at the first torn or corrupted one; `WalWriter::open` cuts it off and goes on from the record before.
+
+use std::convert::TryInto;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::crc32c::masked_crc32c;
+use crate::frame::{invalid, is_corruption, is_skippable, truncated, EncoderOptions, Frame, FrameEncoder, Frames, CHUNK_HEADER_SIZE,
+  CHUNK_STREAM_IDENTIFIER};
+use crate::SnappyError;
+
+/// Metadata chunk type starting each record: sequence number and data length as u64 LE, masked CRC-32C of them
+pub const WAL_RECORD_TAG: u8 = 0x92;
+
+const RECORD_HEADER_LEN: usize = 20;
+
+/// Appending writer of a write-ahead log
+pub struct WalWriter<W: Write> {
+  encoder: FrameEncoder<W>,
+  next_seq: u64,
+  /// Makes records written durable
+  sync: fn(&mut W) -> io::Result<()>,
+}
+
+impl<W: Write> WalWriter<W> {
+  /// New log into `inner`, from sequence number 0; records are flushed, syncing is up to `inner`
+  pub fn new(inner: W) -> Self { WalWriter { encoder: FrameEncoder::new(inner), next_seq: 0, sync: |_| Ok(()) } }
+
+  /// Write `data` as the next record, flushed and synced once this returns; returns its sequence number
+  pub fn append(&mut self, data: &[u8]) -> io::Result<u64> {
+    let seq = self.next_seq;
+    self.encoder.write_metadata(WAL_RECORD_TAG, &record_header(seq, data.len() as u64))?;
+    self.encoder.write_data(data)?;
+    self.encoder.sync_flush()?;
+    (self.sync)(self.encoder.get_mut())?;
+    self.next_seq += 1;
+    Ok(seq)
+  }
+
+  /// Sequence number of the next record
+  pub fn next_seq(&self) -> u64 { self.next_seq }
+
+  pub fn get_ref(&self) -> &W { self.encoder.get_ref() }
+  pub fn into_inner(self) -> io::Result<W> { self.encoder.into_inner() }
+}
+
+impl WalWriter<File> {
+  /// Open the log file at `path`, created if missing, with every record synced by `File::sync_data`
+  ///
+  /// A torn or corrupted record at the end, e.g. of a crash while appending, is truncated away with all after it;
+  /// sequence numbers go on from the last whole record.
+  pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+    let mut reader = WalReader::new(BufReader::new(&file));
+    let mut next_seq = 0;
+    for record in reader.by_ref() { next_seq = record?.seq + 1 }
+    let end = reader.valid_len();
+
+    file.set_len(end)?;
+    file.sync_all()?;
+    file.seek(SeekFrom::Start(end))?;
+    let encoder = if end == 0 { FrameEncoder::new(file) } else { FrameEncoder::mid_stream(file, EncoderOptions::new()).map_err(invalid)? };
+    Ok(WalWriter { encoder, next_seq, sync: |file| file.sync_data() })
+  }
+}
+
+fn record_header(seq: u64, len: u64) -> [u8; RECORD_HEADER_LEN] {
+  let mut header = [0; RECORD_HEADER_LEN];
+  header[..8].copy_from_slice(&seq.to_le_bytes());
+  header[8..16].copy_from_slice(&len.to_le_bytes());
+  let crc = masked_crc32c(&header[..16]);
+  header[16..].copy_from_slice(&crc.to_le_bytes());
+  header
+}
+
+/// Sequence number and data length of record header `body`, `None` if corrupted
+fn parse_record_header(body: &[u8]) -> Option<(u64, u64)> {
+  let body: &[u8; RECORD_HEADER_LEN] = body.try_into().ok()?;
+  let crc = u32::from_le_bytes([body[16], body[17], body[18], body[19]]);
+  if masked_crc32c(&body[..16]) != crc { return None }
+  let (seq, len) = body[..16].split_at(8);
+  Some((u64::from_le_bytes(seq.try_into().unwrap()), u64::from_le_bytes(len.try_into().unwrap())))
+}
+
+/// One record of a write-ahead log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalRecord {
+  pub seq: u64,
+  pub data: Vec<u8>,
+}
+
+/// Iterator over the records of a write-ahead log, ending at the first torn or corrupted one
+///
+/// Checksums of record headers and data are verified, and sequence numbers must follow each other;
+/// only I/O errors are given as errors.
+pub struct WalReader<R: Read> {
+  frames: Frames<Counted<R>>,
+  /// End of the last whole record, or of the stream identifier before any
+  valid_len: u64,
+  next_seq: Option<u64>,
+  torn: bool,
+  done: bool,
+}
+
+impl<R: Read> WalReader<R> {
+  pub fn new(inner: R) -> Self {
+    WalReader { frames: Frames::new(Counted { inner, len: 0 }), valid_len: 0, next_seq: None, torn: false, done: false }
+  }
+
+  /// Length of the log up to the end of the last whole record read, where appending goes on
+  pub fn valid_len(&self) -> u64 { self.valid_len }
+
+  /// Whether reading ended at a torn or corrupted record, rather than at the end of the log
+  pub fn is_torn(&self) -> bool { self.torn }
+
+  pub fn into_inner(self) -> R { self.frames.into_inner().inner }
+
+  fn read_record(&mut self) -> io::Result<Option<WalRecord>> {
+    let (seq, len, mut end) = loop {
+      let frame = match self.frames.next() {
+        Some(frame) => frame?,
+        None => return Ok(None),
+      };
+      match frame.kind {
+        CHUNK_STREAM_IDENTIFIER => if self.next_seq.is_none() { self.valid_len = end_of(&frame) },
+        WAL_RECORD_TAG => {
+          let (seq, len) = parse_record_header(&frame.body).ok_or_else(|| bad_chunk(&frame))?;
+          break (seq, len, end_of(&frame))
+        },
+        kind if is_skippable(kind) => (),
+        _ => return Err(bad_chunk(&frame)),
+      }
+    };
+    if self.next_seq.is_some_and(|next| next != seq) { return Err(invalid(SnappyError::InvalidInput)) }
+
+    let mut data = Vec::new();
+    while (data.len() as u64) < len {
+      let frame = match self.frames.next() {
+        Some(frame) => frame?,
+        None => return Err(invalid(truncated(data.len(), len as usize))),
+      };
+      if !frame.is_data() { return Err(bad_chunk(&frame)) }
+      data.extend_from_slice(&frame.decompress().map_err(invalid)?);
+      end = end_of(&frame);
+    }
+    if data.len() as u64 != len { return Err(invalid(SnappyError::InvalidInput)) }
+
+    self.valid_len = end;
+    self.next_seq = Some(seq + 1);
+    Ok(Some(WalRecord { seq, data }))
+  }
+}
+
+impl<R: Read> Iterator for WalReader<R> {
+  type Item = io::Result<WalRecord>;
+
+  fn next(&mut self) -> Option<io::Result<WalRecord>> {
+    if self.done { return None }
+    let record = self.read_record();
+    if !matches!(record, Ok(Some(_))) { self.done = true }
+    match record {
+      // an empty log is no torn one
+      Err(ref e) if is_corruption(e) => { self.torn = self.frames.get_ref().len != 0; None },
+      record => record.transpose(),
+    }
+  }
+}
+
+fn end_of(frame: &Frame) -> u64 { frame.offset + (CHUNK_HEADER_SIZE + frame.compressed_len) as u64 }
+
+fn bad_chunk(frame: &Frame) -> io::Error { invalid(SnappyError::BadChunkHeader { offset: frame.offset }) }
+
+/// Reader counting the bytes read from it
+struct Counted<R> {
+  inner: R,
+  len: u64,
+}
+
+impl<R: Read> Read for Counted<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let n = self.inner.read(buf)?;
+    self.len += n as u64;
+    Ok(n)
+  }
+}
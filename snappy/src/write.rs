@@ -0,0 +1,94 @@
+//! Streaming compression through `io::Write`
+
+use std::io::{self, Write};
+
+use crate::frame::{EncoderOptions, EncoderStats, FrameEncoder};
+use crate::SnappyError;
+
+/// Compressing writer, output is a framed stream
+///
+/// Input is buffered until a block is full, `flush` ends the current block early (see `sync_flush`).
+/// Buffered input is written on drop, call `finish` to handle errors of it.
+#[derive(Clone)]
+pub struct SnappyWriter<W: Write> {
+  /// Always `Some` until finished
+  encoder: Option<FrameEncoder<W>>,
+  buffer: Vec<u8>,
+}
+
+impl<W: Write> SnappyWriter<W> {
+  pub fn new(inner: W) -> Self {
+    SnappyWriter::with_options(inner, EncoderOptions::new()).unwrap()
+  }
+
+  /// Writer with encoder `options`, which are validated first
+  pub fn with_options(inner: W, options: EncoderOptions) -> Result<Self, SnappyError> {
+    let encoder = FrameEncoder::with_options(inner, options)?;
+    let buffer = Vec::with_capacity(encoder.block_size());
+    Ok(SnappyWriter { encoder: Some(encoder), buffer })
+  }
+
+  fn encoder(&mut self) -> &mut FrameEncoder<W> { self.encoder.as_mut().unwrap() }
+
+  /// Compress buffered input as a chunk
+  fn write_buffer(&mut self) -> io::Result<()> {
+    if !self.buffer.is_empty() {
+      let encoder = self.encoder.as_mut().unwrap();
+      encoder.write_data(&self.buffer)?;
+      self.buffer.clear();
+    }
+    Ok(())
+  }
+
+  /// Write metadata chunk after the buffered input, see `FrameEncoder::write_metadata`
+  pub fn write_metadata(&mut self, tag: u8, data: &[u8]) -> io::Result<()> {
+    self.write_buffer()?;
+    self.encoder().write_metadata(tag, data)
+  }
+
+  /// End the current block and flush, so a reader can decode everything written so far, see `FrameEncoder::sync_flush`
+  ///
+  /// Same as `flush`; each call ends a block early, so frequent syncs lower compression ratio.
+  pub fn sync_flush(&mut self) -> io::Result<()> {
+    self.write_buffer()?;
+    self.encoder().sync_flush()
+  }
+
+  /// Counts of the data compressed so far, buffered input excluded, see `FrameEncoder::stats`
+  pub fn stats(&self) -> EncoderStats { self.encoder.as_ref().unwrap().stats() }
+
+  /// Write buffered input and get back the inner writer
+  pub fn finish(mut self) -> io::Result<W> {
+    self.write_buffer()?;
+    self.encoder.take().unwrap().into_inner()
+  }
+
+  pub fn get_ref(&self) -> &W { self.encoder.as_ref().unwrap().get_ref() }
+  pub fn get_mut(&mut self) -> &mut W { self.encoder().get_mut() }
+}
+
+impl<W: Write> Write for SnappyWriter<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let block_size = self.encoder().block_size();
+
+    // whole blocks skip the buffer
+    if self.buffer.is_empty() && buf.len() >= block_size {
+      let len = buf.len() - buf.len() % block_size;
+      self.encoder().write_data(&buf[..len])?;
+      return Ok(len);
+    }
+
+    let len = buf.len().min(block_size - self.buffer.len());
+    self.buffer.extend_from_slice(&buf[..len]);
+    if self.buffer.len() == block_size { self.write_buffer()? }
+    Ok(len)
+  }
+
+  fn flush(&mut self) -> io::Result<()> { self.sync_flush() }
+}
+
+impl<W: Write> Drop for SnappyWriter<W> {
+  fn drop(&mut self) {
+    if self.encoder.is_some() { let _ = self.write_buffer(); }
+  }
+}
@@ -0,0 +1,195 @@
+//! snappy-java (xerial) `SnappyOutputStream` format, used by Kafka and many JVM systems
+//!
+//! Stream starts with a 16 bytes header: magic `\x82SNAPPY\0`, then big-endian `i32` version and compatible version.
+//! Blocks follow, each a big-endian `i32` compressed length and a raw snappy block. There is no checksum.
+
+use std::io::{self, Read, Write};
+
+use crate::frame::{invalid, read_full, truncated};
+use crate::{compress_into, decompress_into, snappy_max_compressed_length, snappy_uncompressed_length, status, SnappyError};
+
+/// Magic bytes starting the stream header
+pub const MAGIC: &[u8] = b"\x82SNAPPY\x00";
+/// Stream header: magic, version 1 and compatible version 1
+pub const HEADER: &[u8] = b"\x82SNAPPY\x00\x00\x00\x00\x01\x00\x00\x00\x01";
+/// Uncompressed length of blocks `XerialWriter` writes by default, same as snappy-java
+pub const DEFAULT_BLOCK_SIZE: usize = 32 * 1024;
+/// Max uncompressed length of blocks `XerialReader` accepts by default, far above what snappy-java writes
+pub const DEFAULT_MAX_BLOCK_LEN: usize = 4 * 1024 * 1024;
+
+/// Compressing writer of xerial snappy streams
+///
+/// Header is written before the first block, or by `finish` for empty input.
+/// Buffered input is written on drop, call `finish` to handle errors of it.
+pub struct XerialWriter<W: Write> {
+  /// Always `Some` until finished
+  inner: Option<W>,
+  header_written: bool,
+  block_size: usize,
+  buffer: Vec<u8>,
+  compressed: Vec<u8>,
+}
+
+impl<W: Write> XerialWriter<W> {
+  pub fn new(inner: W) -> Self { XerialWriter::with_block_size(inner, DEFAULT_BLOCK_SIZE) }
+
+  /// Writer with blocks of `block_size`
+  pub fn with_block_size(inner: W, block_size: usize) -> Self {
+    assert!(block_size != 0 && block_size <= i32::MAX as usize, "block size out of range");
+    let compressed = vec![0; 4 + unsafe { snappy_max_compressed_length(block_size) }];
+    XerialWriter { inner: Some(inner), header_written: false, block_size, buffer: Vec::with_capacity(block_size), compressed }
+  }
+
+  /// Write header if not yet
+  fn write_header(&mut self) -> io::Result<()> {
+    if !self.header_written {
+      self.inner.as_mut().unwrap().write_all(HEADER)?;
+      self.header_written = true;
+    }
+    Ok(())
+  }
+
+  /// Compress buffered input as a block
+  fn write_buffer(&mut self) -> io::Result<()> {
+    if self.buffer.is_empty() { return Ok(()) }
+    self.write_header()?;
+    let len = compress_into(&self.buffer, &mut self.compressed[4..]).expect("buffer of max compressed length is always enough");
+    self.compressed[..4].copy_from_slice(&(len as u32).to_be_bytes());
+    self.inner.as_mut().unwrap().write_all(&self.compressed[..4 + len])?;
+    self.buffer.clear();
+    Ok(())
+  }
+
+  /// Write buffered input and get back the inner writer
+  pub fn finish(mut self) -> io::Result<W> {
+    self.write_buffer()?;
+    self.write_header()?;
+    Ok(self.inner.take().unwrap())
+  }
+
+  pub fn get_ref(&self) -> &W { self.inner.as_ref().unwrap() }
+  pub fn get_mut(&mut self) -> &mut W { self.inner.as_mut().unwrap() }
+}
+
+impl<W: Write> Write for XerialWriter<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let len = buf.len().min(self.block_size - self.buffer.len());
+    self.buffer.extend_from_slice(&buf[..len]);
+    if self.buffer.len() == self.block_size { self.write_buffer()? }
+    Ok(len)
+  }
+
+  /// Ends the current block early
+  fn flush(&mut self) -> io::Result<()> {
+    self.write_buffer()?;
+    self.get_mut().flush()
+  }
+}
+
+impl<W: Write> Drop for XerialWriter<W> {
+  fn drop(&mut self) {
+    if self.inner.is_some() { let _ = self.write_buffer(); }
+  }
+}
+
+/// Decompressing reader of xerial snappy streams
+///
+/// Like snappy-java, concatenated streams are read as one: a header may appear in place of a block.
+/// Block lengths are checked against `max_block_len` before anything is allocated for them.
+pub struct XerialReader<R: Read> {
+  inner: R,
+  header_read: bool,
+  max_block_len: usize,
+  max_output_size: u64,
+  /// Length of data decoded so far
+  output_len: u64,
+  compressed: Vec<u8>,
+  block: Vec<u8>,
+  /// Position in `block`
+  pos: usize,
+  /// Length of the stream read so far
+  offset: u64,
+}
+
+impl<R: Read> XerialReader<R> {
+  pub fn new(inner: R) -> Self {
+    XerialReader {
+      inner, header_read: false, max_block_len: DEFAULT_MAX_BLOCK_LEN, max_output_size: u64::MAX, output_len: 0, compressed: Vec::new(),
+      block: Vec::new(), pos: 0, offset: 0,
+    }
+  }
+
+  /// Refuse blocks of data over `len` bytes, or compressed over the max compressed length of it, as `BadChunkHeader`;
+  /// `DEFAULT_MAX_BLOCK_LEN` by default
+  pub fn max_block_len(mut self, len: usize) -> Self { self.max_block_len = len; self }
+
+  /// Fail with `OutputLimitExceeded` rather than decode more than `size` bytes in all, no limit by default
+  ///
+  /// Checked on the length each block claims, before it is decompressed, see `DecoderOptions::max_output_size`.
+  pub fn max_output_size(mut self, size: u64) -> Self { self.max_output_size = size; self }
+
+  /// Check the rest of a header, after its first 4 bytes
+  fn read_header_rest(&mut self, start: &[u8]) -> io::Result<()> {
+    let mut header = [0u8; 16];
+    header[..4].copy_from_slice(start);
+    let n = read_full(&mut self.inner, &mut header[4..])?;
+    self.offset += n as u64;
+    if n != 12 || !header.starts_with(MAGIC) {
+      return Err(invalid(SnappyError::BadStreamIdentifier))
+    }
+    self.header_read = true;
+    Ok(())
+  }
+
+  /// Decode the next block into `block`, `false` at the end of stream
+  fn read_block(&mut self) -> io::Result<bool> {
+    let mut len = [0u8; 4];
+    loop {
+      let n = read_full(&mut self.inner, &mut len)?;
+      self.offset += n as u64;
+      match n {
+        0 if self.header_read => return Ok(false),
+        4 if len == MAGIC[..4] => self.read_header_rest(&len)?,
+        4 if self.header_read => break,
+        n => return Err(invalid(if self.header_read { truncated(n, 4) } else { SnappyError::BadStreamIdentifier })),
+      }
+    }
+
+    let (len, offset) = (i32::from_be_bytes(len), self.offset - 4);
+    if len < 0 || len as usize > unsafe { snappy_max_compressed_length(self.max_block_len) } {
+      return Err(invalid(SnappyError::BadChunkHeader { offset }))
+    }
+    self.compressed.resize(len as usize, 0);
+    let n = read_full(&mut self.inner, &mut self.compressed)?;
+    self.offset += n as u64;
+    if n != len as usize { return Err(invalid(truncated(n, len as usize))) }
+
+    let mut block_len = 0;
+    unsafe { status(snappy_uncompressed_length(self.compressed.as_ptr(), self.compressed.len(), &mut block_len)) }.map_err(invalid)?;
+    if block_len > self.max_block_len { return Err(invalid(SnappyError::BadChunkHeader { offset })) }
+    if self.output_len + block_len as u64 > self.max_output_size { return Err(invalid(SnappyError::OutputLimitExceeded)) }
+    self.output_len += block_len as u64;
+    self.block.resize(block_len, 0);
+    decompress_into(&self.compressed, &mut self.block).map_err(invalid)?;
+    self.pos = 0;
+    Ok(true)
+  }
+
+  pub fn get_ref(&self) -> &R { &self.inner }
+  pub fn get_mut(&mut self) -> &mut R { &mut self.inner }
+  pub fn into_inner(self) -> R { self.inner }
+}
+
+impl<R: Read> Read for XerialReader<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    while self.pos == self.block.len() {
+      if !self.read_block()? { return Ok(0) }
+    }
+
+    let block = &self.block[self.pos..];
+    let len = block.len().min(buf.len());
+    buf[..len].copy_from_slice(&block[..len]);
+    self.pos += len;
+    Ok(len)
+  }
+}
@@ -0,0 +1,27 @@
+#![cfg(feature = "tar")]
+
+use std::fs;
+
+use snappy::archive::{archive_dir, extract_archive};
+use snappy::frame::STREAM_IDENTIFIER;
+
+#[test]
+fn trees_round_trip_through_tar_sz() {
+  let dir = std::env::temp_dir().join(format!("snappy-archive-{}", std::process::id()));
+  let (src, dst) = (dir.join("src"), dir.join("dst"));
+  fs::create_dir_all(src.join("nested/deeper")).unwrap();
+  fs::write(src.join("top"), b"top file").unwrap();
+  fs::write(src.join("nested/deeper/data"), b"archive ".repeat(10_000)).unwrap();
+
+  let archive = archive_dir(&src, Vec::new()).unwrap();
+  assert!(archive.starts_with(STREAM_IDENTIFIER));
+  assert!(archive.len() < 80_000);
+
+  extract_archive(&archive[..], &dst).unwrap();
+  assert_eq!(fs::read(dst.join("top")).unwrap(), b"top file");
+  assert_eq!(fs::read(dst.join("nested/deeper/data")).unwrap(), b"archive ".repeat(10_000));
+
+  // corrupted archives fail
+  assert!(extract_archive(&archive[..archive.len() / 2], dir.join("bad")).is_err());
+  fs::remove_dir_all(dir).unwrap();
+}
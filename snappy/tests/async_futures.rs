@@ -0,0 +1,22 @@
+#![cfg(feature = "futures-io")]
+
+use futures::executor::block_on;
+use futures::io::{AsyncReadExt, AsyncWriteExt, Cursor};
+use snappy::async_futures::{AsyncFrameDecoder, AsyncFrameEncoder};
+
+#[test]
+fn futures_io_round_trips() {
+  let input: Vec<u8> = (0..200_000u32).map(|i| (i % 101) as u8).collect();
+  block_on(async {
+    let mut encoder = AsyncFrameEncoder::new(Cursor::new(Vec::new()));
+    for piece in input.chunks(9000) { encoder.write_all(piece).await.unwrap() }
+    encoder.close().await.unwrap();
+    let stream = encoder.into_inner().into_inner();
+
+    let mut output = Vec::new();
+    AsyncFrameDecoder::new(&stream[..]).read_to_end(&mut output).await.unwrap();
+    assert_eq!(output, input);
+
+    assert!(AsyncFrameDecoder::new(&stream[..stream.len() - 2]).read_to_end(&mut Vec::new()).await.is_err());
+  });
+}
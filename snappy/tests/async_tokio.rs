@@ -0,0 +1,56 @@
+#![cfg(feature = "tokio")]
+
+use std::io::Write;
+
+use snappy::async_tokio::{AsyncFrameDecoder, AsyncFrameEncoder};
+use snappy::SnappyWriter;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+fn input() -> Vec<u8> { (0..200_000u32).map(|i| (i % 97) as u8).collect() }
+
+#[tokio::test]
+async fn async_encoder_matches_blocking_writer() {
+  let input = input();
+  let mut encoder = AsyncFrameEncoder::new(Vec::new());
+  for piece in input.chunks(7000) { encoder.write_all(piece).await.unwrap() }
+  encoder.shutdown().await.unwrap();
+
+  let mut writer = SnappyWriter::new(Vec::new());
+  for piece in input.chunks(7000) { writer.write_all(piece).unwrap() }
+  assert_eq!(encoder.into_inner(), writer.finish().unwrap());
+}
+
+#[tokio::test]
+async fn async_round_trip_over_duplex_pipe() {
+  let input = input();
+  let (client, server) = tokio::io::duplex(1000);
+
+  let sent = input.clone();
+  let writer = tokio::spawn(async move {
+    let mut encoder = AsyncFrameEncoder::new(client);
+    encoder.write_all(&sent[..10]).await.unwrap();
+    encoder.flush().await.unwrap();
+    encoder.write_all(&sent[10..]).await.unwrap();
+    encoder.shutdown().await.unwrap();
+  });
+
+  let mut decoder = AsyncFrameDecoder::new(server);
+  let mut first = [0u8; 10];
+  decoder.read_exact(&mut first).await.unwrap();
+  assert_eq!(first, input[..10]);
+  let mut rest = Vec::new();
+  decoder.read_to_end(&mut rest).await.unwrap();
+  assert_eq!(rest, input[10..]);
+  writer.await.unwrap();
+}
+
+#[tokio::test]
+async fn async_decoder_reports_truncation() {
+  let mut stream = snappy::FrameEncoder::new(Vec::new());
+  stream.write_data(&input()).unwrap();
+  let stream = stream.into_inner().unwrap();
+
+  let mut output = Vec::new();
+  assert!(AsyncFrameDecoder::new(&stream[..stream.len() - 1]).read_to_end(&mut output).await.is_err());
+  assert!(AsyncFrameDecoder::new(&b""[..]).read_to_end(&mut output).await.is_err());
+}
@@ -0,0 +1,20 @@
+#![cfg(feature = "rayon")]
+
+use rayon::prelude::*;
+use snappy::{compress_all, decompress_all, ParallelSnappyExt, SnappyError};
+
+#[test]
+fn records_compress_in_parallel() {
+  let records: Vec<Vec<u8>> = (0..1000u32).map(|i| format!("record {} ", i).repeat(i as usize % 50).into_bytes()).collect();
+  let compressed = compress_all(&records);
+  assert_eq!(compressed.len(), records.len());
+  assert!(compressed.iter().zip(&records).all(|(block, record)| *block == snappy::compress(record)));
+  assert_eq!(decompress_all(&compressed).unwrap(), records);
+
+  let lens: Vec<usize> = records.par_iter().snappy_compress().snappy_decompress().map(|data| data.unwrap().len()).collect();
+  assert_eq!(lens, records.iter().map(Vec::len).collect::<Vec<_>>());
+
+  let mut corrupted = compressed.clone();
+  corrupted[500] = vec![0xff; 3];
+  assert_eq!(decompress_all(&corrupted), Err(SnappyError::InvalidInput));
+}
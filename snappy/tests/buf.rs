@@ -0,0 +1,45 @@
+#![cfg(feature = "bytes")]
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use snappy::buf::{compress_buf, compress_into_bytes_mut, compress_to_buf, decompress_buf, decompress_into_bytes_mut, decompress_to_buf};
+use snappy::SnappyError;
+
+#[test]
+fn buf_round_trips() {
+  let input = Bytes::from(vec![b'z'; 10_000]);
+  let compressed = compress_buf(input.clone());
+  assert_eq!(compressed, snappy::compress(&input));
+  assert_eq!(decompress_buf(compressed.clone()).unwrap(), input);
+
+  // data split over two chunks
+  let split = Buf::chain(&compressed[..3], &compressed[3..]);
+  assert_eq!(decompress_buf(split).unwrap(), input);
+}
+
+#[test]
+fn bytes_mut_is_appended_to() {
+  let mut output = BytesMut::from(&b"head:"[..]);
+  let len = compress_into_bytes_mut(b"payload payload payload", &mut output);
+  assert_eq!(&output[..5], b"head:");
+  assert_eq!(output.len(), 5 + len);
+
+  let mut data = BytesMut::new();
+  assert_eq!(decompress_into_bytes_mut(&output[5..], &mut data).unwrap(), 23);
+  assert_eq!(&data[..], b"payload payload payload");
+}
+
+#[test]
+fn fixed_buf_mut_is_checked() {
+  let compressed = snappy::compress(&[3; 100]);
+  let mut small = [0u8; 50];
+  assert_eq!(decompress_to_buf(&compressed, &mut &mut small[..]), Err(SnappyError::InsufficientBuffer));
+
+  let mut exact = [0u8; 100];
+  assert_eq!(decompress_to_buf(&compressed, &mut &mut exact[..]).unwrap(), 100);
+  assert_eq!(exact, [3; 100]);
+
+  let mut vec = Vec::new().limit(1000);
+  let len = compress_to_buf(&[3; 100], &mut vec);
+  assert_eq!(vec.into_inner(), compressed);
+  assert_eq!(len, compressed.len());
+}
@@ -0,0 +1,38 @@
+#![cfg(feature = "stream")]
+
+use bytes::Bytes;
+use futures::executor::block_on;
+use futures::stream::{self, StreamExt, TryStreamExt};
+
+fn input() -> Vec<u8> { (0..150_000u32).map(|i| (i % 89) as u8).collect() }
+
+#[test]
+fn stream_round_trips() {
+  let input = input();
+  let pieces: Vec<Bytes> = input.chunks(10_000).map(Bytes::copy_from_slice).collect();
+
+  let compressed: Vec<Bytes> = block_on(snappy::compress_stream(stream::iter(pieces)).collect());
+  assert_eq!(compressed.len(), 3);
+  let joined: Vec<u8> = compressed.concat();
+  let mut expected = Vec::new();
+  snappy::FrameDecoder::new(&joined[..]).read_to_end(&mut expected).unwrap();
+  assert_eq!(expected, input);
+
+  // re-split at odd places
+  let pieces: Vec<Bytes> = joined.chunks(777).map(Bytes::copy_from_slice).collect();
+  let blocks: Vec<Bytes> = block_on(snappy::decompress_stream(stream::iter(pieces)).try_collect()).unwrap();
+  assert_eq!(blocks.concat(), input);
+}
+
+#[test]
+fn stream_of_nothing_is_a_valid_stream() {
+  let compressed: Vec<Bytes> = block_on(snappy::compress_stream(stream::empty()).collect());
+  assert_eq!(compressed, [Bytes::from_static(snappy::frame::STREAM_IDENTIFIER)]);
+  let blocks: Vec<Bytes> = block_on(snappy::decompress_stream(stream::iter(compressed)).try_collect()).unwrap();
+  assert!(blocks.is_empty());
+
+  let truncated = stream::iter(vec![Bytes::from_static(&snappy::frame::STREAM_IDENTIFIER[..5])]);
+  let results: Vec<_> = block_on(snappy::decompress_stream(truncated).collect());
+  assert_eq!(results.len(), 1);
+  assert!(results[0].is_err());
+}
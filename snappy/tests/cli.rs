@@ -0,0 +1,65 @@
+#![cfg(feature = "cli")]
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Run szip with `args`, feeding `input` to stdin
+fn szip(args: &[&str], input: &[u8]) -> Output {
+  let mut child = Command::new(env!("CARGO_BIN_EXE_szip")).args(args)
+    .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+  child.stdin.take().unwrap().write_all(input).unwrap();
+  child.wait_with_output().unwrap()
+}
+
+#[test]
+fn formats_round_trip_through_pipes() {
+  let input = b"szip szip szip szip szip szip".repeat(100);
+  for format in ["raw", "framed", "hadoop", "java"] {
+    let compressed = szip(&["compress", "--format", format], &input);
+    assert!(compressed.status.success());
+    assert!(compressed.stdout.len() < input.len());
+
+    // detected, then given
+    assert_eq!(szip(&["decompress"], &compressed.stdout).stdout, input);
+    assert_eq!(szip(&["cat", "--format", format], &compressed.stdout).stdout, input);
+    assert!(szip(&["validate", "--format", format], &compressed.stdout).status.success());
+  }
+}
+
+#[test]
+fn bad_input_and_usage_fail() {
+  let invalid = szip(&["validate"], b"not snappy at all");
+  assert_eq!(invalid.status.code(), Some(1));
+  assert!(!invalid.stderr.is_empty());
+  assert_eq!(szip(&["squash"], b"").status.code(), Some(2));
+}
+
+#[test]
+fn list_shows_chunks_and_checksums() {
+  let mut compressed = szip(&["compress"], &b"list".repeat(100)).stdout;
+  let listing = String::from_utf8(szip(&["list"], &compressed).stdout).unwrap();
+  let lines: Vec<&str> = listing.lines().collect();
+  assert_eq!(lines.len(), 3);
+  assert!(lines[1].contains("stream identifier"));
+  assert!(lines[2].contains("compressed") && lines[2].contains("400") && lines[2].ends_with("ok"));
+
+  // flip a stored checksum bit
+  compressed[14] ^= 1;
+  let listing = String::from_utf8(szip(&["list"], &compressed).stdout).unwrap();
+  assert!(listing.lines().nth(2).unwrap().ends_with("mismatch"));
+}
+
+#[test]
+fn recursive_compress_writes_next_to_files() {
+  let dir = std::env::temp_dir().join(format!("szip-recursive-{}", std::process::id()));
+  std::fs::create_dir_all(dir.join("sub")).unwrap();
+  std::fs::write(dir.join("sub/data"), b"recursive").unwrap();
+  let output = szip(&["compress", "-r", "--remove", dir.to_str().unwrap()], b"");
+  assert!(output.status.success());
+  assert!(!dir.join("sub/data").exists());
+  assert_eq!(szip(&["cat", dir.join("sub/data.sz").to_str().unwrap()], b"").stdout, b"recursive");
+
+  // recursion only compresses to framed streams
+  assert_eq!(szip(&["decompress", "-r", dir.to_str().unwrap()], b"").status.code(), Some(2));
+  std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,44 @@
+#![cfg(feature = "codec")]
+
+use bytes::{Bytes, BytesMut};
+use snappy::codec::SnappyCodec;
+use snappy::MessageReader;
+use tokio_util::codec::{Decoder, Encoder};
+
+#[test]
+fn codec_matches_message_format() {
+  let long = vec![b'y'; 5000];
+  let mut codec = SnappyCodec::new();
+  let mut buf = BytesMut::new();
+  codec.encode(&b"hello"[..], &mut buf).unwrap();
+  codec.encode(&long, &mut buf).unwrap();
+
+  let messages: Vec<Vec<u8>> = MessageReader::new(&buf[..]).collect::<Result<_, _>>().unwrap();
+  assert_eq!(messages, [&b"hello"[..], &long]);
+
+  // fed a byte at a time
+  let mut src = BytesMut::new();
+  let mut decoded = Vec::new();
+  for &byte in buf.iter() {
+    src.extend_from_slice(&[byte]);
+    while let Some(message) = codec.decode(&mut src).unwrap() { decoded.push(message) }
+  }
+  assert_eq!(decoded, [Bytes::from_static(b"hello"), Bytes::from(long)]);
+  assert!(src.is_empty());
+}
+
+#[test]
+fn codec_rejects_long_messages() {
+  let mut codec = SnappyCodec::with_max_len(4);
+  let mut buf = BytesMut::new();
+  assert!(codec.encode(&b"not so short"[..], &mut buf).is_err());
+  assert!(buf.is_empty());
+
+  SnappyCodec::new().encode(&b"not so short"[..], &mut buf).unwrap();
+  assert!(codec.decode(&mut buf).is_err());
+
+  // 6 bytes claiming 4 GiB
+  let mut hostile = BytesMut::from(&[5, 0xff, 0xff, 0xff, 0xff, 0x0f][..]);
+  let error = SnappyCodec::new().decode(&mut hostile).unwrap_err();
+  assert_eq!(error.into_inner().unwrap().downcast::<snappy::SnappyError>().unwrap(), Box::new(snappy::SnappyError::OutputLimitExceeded));
+}
@@ -0,0 +1,48 @@
+#![cfg(feature = "http")]
+
+use bytes::Bytes;
+use futures::executor::block_on;
+use http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH};
+use http::{Request, Response};
+use http_body_util::{BodyExt, Full, StreamBody};
+use snappy::content_encoding::{accepts_snappy, compress_response, decompress_request, decompress_response, X_SNAPPY_FRAMED};
+
+#[test]
+fn response_round_trips_with_content_encoding() {
+  let input: Vec<u8> = (0..100_000u32).map(|i| (i % 7) as u8).collect();
+  let response = Response::builder().header(CONTENT_LENGTH, input.len()).body(Full::new(Bytes::from(input.clone()))).unwrap();
+
+  let compressed = compress_response(response);
+  assert_eq!(compressed.headers()[CONTENT_ENCODING], X_SNAPPY_FRAMED);
+  assert!(compressed.headers().get(CONTENT_LENGTH).is_none());
+  let (parts, body) = compressed.into_parts();
+  let stream = block_on(body.collect()).unwrap().to_bytes();
+  assert!(stream.len() < input.len());
+
+  // body split into odd pieces on the way
+  let pieces: Vec<Result<_, std::convert::Infallible>> = stream.chunks(1000).map(|c| Ok(http_body::Frame::data(Bytes::copy_from_slice(c)))).collect();
+  let received = Response::from_parts(parts, StreamBody::new(futures::stream::iter(pieces)));
+  let decompressed = decompress_response(received);
+  assert!(decompressed.headers().get(CONTENT_ENCODING).is_none());
+  assert_eq!(block_on(decompressed.into_body().collect()).unwrap().to_bytes(), input);
+}
+
+#[test]
+fn unencoded_bodies_pass_through() {
+  let request = Request::new(Full::new(Bytes::from_static(b"plain")));
+  let body = decompress_request(request).into_body();
+  assert!(!body.is_decompressing());
+  assert_eq!(block_on(body.collect()).unwrap().to_bytes(), &b"plain"[..]);
+
+  let corrupted = Request::builder().header(CONTENT_ENCODING, X_SNAPPY_FRAMED).body(Full::new(Bytes::from_static(b"plain"))).unwrap();
+  assert!(block_on(decompress_request(corrupted).into_body().collect()).is_err());
+}
+
+#[test]
+fn accept_encoding_is_negotiated() {
+  let accepting = |value: &str| accepts_snappy(Request::builder().header(ACCEPT_ENCODING, value).body(()).unwrap().headers());
+  assert!(accepting("gzip, x-snappy-framed"));
+  assert!(accepting("X-Snappy-Framed;q=0.5"));
+  assert!(!accepting("gzip, x-snappy-framed;q=0"));
+  assert!(!accepting("snappy"));
+}
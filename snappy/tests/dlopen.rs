@@ -0,0 +1,31 @@
+#![cfg(feature = "dlopen")]
+
+use snappy::backend::Backend;
+use snappy::dlopen::DynamicLibSnappy;
+
+#[test]
+fn loaded_library_round_trips_or_fails_gracefully() {
+  let missing = DynamicLibSnappy::open("libsnappy-missing.so").unwrap_err();
+  assert_eq!(missing.kind(), std::io::ErrorKind::NotFound);
+
+  // the library may be missing where tests run, loading must not panic then
+  let library = match DynamicLibSnappy::load() {
+    Ok(library) => library,
+    Err(e) => return assert_eq!(e.kind(), std::io::ErrorKind::NotFound),
+  };
+  let input = b"loaded at runtime ".repeat(100);
+  let compressed = library.compress_vec(&input);
+  assert!(library.validate(&compressed));
+  assert_eq!(snappy::decompress(&compressed).unwrap(), input);
+  assert_eq!(library.decompress_vec(&snappy::compress(&input)).unwrap(), input);
+  assert!(library.decompress_vec(b"\xff\xff\xff").is_err());
+  assert!(std::ptr::eq(library, DynamicLibSnappy::load().unwrap()));
+}
+
+#[test]
+#[cfg(feature = "pure-rust")]
+fn falls_back_to_pure_rust() {
+  let backend = snappy::dlopen::load_or_pure();
+  let input = b"fallback".repeat(100);
+  assert_eq!(backend.decompress_vec(&backend.compress_vec(&input)).unwrap(), input);
+}
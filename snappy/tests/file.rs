@@ -0,0 +1,255 @@
+#![cfg(feature = "std")]
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use snappy::frame::{DecoderOptions, EncoderOptions, FrameDecoder, STREAM_IDENTIFIER};
+use snappy::volume::volume_path;
+use snappy::{compress_dir, compress_file, decompress_file, DirOptions, FileOptions, FileStats, VolumeReader, VolumeWriter};
+
+/// Path of a file in a fresh temporary directory of this test
+fn temp_path(test: &str, name: &str) -> PathBuf {
+  let dir = std::env::temp_dir().join(format!("snappy-{}-{}", test, std::process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  dir.join(name)
+}
+
+#[test]
+fn files_round_trip_with_counts() {
+  let (src, compressed, decompressed) = (temp_path("file", "data"), temp_path("file", "data.sz"), temp_path("file", "data.out"));
+  let input: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+  fs::write(&src, &input).unwrap();
+
+  let stats = compress_file(&src, &compressed, EncoderOptions::new(), FileOptions::new()).unwrap();
+  let compressed_len = fs::metadata(&compressed).unwrap().len();
+  assert_eq!(stats, FileStats { input_len: input.len() as u64, output_len: compressed_len });
+  assert!(compressed_len < input.len() as u64);
+
+  let stats = decompress_file(&compressed, &decompressed, DecoderOptions::new(), FileOptions::new()).unwrap();
+  assert_eq!(stats, FileStats { input_len: compressed_len, output_len: input.len() as u64 });
+  assert_eq!(fs::read(&decompressed).unwrap(), input);
+
+  // not a framed stream
+  assert!(decompress_file(&src, &decompressed, DecoderOptions::new(), FileOptions::new()).is_err());
+  fs::remove_dir_all(src.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn atomic_output_is_never_truncated() {
+  let (src, dst) = (temp_path("atomic", "data"), temp_path("atomic", "data.out"));
+  let atomic = FileOptions::new().atomic(true);
+  fs::write(&src, b"not a framed stream").unwrap();
+  fs::write(&dst, b"old").unwrap();
+
+  assert!(decompress_file(&src, &dst, DecoderOptions::new(), atomic).is_err());
+  assert_eq!(fs::read(&dst).unwrap(), b"old");
+  assert_eq!(fs::read_dir(src.parent().unwrap()).unwrap().count(), 2);
+
+  let stats = compress_file(&src, &dst, EncoderOptions::new(), atomic).unwrap();
+  assert_eq!(stats.output_len, fs::metadata(&dst).unwrap().len());
+  assert_eq!(fs::read_dir(src.parent().unwrap()).unwrap().count(), 2);
+  fs::remove_dir_all(src.parent().unwrap()).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn permissions_are_kept() {
+  use std::os::unix::fs::PermissionsExt;
+
+  let (src, compressed) = (temp_path("perm", "data"), temp_path("perm", "data.sz"));
+  fs::write(&src, b"").unwrap();
+  fs::set_permissions(&src, fs::Permissions::from_mode(0o640)).unwrap();
+
+  compress_file(&src, &compressed, EncoderOptions::new(), FileOptions::new()).unwrap();
+  assert_eq!(fs::metadata(&compressed).unwrap().permissions().mode() & 0o777, 0o640);
+  fs::remove_dir_all(src.parent().unwrap()).unwrap();
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn mapped_files_round_trip() {
+  use snappy::file::{compress_mapped, decompress_mapped};
+
+  let (src, compressed, decompressed) = (temp_path("mmap", "data"), temp_path("mmap", "data.sz"), temp_path("mmap", "data.out"));
+  let input: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+  fs::write(&src, &input).unwrap();
+
+  let stats = compress_mapped(&src, &compressed, EncoderOptions::new(), FileOptions::new()).unwrap();
+  assert_eq!(stats.output_len, fs::metadata(&compressed).unwrap().len());
+  // same stream as buffered compression
+  compress_file(&src, &decompressed, EncoderOptions::new(), FileOptions::new()).unwrap();
+  assert_eq!(fs::read(&compressed).unwrap(), fs::read(&decompressed).unwrap());
+
+  let stats = decompress_mapped(&compressed, &decompressed, DecoderOptions::new(), FileOptions::new()).unwrap();
+  assert_eq!(stats, FileStats { input_len: fs::metadata(&compressed).unwrap().len(), output_len: input.len() as u64 });
+  assert_eq!(fs::read(&decompressed).unwrap(), input);
+
+  fs::write(&src, b"").unwrap();
+  assert_eq!(compress_mapped(&src, &compressed, EncoderOptions::new(), FileOptions::new()).unwrap().output_len, 10);
+  assert_eq!(decompress_mapped(&compressed, &decompressed, DecoderOptions::new(), FileOptions::new()).unwrap().output_len, 0);
+  fs::remove_dir_all(src.parent().unwrap()).unwrap();
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn mapped_compression_splits_frames() {
+  use snappy::file::compress_mapped;
+
+  let (src, compressed, expected) = (temp_path("mmap-frames", "data"), temp_path("mmap-frames", "data.sz"), temp_path("mmap-frames", "data.ref"));
+  // incompressible, so every block is split into stored chunks of 32 bytes with a header each
+  let mut state = 1u32;
+  let input: Vec<u8> = (0..131_072).map(|_| { state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345); (state >> 24) as u8 }).collect();
+  fs::write(&src, &input).unwrap();
+
+  let options = EncoderOptions::new().max_frame_size(64);
+  let stats = compress_mapped(&src, &compressed, options, FileOptions::new()).unwrap();
+  assert!(stats.output_len > input.len() as u64 * 6 / 5);
+  compress_file(&src, &expected, options, FileOptions::new()).unwrap();
+  assert_eq!(fs::read(&compressed).unwrap(), fs::read(&expected).unwrap());
+
+  let mut output = Vec::new();
+  FrameDecoder::new(fs::File::open(&compressed).unwrap()).read_to_end(&mut output).unwrap();
+  assert_eq!(output, input);
+  fs::remove_dir_all(src.parent().unwrap()).unwrap();
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn shared_segments_are_read_lazily() {
+  use snappy::shm::{write_segment, Segment};
+
+  let path = temp_path("shm", "segment");
+  let data: Vec<u8> = (0..200_000u32).map(|i| (i / 10 % 251) as u8).collect();
+  let file = fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+  let len = write_segment(&file, &data).unwrap();
+  assert!(len < data.len() as u64 / 2 && fs::metadata(&path).unwrap().len() == len);
+
+  // mapped anew, as by another process
+  let segment = Segment::open(&fs::File::open(&path).unwrap()).unwrap();
+  assert_eq!((segment.len(), segment.block_count()), (200_000, 4));
+  assert_eq!(segment.block(3).unwrap(), &data[196_608..]);
+  let mut buf = vec![0; 70_000];
+  assert_eq!(segment.read_at(60_000, &mut buf).unwrap(), 70_000);
+  assert_eq!(buf, &data[60_000..130_000]);
+  assert_eq!(segment.read_at(199_000, &mut buf).unwrap(), 1000);
+
+  let mut corrupted = fs::read(&path).unwrap();
+  let last = corrupted.len() - 1;
+  corrupted[last] ^= 0x55;
+  fs::write(&path, &corrupted).unwrap();
+  let segment = Segment::open(&fs::File::open(&path).unwrap()).unwrap();
+  assert!(segment.block(0).is_ok() && segment.block(3).is_err());
+  corrupted.truncate(40);
+  fs::write(&path, &corrupted).unwrap();
+  assert_eq!(Segment::open(&fs::File::open(&path).unwrap()).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+  write_segment(&file, b"").unwrap();
+  assert!(Segment::open(&file).unwrap().is_empty());
+  fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+#[test]
+fn uring_pipeline_matches_compress_file() {
+  use snappy::uring::compress_file_uring;
+
+  let (src, compressed, expected) = (temp_path("uring", "data"), temp_path("uring", "data.sz"), temp_path("uring", "expected.sz"));
+  let options = EncoderOptions::new().block_size(4096);
+  for len in [0, 1, 4096, 100_000] {
+    let input: Vec<u8> = (0..len as u32).map(|i| (i % 251 * (i % 7)) as u8).collect();
+    fs::write(&src, &input).unwrap();
+    let stats = compress_file_uring(&src, &compressed, options, FileOptions::new()).unwrap();
+    assert_eq!(stats, compress_file(&src, &expected, options, FileOptions::new()).unwrap());
+    assert_eq!(fs::read(&compressed).unwrap(), fs::read(&expected).unwrap());
+  }
+  fs::remove_dir_all(src.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn dir_trees_compress_with_globs_and_removal() {
+  let root = temp_path("dir", "tree");
+  fs::create_dir_all(root.join("logs/old")).unwrap();
+  fs::create_dir_all(root.join("skip")).unwrap();
+  let input = b"tree tree tree tree".repeat(1000);
+  for name in ["a.log", "b.txt", "logs/c.log", "logs/old/d.log", "skip/e.log", "done.log.sz"] {
+    fs::write(root.join(name), &input).unwrap();
+  }
+
+  let options = DirOptions::new().include("*.log").exclude("sk?p").remove_originals(true).threads(2);
+  let results = compress_dir(&root, &options);
+  let paths: Vec<PathBuf> = results.iter().map(|(path, result)| { assert!(result.is_ok()); path.clone() }).collect();
+  assert_eq!(paths, ["a.log", "logs/c.log", "logs/old/d.log"].map(|name| root.join(name)));
+
+  for name in ["a.log", "logs/c.log", "logs/old/d.log"] {
+    assert!(!root.join(name).exists());
+    let mut compressed = root.join(name).into_os_string();
+    compressed.push(".sz");
+    let mut output = Vec::new();
+    FrameDecoder::new(fs::File::open(compressed).unwrap()).read_to_end(&mut output).unwrap();
+    assert_eq!(output, input);
+  }
+  assert!(root.join("b.txt").exists() && root.join("skip/e.log").exists() && !root.join("skip/e.log.sz").exists());
+  fs::remove_dir_all(root.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn volumes_stay_under_size_and_read_back() {
+  let base = temp_path("volume", "data.sz");
+  let input: Vec<u8> = (0..100_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8 ^ (i % 7) as u8).collect();
+  let options = EncoderOptions::new().block_size(4096);
+  assert!(VolumeWriter::with_options(&base, 1000, options).is_err());
+
+  let mut writer = VolumeWriter::with_options(&base, 10_000, options).unwrap();
+  writer.write_all(&input).unwrap();
+  let volumes = writer.finish().unwrap();
+  assert!(volumes.len() > 2);
+  assert_eq!(volumes[1], volume_path(&base, 1));
+  for volume in &volumes {
+    let data = fs::read(volume).unwrap();
+    assert!(data.len() <= 10_000 && data.starts_with(STREAM_IDENTIFIER));
+  }
+
+  let mut output = Vec::new();
+  VolumeReader::open(&base).unwrap().read_to_end(&mut output).unwrap();
+  assert_eq!(output, input);
+  fs::remove_dir_all(base.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn wal_recovers_up_to_torn_record() {
+  use snappy::{WalReader, WalRecord, WalWriter};
+
+  let records = |log: &[u8]| {
+    let mut reader = WalReader::new(log);
+    let records: Vec<WalRecord> = reader.by_ref().map(Result::unwrap).collect();
+    (records.into_iter().map(|record| (record.seq, record.data.len())).collect::<Vec<_>>(), reader.is_torn(), reader.valid_len())
+  };
+  let mut writer = WalWriter::new(Vec::new());
+  assert_eq!(writer.append(b"first").unwrap(), 0);
+  let first_end = writer.get_ref().len() as u64;
+  writer.append(&[7; 100_000]).unwrap();
+  writer.append(b"").unwrap();
+  let log = writer.into_inner().unwrap();
+
+  assert_eq!(records(&log), (vec![(0, 5), (1, 100_000), (2, 0)], false, log.len() as u64));
+  assert_eq!(records(b""), (vec![], false, 0));
+  // the empty record is its 24 byte header chunk alone
+  assert_eq!(records(&log[..log.len() - 10]), (vec![(0, 5), (1, 100_000)], true, log.len() as u64 - 24));
+  assert_eq!(records(&log[..first_end as usize + 40]), (vec![(0, 5)], true, first_end));
+  let mut corrupted = log.clone();
+  corrupted[first_end as usize + 12] ^= 1;
+  assert_eq!(records(&corrupted), (vec![(0, 5)], true, first_end));
+
+  let path = temp_path("wal", "log");
+  fs::write(&path, &log[..log.len() - 10]).unwrap();
+  let mut writer = WalWriter::open(&path).unwrap();
+  assert_eq!(writer.append(b"after crash").unwrap(), 2);
+  drop(writer);
+  let log = fs::read(&path).unwrap();
+  assert_eq!(records(&log), (vec![(0, 5), (1, 100_000), (2, 11)], false, log.len() as u64));
+
+  fs::remove_file(&path).unwrap();
+  WalWriter::open(&path).unwrap().append(b"new").unwrap();
+  assert_eq!(records(&fs::read(&path).unwrap()).0, [(0, 3)]);
+}
@@ -0,0 +1,632 @@
+#![cfg(feature = "std")]
+
+use std::io::{self, BufReader, Cursor, IoSlice, Read, Seek, SeekFrom, Write};
+
+use snappy::crc32c::{crc32c, masked_crc32c};
+use snappy::seek::{FrameIndex, SeekableDecoder};
+use snappy::frame::{validate_frames, StreamStats, DecoderOptions, EncoderOptions, Frame, FrameDecoder, FrameEncoder, Frames, PushDecoder, STREAM_IDENTIFIER, CHUNK_COMPRESSED, CHUNK_UNCOMPRESSED};
+use snappy::SnappyError;
+
+/// Split a framed stream into (type, body) chunks
+fn chunks(mut stream: &[u8]) -> Vec<(u8, &[u8])> {
+  let mut chunks = Vec::new();
+  while !stream.is_empty() {
+    let len = stream[1] as usize | (stream[2] as usize) << 8 | (stream[3] as usize) << 16;
+    chunks.push((stream[0], &stream[4..4 + len]));
+    stream = &stream[4 + len..];
+  }
+  chunks
+}
+
+#[test]
+fn crc32c_check_value() {
+  assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+  assert_eq!(crc32c(b""), 0);
+}
+
+#[test]
+fn empty_stream_is_stream_identifier() {
+  let encoder = FrameEncoder::new(Vec::new());
+  assert_eq!(encoder.into_inner().unwrap(), STREAM_IDENTIFIER);
+  assert_eq!(FrameEncoder::new(Vec::new()).finish().unwrap(), STREAM_IDENTIFIER);
+}
+
+#[test]
+fn encoder_splits_input_into_blocks() {
+  let input: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+  let mut encoder = FrameEncoder::new(Vec::new());
+  encoder.write_data(&input).unwrap();
+  let stream = encoder.into_inner().unwrap();
+
+  assert!(stream.starts_with(STREAM_IDENTIFIER));
+  let chunks = chunks(&stream[STREAM_IDENTIFIER.len()..]);
+  assert_eq!(chunks.len(), 2);
+
+  let blocks = [&input[..65536], &input[65536..]];
+  for ((kind, body), block) in chunks.iter().zip(&blocks) {
+    assert_eq!(*kind, CHUNK_COMPRESSED);
+    assert_eq!(body[..4], masked_crc32c(block).to_le_bytes());
+    assert_eq!(&snappy::decompress(&body[4..]).unwrap()[..], *block);
+  }
+}
+
+/// Framed stream of `input`
+fn encode(input: &[u8]) -> Vec<u8> {
+  let mut encoder = FrameEncoder::new(Vec::new());
+  encoder.write_data(input).unwrap();
+  encoder.into_inner().unwrap()
+}
+
+/// Decode framed stream, error is taken back to `SnappyError`
+fn decode(stream: &[u8]) -> Result<Vec<u8>, SnappyError> {
+  let mut output = Vec::new();
+  FrameDecoder::new(stream).read_to_end(&mut output)
+    .map_err(|e| *e.into_inner().unwrap().downcast::<SnappyError>().unwrap())?;
+  Ok(output)
+}
+
+#[test]
+fn decoder_round_trips() {
+  let input: Vec<u8> = (0..200_000u32).map(|i| (i * 7 % 256) as u8).collect();
+  assert_eq!(decode(&encode(&input)).unwrap(), input);
+  assert_eq!(decode(STREAM_IDENTIFIER).unwrap(), b"");
+}
+
+#[test]
+fn decoder_accepts_uncompressed_chunks() {
+  let mut stream = STREAM_IDENTIFIER.to_vec();
+  stream.extend_from_slice(&[CHUNK_UNCOMPRESSED, 7, 0, 0]);
+  stream.extend_from_slice(&masked_crc32c(b"abc").to_le_bytes());
+  stream.extend_from_slice(b"abc");
+
+  assert_eq!(decode(&stream).unwrap(), b"abc");
+}
+
+#[test]
+fn decoder_rejects_corruption() {
+  let stream = encode(b"corrupt me, corrupt me, corrupt me");
+
+  assert_eq!(decode(&stream[STREAM_IDENTIFIER.len()..]), Err(SnappyError::BadStreamIdentifier));
+  assert_eq!(decode(&stream[..stream.len() - 1]), Err(SnappyError::TruncatedStream { missing: 1 }));
+
+  let mut bad_crc = stream.clone();
+  bad_crc[STREAM_IDENTIFIER.len() + 4] ^= 1;
+  assert!(matches!(decode(&bad_crc), Err(SnappyError::ChecksumMismatch { chunk_index: 0, .. })));
+
+  let mut reserved = stream;
+  reserved[STREAM_IDENTIFIER.len()] = 0x02;
+  assert_eq!(decode(&reserved), Err(SnappyError::UnsupportedChunk(0x02)));
+}
+
+#[test]
+fn bufread_decoder_matches_read_decoder() {
+  let input: Vec<u8> = (0..250_000u32).map(|i| (i % 97) as u8).collect();
+  let stream = encode(&input);
+
+  for &capacity in &[16, 1000, 70_000, 1 << 20] {
+    let mut output = Vec::new();
+    FrameDecoder::from_bufread(BufReader::with_capacity(capacity, &stream[..])).read_to_end(&mut output).unwrap();
+    assert_eq!(output, input);
+  }
+
+  let mut bad_crc = stream;
+  bad_crc[STREAM_IDENTIFIER.len() + 4] ^= 1;
+  assert!(FrameDecoder::from_bufread(&bad_crc[..]).read_to_end(&mut Vec::new()).is_err());
+}
+
+#[test]
+fn encoder_block_size_is_configurable() {
+  let input = vec![42u8; 10_000];
+  let mut encoder = FrameEncoder::with_options(Vec::new(), EncoderOptions::new().block_size(4096)).unwrap();
+  encoder.write_data(&input).unwrap();
+  let stream = encoder.into_inner().unwrap();
+
+  assert_eq!(chunks(&stream[STREAM_IDENTIFIER.len()..]).len(), 3);
+  assert_eq!(decode(&stream).unwrap(), input);
+
+  for &size in &[0, 1023, 65537] {
+    let error = FrameEncoder::with_options(Vec::new(), EncoderOptions::new().block_size(size)).err();
+    assert_eq!(error, Some(SnappyError::BadBlockSize(size)));
+  }
+}
+
+#[test]
+fn checksums_can_be_skipped() {
+  let input = b"trusted trusted trusted";
+  let mut encoder = FrameEncoder::with_options(Vec::new(), EncoderOptions::new().checksum(false)).unwrap();
+  encoder.write_data(input).unwrap();
+  let stream = encoder.into_inner().unwrap();
+
+  assert!(matches!(decode(&stream), Err(SnappyError::ChecksumMismatch { chunk_index: 0, expected: 0, .. })));
+
+  let mut output = Vec::new();
+  FrameDecoder::with_options(&stream[..], DecoderOptions::new().verify_checksum(false)).read_to_end(&mut output).unwrap();
+  assert_eq!(output, input);
+}
+
+#[test]
+fn crc32c_matches_bitwise_reference() {
+  fn reference(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+      crc ^= b as u32;
+      for _ in 0..8 { crc = if crc & 1 == 1 { (crc >> 1) ^ 0x82f6_3b78 } else { crc >> 1 } }
+    }
+    !crc
+  }
+
+  let data: Vec<u8> = (0..1000u32).map(|i| (i * 31 + 7) as u8).collect();
+  for len in (0..40).chain(990..1000) {
+    assert_eq!(crc32c(&data[..len]), reference(&data[..len]), "length {}", len);
+  }
+}
+
+#[test]
+fn decoder_skips_padding_and_skippable_chunks() {
+  let mut encoder = FrameEncoder::new(Vec::new());
+  encoder.write_data(b"before").unwrap();
+  encoder.write_padding(4096 - encoder.get_ref().len()).unwrap();
+  assert_eq!(encoder.get_ref().len(), 4096);
+  encoder.write_data(b" after").unwrap();
+  let mut stream = encoder.into_inner().unwrap();
+
+  stream.extend_from_slice(&[0x80, 3, 0, 0, 1, 2, 3]);
+  assert_eq!(decode(&stream).unwrap(), b"before after");
+
+  let mut output = Vec::new();
+  FrameDecoder::from_bufread(BufReader::with_capacity(64, &stream[..])).read_to_end(&mut output).unwrap();
+  assert_eq!(output, b"before after");
+
+  assert!(FrameEncoder::new(Vec::new()).write_padding(3).is_err());
+}
+
+#[test]
+fn concatenated_streams_decode_as_one() {
+  let mut stream = encode(b"first,");
+  stream.extend(encode(b"second"));
+  assert_eq!(decode(&stream).unwrap(), b"first,second");
+
+  let options = DecoderOptions::new().multi_stream(false);
+  let mut output = Vec::new();
+  let mut decoder = FrameDecoder::from_bufread_with_options(&stream[..], options);
+  decoder.read_to_end(&mut output).unwrap();
+  assert_eq!(output, b"first,");
+
+  let rest = decoder.into_inner();
+  assert_eq!(decode(rest).unwrap(), b"second");
+
+  let mut output = Vec::new();
+  FrameDecoder::with_options(&stream[..], options).read_to_end(&mut output).unwrap();
+  assert_eq!(output, b"first,");
+}
+
+#[test]
+fn lenient_decoder_resyncs_after_corruption() {
+  let input: Vec<u8> = (0..3072u32).map(|i| (i * 7 % 253) as u8).collect();
+  let mut encoder = FrameEncoder::with_options(Vec::new(), EncoderOptions::new().block_size(1024)).unwrap();
+  encoder.write_data(&input).unwrap();
+  let mut stream = encoder.into_inner().unwrap();
+
+  let lens: Vec<usize> = chunks(&stream[STREAM_IDENTIFIER.len()..]).iter().map(|(_, body)| 4 + body.len()).collect();
+  let second = STREAM_IDENTIFIER.len() + lens[0];
+  stream[second + 4] ^= 0xff;
+  assert!(matches!(decode(&stream), Err(SnappyError::ChecksumMismatch { chunk_index: 1, .. })));
+
+  let mut output = Vec::new();
+  let mut decoder = FrameDecoder::with_options(&stream[..], DecoderOptions::new().lenient(true));
+  decoder.read_to_end(&mut output).unwrap();
+  assert_eq!(output, [&input[..1024], &input[2048..]].concat());
+  assert_eq!(decoder.skipped().collect::<Vec<_>>(), [(second as u64, (second + lens[1]) as u64)].map(|(a, b)| a..b));
+  assert_eq!(decoder.offset(), stream.len() as u64);
+
+  let mut garbage = stream[..second].to_vec();
+  garbage.extend_from_slice(b"garbage");
+  garbage.extend_from_slice(&stream[second..]);
+  let mut output = Vec::new();
+  let mut decoder = FrameDecoder::from_bufread_with_options(&garbage[..], DecoderOptions::new().lenient(true));
+  decoder.read_to_end(&mut output).unwrap();
+  assert_eq!(output, [&input[..1024], &input[2048..]].concat());
+  assert_eq!(decoder.skipped().collect::<Vec<_>>(), [(second as u64, (second + 7 + lens[1]) as u64)].map(|(a, b)| a..b));
+
+  // padding whose length runs past the end: the chunks it swallowed are scanned again
+  let mut encoder = FrameEncoder::with_options(Vec::new(), EncoderOptions::new().block_size(1024)).unwrap();
+  encoder.write_data(&input[..1024]).unwrap();
+  encoder.write_padding(100).unwrap();
+  encoder.write_data(&input[1024..]).unwrap();
+  let mut padded = encoder.into_inner().unwrap();
+  let padding = STREAM_IDENTIFIER.len() + lens[0];
+  padded[padding + 1..padding + 4].copy_from_slice(&[0xff, 0xff, 0xff]);
+  let mut output = Vec::new();
+  let mut decoder = FrameDecoder::with_options(&padded[..], DecoderOptions::new().lenient(true));
+  decoder.read_to_end(&mut output).unwrap();
+  assert_eq!(output, input);
+  assert_eq!(decoder.skipped().collect::<Vec<_>>(), [(padding as u64, (padding + 100) as u64)].map(|(a, b)| a..b));
+  assert_eq!(decoder.offset(), padded.len() as u64);
+}
+
+#[test]
+fn seekable_decoder_reads_at_any_position() {
+  let input: Vec<u8> = (0..10_000u32).map(|i| (i * 13 % 241) as u8).collect();
+  let mut encoder = FrameEncoder::with_options(Vec::new(), EncoderOptions::new().block_size(1024).index(true)).unwrap();
+  encoder.write_data(&input).unwrap();
+  encoder.write_metadata(0x80, b"between").unwrap();
+  encoder.write_data(&input[..100]).unwrap();
+  let index = encoder.index().unwrap().clone();
+  let stream = encoder.into_inner().unwrap();
+
+  assert_eq!(index.entries().len(), 11);
+  assert_eq!(index.uncompressed_len(), 10_100);
+  assert_eq!(FrameIndex::scan(&mut Cursor::new(&stream)).unwrap(), index);
+
+  let mut decoder = SeekableDecoder::with_index(Cursor::new(&stream), index);
+  for &pos in &[5000u64, 0, 1023, 1024, 9990, 3333] {
+    let mut buf = [0u8; 40];
+    assert_eq!(decoder.seek(SeekFrom::Start(pos)).unwrap(), pos);
+    decoder.read_exact(&mut buf).unwrap();
+    let expected: Vec<u8> = input.iter().chain(&input[..100]).skip(pos as usize).take(40).copied().collect();
+    assert_eq!(buf[..], expected[..]);
+  }
+
+  let mut tail = Vec::new();
+  decoder.seek(SeekFrom::End(-50)).unwrap();
+  decoder.read_to_end(&mut tail).unwrap();
+  assert_eq!(tail, &input[50..100]);
+  assert!(decoder.seek(SeekFrom::Current(-20_000)).is_err());
+}
+
+#[test]
+fn encoder_appends_to_existing_stream() {
+  let mut file = Cursor::new(Vec::new());
+  FrameEncoder::append(&mut file).unwrap().write_data(b"first record;").unwrap();
+  FrameEncoder::append(&mut file).unwrap().write_data(b"second record").unwrap();
+  assert_eq!(decode(file.get_ref()).unwrap(), b"first record;second record");
+  assert_eq!(file.get_ref().windows(STREAM_IDENTIFIER.len()).filter(|w| *w == STREAM_IDENTIFIER).count(), 1);
+
+  let mut torn = Cursor::new(file.get_ref()[..file.get_ref().len() - 3].to_vec());
+  assert!(FrameEncoder::append(&mut torn).is_err());
+  let mut corrupted = file.into_inner();
+  let last = corrupted.len() - 1;
+  corrupted[last] ^= 0xff;
+  assert!(FrameEncoder::append(Cursor::new(corrupted)).is_err());
+}
+
+#[test]
+fn frames_iterates_over_chunks() {
+  let mut encoder = FrameEncoder::with_options(Vec::new(), EncoderOptions::new().block_size(1024)).unwrap();
+  encoder.write_data(&[7; 1500]).unwrap();
+  encoder.write_metadata(0x90, b"name").unwrap();
+  let stream = encoder.into_inner().unwrap();
+
+  let frames: Vec<Frame> = Frames::new(&stream[..]).collect::<Result<_, _>>().unwrap();
+  let kinds: Vec<u8> = frames.iter().map(|f| f.kind).collect();
+  assert_eq!(kinds, [0xff, CHUNK_COMPRESSED, CHUNK_COMPRESSED, 0x90]);
+  assert_eq!(frames[1].offset, STREAM_IDENTIFIER.len() as u64);
+  assert_eq!(frames[2].uncompressed_len, Some(476));
+  assert_eq!(frames[2].checksum, Some(masked_crc32c(&[7; 476])));
+  assert_eq!(frames[2].decompress().unwrap(), [7; 476]);
+  assert_eq!(frames[3].body, b"name");
+  assert_eq!(frames[3].decompress(), Err(SnappyError::UnsupportedChunk(0x90)));
+
+  let mut frames = Frames::new(&stream[..stream.len() - 1]);
+  assert_eq!(frames.by_ref().filter(Result::is_err).count(), 1);
+  assert!(frames.next().is_none());
+}
+
+#[test]
+fn validate_frames_counts_and_checks() {
+  let mut stream = encode(&[1; 70_000]);
+  stream.extend(encode(b""));
+  let stats = validate_frames(&stream[..]).unwrap();
+  assert_eq!(stats, StreamStats { streams: 2, data_chunks: 2, skipped_chunks: 0, compressed_len: stream.len() as u64, uncompressed_len: 70_000 });
+
+  stream[STREAM_IDENTIFIER.len() + 5] ^= 1;
+  let error = validate_frames(&stream[..]).unwrap_err();
+  assert!(matches!(*error.into_inner().unwrap().downcast::<SnappyError>().unwrap(), SnappyError::ChecksumMismatch { chunk_index: 0, .. }));
+}
+
+#[test]
+fn encoder_caps_frame_size() {
+  let mut seed = 1u32;
+  let input: Vec<u8> = (0..20_000).map(|_| { seed ^= seed << 13; seed ^= seed >> 17; seed ^= seed << 5; seed as u8 }).collect();
+  let options = EncoderOptions::new().max_frame_size(1400);
+  let mut encoder = FrameEncoder::with_options(Vec::new(), options).unwrap();
+  encoder.write_data(&input).unwrap();
+  encoder.write_data(&[0; 60_000]).unwrap();
+  encoder.write_data(&input[..1390]).unwrap();
+  assert!(encoder.write_metadata(0x80, &[0; 1400]).is_err());
+  let stream = encoder.into_inner().unwrap();
+
+  let chunks = chunks(&stream[STREAM_IDENTIFIER.len()..]);
+  assert!(chunks.iter().all(|(_, body)| 4 + body.len() <= 1400));
+  assert!(chunks.iter().any(|&(kind, _)| kind == CHUNK_UNCOMPRESSED));
+  assert_eq!(decode(&stream).unwrap(), [&input[..], &[0; 60_000], &input[..1390]].concat());
+
+  assert_eq!(FrameEncoder::with_options(Vec::new(), EncoderOptions::new().max_frame_size(9)).err(), Some(SnappyError::BadFrameSize(9)));
+}
+
+#[test]
+fn incompressible_blocks_are_stored() {
+  let mut seed = 7u32;
+  let noise: Vec<u8> = (0..3000).map(|_| { seed ^= seed << 13; seed ^= seed >> 17; seed ^= seed << 5; seed as u8 }).collect();
+  let options = EncoderOptions::new().block_size(1024);
+  let mut encoder = FrameEncoder::with_options(Vec::new(), options).unwrap();
+  encoder.write_data(&noise).unwrap();
+  encoder.write_data(&[1; 2048]).unwrap();
+
+  let stats = encoder.stats();
+  assert_eq!((stats.stored_blocks, stats.compressed_blocks, stats.uncompressed_len), (3, 2, 5048));
+  let stream = encoder.into_inner().unwrap();
+  assert_eq!(stats.stream_len, stream.len() as u64);
+  let kinds: Vec<u8> = chunks(&stream[STREAM_IDENTIFIER.len()..]).iter().map(|&(kind, _)| kind).collect();
+  assert_eq!(kinds, [CHUNK_UNCOMPRESSED, CHUNK_UNCOMPRESSED, CHUNK_UNCOMPRESSED, CHUNK_COMPRESSED, CHUNK_COMPRESSED]);
+  assert_eq!(decode(&stream).unwrap(), [&noise[..], &[1; 2048]].concat());
+
+  let mut encoder = FrameEncoder::with_options(Vec::new(), options.store_threshold(255)).unwrap();
+  encoder.write_data(&noise).unwrap();
+  assert_eq!(encoder.stats().stored_blocks, 0);
+}
+
+/// Writer counting its calls, taking at most `max` bytes per call
+struct CountingWriter { data: Vec<u8>, calls: usize, max: usize }
+
+impl Write for CountingWriter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.write_vectored(&[IoSlice::new(buf)]) }
+
+  fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+    self.calls += 1;
+    let all: Vec<u8> = bufs.iter().flat_map(|buf| buf.iter().copied()).take(self.max).collect();
+    self.data.extend_from_slice(&all);
+    Ok(all.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+#[test]
+fn chunks_are_written_with_one_vectored_write() {
+  let mut seed = 3u32;
+  let noise: Vec<u8> = (0..2048).map(|_| { seed ^= seed << 13; seed ^= seed >> 17; seed ^= seed << 5; seed as u8 }).collect();
+  let options = EncoderOptions::new().block_size(1024);
+  let mut encoder = FrameEncoder::with_options(CountingWriter { data: Vec::new(), calls: 0, max: usize::MAX }, options).unwrap();
+  encoder.write_data(&noise).unwrap();
+  encoder.write_data(&[1; 1024]).unwrap();
+  encoder.write_metadata(0x80, b"name").unwrap();
+  assert_eq!(encoder.stats().stored_blocks, 2);
+  let writer = encoder.into_inner().unwrap();
+  assert_eq!(writer.calls, 4);
+  assert_eq!(decode(&writer.data).unwrap(), [&noise[..], &[1; 1024]].concat());
+
+  // partial writes go on from where they stopped
+  let mut encoder = FrameEncoder::with_options(CountingWriter { data: Vec::new(), calls: 0, max: 7 }, options).unwrap();
+  encoder.write_data(&noise).unwrap();
+  assert_eq!(decode(&encoder.into_inner().unwrap().data).unwrap(), noise);
+}
+
+#[test]
+fn output_size_is_limited() {
+  let input = vec![b'b'; 300_000];
+  let stream = encode(&input);
+  let limited = |size| {
+    let mut decoder = FrameDecoder::with_options(&stream[..], DecoderOptions::new().max_output_size(size));
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).map_err(|e| *e.into_inner().unwrap().downcast::<SnappyError>().unwrap()).map(|_| output)
+  };
+  assert_eq!(limited(300_000).unwrap(), input);
+  assert_eq!(limited(299_999), Err(SnappyError::OutputLimitExceeded));
+
+  // a raw block claiming 4 GiB, nothing allocated for it
+  let bomb = b"\xff\xff\xff\xff\x0f\x00";
+  assert_eq!(snappy::decompress_with_limit(bomb, 1 << 20), Err(SnappyError::OutputLimitExceeded));
+  let compressed = snappy::compress(&input);
+  assert_eq!(snappy::decompress_with_limit(&compressed, input.len()).unwrap(), input);
+  assert_eq!(snappy::decompress_with_limit(&compressed, input.len() - 1), Err(SnappyError::OutputLimitExceeded));
+}
+
+#[test]
+fn errors_tell_where_the_stream_is_corrupted() {
+  let input: Vec<u8> = (0..3072u32).map(|i| (i * 7 % 253) as u8).collect();
+  let mut encoder = FrameEncoder::with_options(Vec::new(), EncoderOptions::new().block_size(1024)).unwrap();
+  encoder.write_data(&input).unwrap();
+  let stream = encoder.into_inner().unwrap();
+  let lens: Vec<usize> = chunks(&stream[STREAM_IDENTIFIER.len()..]).iter().map(|(_, body)| 4 + body.len()).collect();
+  let third = STREAM_IDENTIFIER.len() + lens[0] + lens[1];
+
+  let mut bad_crc = stream.clone();
+  bad_crc[third + 4] ^= 0xff;
+  let (expected, actual) = match decode(&bad_crc) {
+    Err(SnappyError::ChecksumMismatch { chunk_index: 2, expected, actual }) => (expected, actual),
+    other => panic!("{:?}", other),
+  };
+  assert_eq!(expected ^ actual, 0xff);
+  assert_eq!(Frames::new(&bad_crc[..]).nth(3).unwrap().unwrap().decompress().unwrap_err(), decode(&bad_crc).unwrap_err());
+
+  let mut bad_header = stream.clone();
+  bad_header[third] = CHUNK_UNCOMPRESSED;
+  bad_header[third + 1..third + 4].copy_from_slice(&[0xff, 0xff, 0x01]);
+  assert_eq!(decode(&bad_header), Err(SnappyError::BadChunkHeader { offset: third as u64 }));
+  assert_eq!(decode(&stream[..third + 6]), Err(SnappyError::TruncatedStream { missing: lens[2] as u64 - 6 }));
+  assert_eq!(SnappyError::BadChunkHeader { offset: 42 }.to_string(), "Bad Chunk Header at offset 42");
+}
+
+#[test]
+fn encoders_and_decoders_are_cloned_from_prototypes() {
+  let input: Vec<u8> = (0..20_000u32).map(|i| (i % 31) as u8).collect();
+  let prototype = FrameEncoder::with_options(io::sink(), EncoderOptions::new().block_size(4096).checksum(false)).unwrap();
+  let mut encoder = prototype.with_writer(Vec::new());
+  encoder.write_data(&input).unwrap();
+  let stream = encoder.finish().unwrap();
+  assert_eq!(chunks(&stream[STREAM_IDENTIFIER.len()..]).len(), 5);
+
+  let prototype = FrameDecoder::with_options(io::empty(), DecoderOptions::new().verify_checksum(false));
+  let mut output = Vec::new();
+  prototype.with_reader(&stream[..]).read_to_end(&mut output).unwrap();
+  assert_eq!(output, input);
+  assert!(decode(&stream).is_err());
+
+  // clones go on with the same stream
+  let mut encoder = FrameEncoder::new(Vec::new());
+  encoder.write_data(b"shared ").unwrap();
+  let mut fork = encoder.clone();
+  fork.write_data(b"fork").unwrap();
+  assert_eq!(decode(&fork.finish().unwrap()).unwrap(), b"shared fork");
+  assert_eq!(decode(&encoder.finish().unwrap()).unwrap(), b"shared ");
+}
+
+#[test]
+fn shuffled_numbers_compress_better() {
+  use snappy::shuffle::{compress_shuffled, decompress_shuffled, shuffle, unshuffle};
+
+  assert_eq!(shuffle(&[1, 2, 3, 4, 5, 6, 7], 2), [1, 3, 5, 2, 4, 6, 7]);
+  assert_eq!(unshuffle(&[1, 3, 5, 2, 4, 6, 7], 2), [1, 2, 3, 4, 5, 6, 7]);
+
+  // timestamps a few seconds apart
+  let readings: Vec<u8> = (0..20_000u32).flat_map(|i| (1_600_000_000 + i * 7 + i * i % 5).to_le_bytes()).collect();
+  let shuffled = compress_shuffled(&readings, 4, EncoderOptions::new()).unwrap();
+  assert!(shuffled.len() < encode(&readings).len() / 2);
+  assert_eq!(decompress_shuffled(&shuffled, DecoderOptions::new()).unwrap(), readings);
+  assert_eq!(decode(&shuffled).unwrap(), shuffle(&readings, 4));
+  assert_eq!(decompress_shuffled(&encode(&readings), DecoderOptions::new()).unwrap(), readings);
+}
+
+#[test]
+fn delta_encoded_timestamps_round_trip() {
+  use snappy::delta::{compress_delta, decompress_delta, delta_decode, delta_encode};
+
+  assert_eq!(delta_encode(&[10, 12, 11, 11, i64::MIN]), [20, 4, 1, 0, u64::MAX - 21]);
+  assert_eq!(delta_decode(&[20, 4, 1, 0, u64::MAX - 21]), [10, 12, 11, 11, i64::MIN]);
+
+  let timestamps: Vec<i64> = (0..20_000).map(|i| 1_700_000_000_000 + i * 1000 + i % 7 - 3).collect();
+  let raw: Vec<u8> = timestamps.iter().flat_map(|t| t.to_le_bytes()).collect();
+  let stream = compress_delta(&timestamps, EncoderOptions::new()).unwrap();
+  assert!(stream.len() < encode(&raw).len() / 4);
+  assert_eq!(decompress_delta(&stream, DecoderOptions::new()).unwrap(), timestamps);
+  assert_eq!(decompress_delta(&encode(&raw), DecoderOptions::new()).unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
+
+/// Run-length encoding: pairs of a byte and how many times it repeats
+#[derive(Debug)]
+struct Rle;
+
+impl snappy::block::Codec for Rle {
+  fn max_len(&self, len: usize) -> usize { 2 * len }
+
+  fn compress_block(&self, input: &[u8], output: &mut [u8]) -> Result<usize, SnappyError> {
+    let mut len = 0;
+    for run in input.chunk_by(|a, b| a == b).flat_map(|run| run.chunks(255)) {
+      output[len..len + 2].copy_from_slice(&[run[0], run.len() as u8]);
+      len += 2;
+    }
+    Ok(len)
+  }
+
+  fn decompress_block(&self, input: &[u8], output: &mut [u8]) -> Result<usize, SnappyError> {
+    if input.len() % 2 == 1 { return Err(SnappyError::InvalidInput) }
+    let mut len = 0;
+    for pair in input.chunks(2) {
+      let run = output.get_mut(len..len + pair[1] as usize).ok_or(SnappyError::InsufficientBuffer)?;
+      run.fill(pair[0]);
+      len += run.len();
+    }
+    Ok(len)
+  }
+}
+
+#[test]
+fn custom_codec_compresses_blocks() {
+  use snappy::parallel::ParallelFrameDecoder;
+
+  let data: Vec<u8> = (0..200_000u32).map(|i| (i / 1000) as u8).collect();
+  let stream = snappy::encode_all(&data, EncoderOptions::new().codec(&Rle)).unwrap();
+  assert!(stream.len() < 2000);
+  assert!(decode(&stream).is_err());
+
+  let options = DecoderOptions::new().codec(&Rle);
+  let mut decoded = Vec::new();
+  FrameDecoder::with_options(&stream[..], options).read_to_end(&mut decoded).unwrap();
+  assert_eq!(decoded, data);
+  decoded.clear();
+  ParallelFrameDecoder::with_options(&stream[..], options.parallel(3)).read_to_end(&mut decoded).unwrap();
+  assert_eq!(decoded, data);
+
+  let frame = Frames::with_codec(&stream[..], &Rle).nth(1).unwrap().unwrap();
+  assert_eq!((frame.kind, frame.uncompressed_len), (CHUNK_COMPRESSED, None));
+  assert_eq!(frame.decompress_with(&Rle).unwrap(), &data[..65536]);
+
+  let mut encoder = FrameEncoder::with_options(Vec::new(), EncoderOptions::new().codec(&Rle).index(true)).unwrap();
+  encoder.write_data(&data).unwrap();
+  let index = encoder.index().unwrap().clone();
+  let mut decoder = SeekableDecoder::with_index(Cursor::new(encoder.finish().unwrap()), index).codec(&Rle);
+  let mut buf = [0u8; 10];
+  decoder.seek(SeekFrom::Start(150_995)).unwrap();
+  decoder.read_exact(&mut buf).unwrap();
+  assert_eq!(buf, [150, 150, 150, 150, 150, 151, 151, 151, 151, 151]);
+
+  // appended chunks are indexed through the codec
+  let options = EncoderOptions::new().codec(&Rle).index(true);
+  let mut file = Cursor::new(Vec::new());
+  FrameEncoder::append_with_options(&mut file, options).unwrap().write_data(&data[..100_000]).unwrap();
+  let mut encoder = FrameEncoder::append_with_options(&mut file, options).unwrap();
+  encoder.write_data(&data[100_000..]).unwrap();
+  assert_eq!(encoder.index().unwrap().uncompressed_len(), 200_000);
+  drop(encoder);
+  decoded.clear();
+  FrameDecoder::with_options(&file.get_ref()[..], DecoderOptions::new().codec(&Rle)).read_to_end(&mut decoded).unwrap();
+  assert_eq!(decoded, data);
+  let last = file.get_ref().len() - 1;
+  file.get_mut()[last] ^= 0xff;
+  assert!(FrameEncoder::append_with_options(&mut file, options).is_err());
+
+  // noise doubles under RLE, past what decoders accept: stored even when the threshold says not to
+  let mut state = 1u32;
+  let noise: Vec<u8> = (0..131_072).map(|_| { state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345); (state >> 24) as u8 }).collect();
+  let stream = snappy::encode_all(&noise, EncoderOptions::new().codec(&Rle).store_threshold(255)).unwrap();
+  assert!(chunks(&stream[STREAM_IDENTIFIER.len()..]).iter().all(|&(kind, _)| kind == CHUNK_UNCOMPRESSED));
+  decoded.clear();
+  FrameDecoder::with_options(&stream[..], DecoderOptions::new().codec(&Rle)).read_to_end(&mut decoded).unwrap();
+  assert_eq!(decoded, noise);
+}
+
+#[test]
+fn frames_refuse_blocks_over_max_block_size() {
+  use snappy::parallel::ParallelFrameDecoder;
+
+  // a compressed chunk claiming 4 GiB of data
+  let stream = [STREAM_IDENTIFIER, b"\x00\x09\x00\x00\x00\x00\x00\x00\xff\xff\xff\xff\x0f"].concat();
+  let bad_header = |e: io::Error| e.get_ref().and_then(|e| e.downcast_ref()) == Some(&SnappyError::BadChunkHeader { offset: 10 });
+  assert!(bad_header(Frames::new(&stream[..]).nth(1).unwrap().unwrap_err()));
+  let mut decoder = ParallelFrameDecoder::with_options(&stream[..], DecoderOptions::new().parallel(2));
+  assert!(bad_header(decoder.read_to_end(&mut Vec::new()).unwrap_err()));
+  assert!(snappy::WalReader::new(&stream[..]).next().is_none());
+
+  let frame = Frame {
+    kind: CHUNK_COMPRESSED, offset: 10, compressed_len: 9, uncompressed_len: None, checksum: Some(0), data_index: Some(0),
+    body: stream[14..].to_vec(),
+  };
+  assert_eq!(frame.decompress(), Err(SnappyError::BadChunkHeader { offset: 10 }));
+}
+
+#[test]
+fn push_decoder_checks_chunk_headers_before_buffering() {
+  let mut encoder = FrameEncoder::new(Vec::new());
+  encoder.write_data(&b"pushed in pieces ".repeat(5000)).unwrap();
+  let stream = encoder.into_inner().unwrap();
+  let mut decoder = PushDecoder::new();
+  let mut output = Vec::new();
+  for piece in stream.chunks(1000) {
+    decoder.push(piece);
+    while let Some(block) = decoder.next_block().unwrap() { output.extend_from_slice(block) }
+  }
+  decoder.finish().unwrap();
+  assert_eq!(output, b"pushed in pieces ".repeat(5000));
+
+  // headers alone are enough to refuse a chunk
+  for header in [[0x02, 0xff, 0xff, 0xff], [CHUNK_COMPRESSED, 0xff, 0xff, 0xff], [CHUNK_UNCOMPRESSED, 0x05, 0x00, 0x01]] {
+    let mut decoder = PushDecoder::new();
+    decoder.push(STREAM_IDENTIFIER);
+    decoder.push(&header);
+    assert_eq!(decoder.next_block().unwrap_err().kind(), io::ErrorKind::InvalidData);
+  }
+  let mut decoder = PushDecoder::new();
+  decoder.push(&[0x02, 0x00, 0x00, 0x00]);
+  assert_eq!(decoder.next_block().unwrap_err().into_inner().unwrap().downcast::<SnappyError>().unwrap(), Box::new(SnappyError::BadStreamIdentifier));
+}
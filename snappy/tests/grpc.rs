@@ -0,0 +1,56 @@
+#![cfg(feature = "tonic")]
+
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures::executor::block_on;
+use http_body_util::{BodyExt, Full};
+use snappy::grpc::GrpcCodec;
+use tonic::body::BoxBody;
+use tonic::client::Grpc;
+use tonic::{Code, Request};
+use tower_service::Service;
+
+/// Service echoing request bodies back, or replying with a fixed body
+#[derive(Clone)]
+struct Echo(Option<Bytes>);
+
+impl Service<http::Request<BoxBody>> for Echo {
+  type Response = http::Response<BoxBody>;
+  type Error = Infallible;
+  type Future = Ready<Result<Self::Response, Infallible>>;
+
+  fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> { Poll::Ready(Ok(())) }
+
+  fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+    let body = match self.0.clone() {
+      Some(reply) => Full::new(reply).map_err(|e| match e {}).boxed_unsync(),
+      None => request.into_body(),
+    };
+    ready(Ok(http::Response::new(body)))
+  }
+}
+
+fn call(service: Echo, codec: GrpcCodec, message: Bytes) -> Result<Bytes, Code> {
+  let mut client = Grpc::new(service);
+  block_on(client.unary(Request::new(message), "/echo.Echo/Echo".parse().unwrap(), codec)).map(|response| response.into_inner())
+    .map_err(|status| status.code())
+}
+
+#[test]
+fn messages_round_trip_through_grpc() {
+  let message = Bytes::from(b"grpc ".repeat(1000));
+  assert_eq!(call(Echo(None), GrpcCodec::new(), message.clone()).unwrap(), message);
+  assert_eq!(call(Echo(None), GrpcCodec::new(), Bytes::new()).unwrap(), Bytes::new());
+
+  assert_eq!(call(Echo(None), GrpcCodec::with_max_len(100), message), Err(Code::OutOfRange));
+}
+
+#[test]
+fn corrupted_message_is_an_error() {
+  // uncompressed flag, length 3, bytes not a snappy block
+  let frame = Bytes::from_static(b"\x00\x00\x00\x00\x03\xff\xff\xff");
+  assert_eq!(call(Echo(Some(frame)), GrpcCodec::new(), Bytes::from_static(b"x")), Err(Code::DataLoss));
+}
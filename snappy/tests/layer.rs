@@ -0,0 +1,90 @@
+#![cfg(feature = "tower")]
+
+use std::future::{ready, Ready};
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures::executor::block_on;
+use http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, VARY};
+use http::{Method, Request, Response, StatusCode};
+use http_body_util::{BodyExt, Full};
+use snappy::content_encoding::{compress_request, decompress_response, DecompressBody, X_SNAPPY_FRAMED};
+use snappy::layer::SnappyLayer;
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Service replying with the request body it read, checked to be decompressed
+struct Echo;
+
+impl Service<Request<DecompressBody<Full<Bytes>>>> for Echo {
+  type Response = Response<Full<Bytes>>;
+  type Error = snappy::content_encoding::BoxError;
+  type Future = Ready<Result<Self::Response, Self::Error>>;
+
+  fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> { Poll::Ready(Ok(())) }
+
+  fn call(&mut self, request: Request<DecompressBody<Full<Bytes>>>) -> Self::Future {
+    assert!(request.headers().get(CONTENT_ENCODING).is_none());
+    ready(block_on(request.into_body().collect()).map(|body| Response::new(Full::new(body.to_bytes()))))
+  }
+}
+
+/// Service replying with an empty body of its status
+struct Empty(StatusCode);
+
+impl Service<Request<DecompressBody<Full<Bytes>>>> for Empty {
+  type Response = Response<Full<Bytes>>;
+  type Error = snappy::content_encoding::BoxError;
+  type Future = Ready<Result<Self::Response, Self::Error>>;
+
+  fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> { Poll::Ready(Ok(())) }
+
+  fn call(&mut self, _: Request<DecompressBody<Full<Bytes>>>) -> Self::Future {
+    let mut response = Response::new(Full::new(Bytes::new()));
+    *response.status_mut() = self.0;
+    ready(Ok(response))
+  }
+}
+
+#[test]
+fn requests_and_responses_are_coded() {
+  let input = Bytes::from(b"tower ".repeat(10_000));
+  let mut service = SnappyLayer::new().layer(Echo);
+
+  let request = compress_request(Request::builder().header(ACCEPT_ENCODING, "gzip, x-snappy-framed").body(Full::new(input.clone())).unwrap());
+  let (parts, body) = request.into_parts();
+  let compressed = block_on(body.collect()).unwrap().to_bytes();
+  assert!(compressed.len() < input.len());
+
+  let response = block_on(service.call(Request::from_parts(parts, Full::new(compressed)))).unwrap();
+  assert_eq!(response.headers()[CONTENT_ENCODING], X_SNAPPY_FRAMED);
+  assert_eq!(response.headers()[VARY], "accept-encoding");
+  assert!(response.body().is_compressing());
+  let response = decompress_response(response);
+  assert_eq!(block_on(response.into_body().collect()).unwrap().to_bytes(), input);
+}
+
+#[test]
+fn plain_requests_get_plain_responses() {
+  let mut service = SnappyLayer::new().layer(Echo);
+  let response = block_on(service.call(Request::new(Full::new(Bytes::from_static(b"plain"))))).unwrap();
+  assert!(response.headers().get(CONTENT_ENCODING).is_none());
+  assert!(!response.body().is_compressing());
+  assert_eq!(block_on(response.into_body().collect()).unwrap().to_bytes(), &b"plain"[..]);
+}
+
+#[test]
+fn bodiless_responses_are_passed_through() {
+  let accepting = || Request::builder().header(ACCEPT_ENCODING, X_SNAPPY_FRAMED);
+  for status in [StatusCode::NO_CONTENT, StatusCode::NOT_MODIFIED, StatusCode::PARTIAL_CONTENT] {
+    let response = block_on(SnappyLayer::new().layer(Empty(status)).call(accepting().body(Full::new(Bytes::new())).unwrap())).unwrap();
+    assert!(response.headers().get(CONTENT_ENCODING).is_none());
+    assert!(block_on(response.into_body().collect()).unwrap().to_bytes().is_empty());
+  }
+
+  let request = accepting().method(Method::HEAD).body(Full::new(Bytes::new())).unwrap();
+  let response = block_on(SnappyLayer::new().layer(Echo).call(request)).unwrap();
+  assert!(response.headers().get(CONTENT_ENCODING).is_none());
+  assert_eq!(response.headers()[VARY], "accept-encoding");
+  assert!(block_on(response.into_body().collect()).unwrap().to_bytes().is_empty());
+}
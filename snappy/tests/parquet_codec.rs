@@ -0,0 +1,28 @@
+#![cfg(feature = "parquet")]
+
+use parquet::basic::Compression;
+use parquet::compression::{create_codec, Codec, CodecOptions};
+
+use snappy::parquet_codec::SnappyCodec;
+
+#[test]
+fn pages_match_the_parquet_snappy_codec() {
+  let page: Vec<u8> = (0..50_000u32).flat_map(|i| (i / 10).to_le_bytes()).collect();
+  let mut codec = SnappyCodec::new();
+  let mut compressed = b"header".to_vec();
+  codec.compress(&page, &mut compressed).unwrap();
+  assert_eq!(&compressed[6..], snappy::compress(&page));
+
+  let mut output = Vec::new();
+  assert_eq!(codec.decompress(&compressed[6..], &mut output, Some(page.len())).unwrap(), page.len());
+  assert_eq!(output, page);
+  assert!(codec.decompress(&compressed[6..], &mut Vec::new(), Some(page.len() - 1)).is_err());
+  assert!(codec.decompress(b"\xff", &mut Vec::new(), None).is_err());
+
+  // parquet's own codec, if built in, reads ours
+  if let Ok(Some(mut theirs)) = create_codec(Compression::SNAPPY, &CodecOptions::default()) {
+    let mut output = Vec::new();
+    theirs.decompress(&compressed[6..], &mut output, Some(page.len())).unwrap();
+    assert_eq!(output, page);
+  }
+}
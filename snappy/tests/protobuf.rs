@@ -0,0 +1,29 @@
+#![cfg(feature = "prost")]
+
+use std::io;
+
+use snappy::{MessageReader, MessageWriter};
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Event {
+  #[prost(uint64, tag = "1")]
+  id: u64,
+  #[prost(string, tag = "2")]
+  payload: String,
+}
+
+#[test]
+fn protobuf_messages_replay() {
+  let events: Vec<Event> = (0..50).map(|id| Event { id, payload: "event ".repeat(id as usize) }).collect();
+  let mut writer = MessageWriter::new(Vec::new());
+  for event in &events { writer.write_proto(event).unwrap() }
+  let log = writer.into_inner();
+
+  let replayed: Vec<Event> = MessageReader::new(&log[..]).protos().collect::<io::Result<_>>().unwrap();
+  assert_eq!(replayed, events);
+
+  let mut garbage = MessageWriter::new(Vec::new());
+  garbage.write_message(&[0xff]).unwrap();
+  let error = MessageReader::new(&garbage.into_inner()[..]).read_proto::<Event>().unwrap_err();
+  assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
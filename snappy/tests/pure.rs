@@ -0,0 +1,128 @@
+#![cfg(feature = "pure-rust")]
+
+use snappy::pure;
+
+/// Inputs of every kind: empty, short, repetitive, incompressible, over a block, long runs
+fn inputs() -> Vec<Vec<u8>> {
+  let mut state = 7u32;
+  let noise: Vec<u8> = (0..100_000).map(|_| { state ^= state << 13; state ^= state >> 17; state ^= state << 5; state as u8 }).collect();
+  let text = b"pure rust snappy backend, ".repeat(5000);
+  vec![Vec::new(), b"a".to_vec(), b"abcd".to_vec(), vec![0; 200_000], noise.clone(), text.clone(), [&text[..], &noise[..], &text[..]].concat()]
+}
+
+#[test]
+fn round_trips_through_crate_functions() {
+  for input in inputs() {
+    let compressed = snappy::compress(&input);
+    assert!(compressed.len() <= pure::max_compressed_length(input.len()));
+    assert!(unsafe { snappy::validate(compressed.as_ptr(), compressed.len()) });
+    assert_eq!(pure::uncompressed_length(&compressed), Ok(input.len()));
+    assert_eq!(snappy::decompress(&compressed).unwrap(), input);
+  }
+  assert!(snappy::compress(&[0; 200_000]).len() < 20_000);
+}
+
+#[test]
+#[cfg(feature = "libsnappy")]
+fn interoperates_with_libsnappy() {
+  use snappy::raw;
+
+  for input in inputs() {
+    let compressed = snappy::compress(&input);
+    let mut output = vec![0; input.len()];
+    let mut output_len = output.len();
+    assert_eq!(unsafe { raw::snappy_uncompress(compressed.as_ptr(), compressed.len(), output.as_mut_ptr(), &mut output_len) }, raw::SNAPPY_OK);
+    assert_eq!(output, input);
+
+    let mut compressed = vec![0; unsafe { raw::snappy_max_compressed_length(input.len()) }];
+    let mut compressed_len = compressed.len();
+    assert_eq!(unsafe { raw::snappy_compress(input.as_ptr(), input.len(), compressed.as_mut_ptr(), &mut compressed_len) }, raw::SNAPPY_OK);
+    assert_eq!(snappy::decompress(&compressed[..compressed_len]).unwrap(), input);
+  }
+}
+
+#[test]
+fn corrupted_input_is_rejected() {
+  let input = b"corrupted corrupted corrupted input".repeat(20);
+  let compressed = snappy::compress(&input);
+  for len in 0..compressed.len() {
+    assert!(snappy::decompress(&compressed[..len]).is_err());
+  }
+
+  // no mutation may panic or read out of the output
+  for i in 0..compressed.len() {
+    for bit in 0..8 {
+      let mut mutated = compressed.clone();
+      mutated[i] ^= 1 << bit;
+      if let Ok(output) = snappy::decompress(&mutated) { assert_eq!(output.len(), pure::uncompressed_length(&mutated).unwrap()) }
+      assert_eq!(pure::validate(&mutated), snappy::decompress(&mutated).is_ok());
+    }
+  }
+  assert_eq!(snappy::decompress_into(&compressed, &mut [0; 10]), Err(snappy::SnappyError::InsufficientBuffer));
+
+  // literal of 4 GiB, its end overflows on 32-bit targets
+  let huge_literal = [5, 0xfc, 0xff, 0xff, 0xff, 0xff, b'a', b'b', b'c', b'd', b'e'];
+  assert!(!pure::validate(&huge_literal));
+  let mut output = [std::mem::MaybeUninit::uninit(); 5];
+  assert_eq!(pure::decompress(&huge_literal, &mut output), Err(snappy::SnappyError::InvalidInput));
+}
+
+#[test]
+#[cfg(feature = "libsnappy")]
+fn backends_are_picked_per_instance() {
+  use snappy::backend::{LibSnappy, PureRust};
+  use snappy::{Compressor, Decompressor};
+
+  let input = b"picked at runtime ".repeat(1000);
+  let mut decompressors = [Decompressor::with_backend(&LibSnappy), Decompressor::with_backend(&PureRust)];
+  for mut compressor in [Compressor::with_backend(&LibSnappy), Compressor::with_backend(&PureRust)] {
+    let compressed = compressor.compress(&input);
+    for decompressor in &mut decompressors { assert_eq!(decompressor.decompress(compressed).unwrap(), &input[..]) }
+  }
+}
+
+#[test]
+fn fixed_buffers_compress_without_heap() {
+  const INPUT_LEN: usize = 4000;
+  let mut output = [0u8; pure::max_compressed_length(INPUT_LEN)];
+  let input: Vec<u8> = b"telemetry 0042 ok; ".iter().cycle().take(INPUT_LEN).copied().collect();
+
+  let mut table = [0u16; pure::MIN_TABLE_SIZE];
+  let len = pure::compress_with_table(&input, &mut table, &mut output).unwrap();
+  assert!(len < INPUT_LEN / 4);
+  let mut decompressed = [0u8; INPUT_LEN];
+  assert_eq!(snappy::decompress_into(&output[..len], &mut decompressed), Ok(INPUT_LEN));
+  assert_eq!(&decompressed[..], &input[..]);
+
+  let mut odd_table = [0u16; 1000];
+  assert_eq!(pure::compress_with_table(&input, &mut odd_table, &mut output), Err(snappy::SnappyError::InvalidInput));
+  assert_eq!(pure::compress_with_table(&input, &mut table, &mut output[..100]), Err(snappy::SnappyError::InsufficientBuffer));
+}
+
+#[test]
+fn patterns_of_every_period_round_trip() {
+  // copies closer and farther than the SIMD widths, matches ending on and off their boundaries
+  for period in 1..=70 {
+    for len in [31, 32, 33, 64, 65, 1000] {
+      let mut input: Vec<u8> = (0..len).map(|i| (i % period) as u8 ^ 0x5a).collect();
+      input.extend((0..period).map(|i| i as u8));
+      let compressed = snappy::compress(&input);
+      assert_eq!(snappy::decompress(&compressed).unwrap(), input, "period {} length {}", period, len);
+    }
+  }
+}
+
+#[test]
+fn level_two_compresses_denser() {
+  let (mut level1, mut level2) = (0, 0);
+  for input in inputs() {
+    let mut output = vec![std::mem::MaybeUninit::uninit(); pure::max_compressed_length(input.len())];
+    let len = pure::compress_level(&input, &mut output, 2).unwrap();
+    let compressed: Vec<u8> = output[..len].iter().map(|byte| unsafe { byte.assume_init() }).collect();
+    assert_eq!(snappy::decompress(&compressed).unwrap(), input);
+
+    level1 += snappy::compress(&input).len();
+    level2 += len;
+  }
+  assert!(level2 < level1, "{} !< {}", level2, level1);
+}
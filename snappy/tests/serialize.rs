@@ -0,0 +1,34 @@
+#![cfg(feature = "serde")]
+
+use std::collections::BTreeMap;
+
+use snappy::serialize::Json;
+use snappy::Compressed;
+
+#[test]
+fn compressed_values_round_trip() {
+  let mut map = BTreeMap::new();
+  for i in 0..200 { map.insert(format!("key {}", i), "value ".repeat(i % 10)); }
+  let json = serde_json::to_vec(&Compressed::<_, Json>::new(map.clone())).unwrap();
+  assert!(json.len() < serde_json::to_vec(&map).unwrap().len());
+
+  let back: Compressed<BTreeMap<String, String>> = serde_json::from_slice(&json).unwrap();
+  assert_eq!(*back, map);
+
+  assert!(serde_json::from_str::<Compressed<String>>("[1, 2, 3]").is_err());
+}
+
+#[test]
+fn bincode_round_trips_compressed() {
+  use snappy::serialize::Bincode;
+  use snappy::{from_compressed_bincode, to_compressed_bincode};
+
+  let value: Vec<(u32, String)> = (0..500).map(|i| (i, "cached ".repeat(3))).collect();
+  let data = to_compressed_bincode(&value).unwrap();
+  assert!(data.len() < bincode::serialize(&value).unwrap().len() / 2);
+  assert_eq!(from_compressed_bincode::<Vec<(u32, String)>>(&data).unwrap(), value);
+  assert!(from_compressed_bincode::<Vec<(u32, String)>>(&data[..data.len() - 1]).is_err());
+
+  let bytes = bincode::serialize(&Compressed::<_, Bincode>::new(value.clone())).unwrap();
+  assert_eq!(bincode::deserialize::<Compressed<Vec<(u32, String)>, Bincode>>(&bytes).unwrap().into_inner(), value);
+}
@@ -0,0 +1,408 @@
+#![cfg(feature = "std")]
+
+use std::io::{self, Read, Write};
+
+use snappy::frame::{DecoderOptions, EncoderOptions, FrameDecoder, Metadata, STREAM_IDENTIFIER};
+use snappy::{copy_compress, copy_decompress, hadoop, xerial, HadoopReader, HadoopWriter, SnappyReader, SnappyWriter, XerialReader, XerialWriter};
+
+/// Decode framed stream
+fn decode(stream: &[u8]) -> Vec<u8> {
+  let mut output = Vec::new();
+  FrameDecoder::new(stream).read_to_end(&mut output).unwrap();
+  output
+}
+
+#[test]
+fn writer_round_trips_small_writes() {
+  let input: Vec<u8> = (0..150_000u32).map(|i| (i % 13) as u8).collect();
+  let mut writer = SnappyWriter::new(Vec::new());
+  for piece in input.chunks(1000) { writer.write_all(piece).unwrap() }
+
+  assert_eq!(decode(&writer.finish().unwrap()), input);
+}
+
+#[test]
+fn writer_flush_ends_chunk() {
+  let mut writer = SnappyWriter::new(Vec::new());
+  writer.write_all(b"hello").unwrap();
+  assert_eq!(writer.get_ref().len(), 0);
+
+  writer.flush().unwrap();
+  let flushed = writer.get_ref().len();
+  assert!(flushed > STREAM_IDENTIFIER.len());
+
+  writer.write_all(b", world").unwrap();
+  let stream = writer.finish().unwrap();
+  assert!(stream.len() > flushed);
+  assert_eq!(decode(&stream), b"hello, world");
+}
+
+#[test]
+fn writer_writes_buffer_on_drop() {
+  let mut stream = Vec::new();
+  SnappyWriter::new(&mut stream).write_all(b"dropped").unwrap();
+
+  assert_eq!(decode(&stream), b"dropped");
+}
+
+#[test]
+fn reader_round_trips_through_io_copy() {
+  let input: Vec<u8> = (0..300_000u32).map(|i| (i / 100) as u8).collect();
+  let mut writer = SnappyWriter::new(Vec::new());
+  writer.write_all(&input).unwrap();
+  let stream = writer.finish().unwrap();
+
+  let mut output = Vec::new();
+  io::copy(&mut SnappyReader::new(&stream[..]), &mut output).unwrap();
+  assert_eq!(output, input);
+
+  let mut small = [0u8; 7];
+  let mut reader = SnappyReader::new(&stream[..]);
+  reader.read_exact(&mut small).unwrap();
+  assert_eq!(small, input[..7]);
+}
+
+#[test]
+fn copy_helpers_report_lengths() {
+  let input: Vec<u8> = (0..200_000u32).map(|i| (i / 50) as u8).collect();
+  let mut stream = Vec::new();
+  let stats = copy_compress(&mut &input[..], &mut stream, EncoderOptions::new()).unwrap();
+  assert_eq!((stats.input_len, stats.output_len), (input.len() as u64, stream.len() as u64));
+  assert_eq!(decode(&stream), input);
+
+  let mut output = Vec::new();
+  let stats = copy_decompress(&mut &stream[..], &mut output, DecoderOptions::new()).unwrap();
+  assert_eq!((stats.input_len, stats.output_len), (stream.len() as u64, input.len() as u64));
+  assert_eq!(output, input);
+
+  let mut empty = Vec::new();
+  assert_eq!(copy_compress(&mut io::empty(), &mut empty, EncoderOptions::new()).unwrap().output_len, STREAM_IDENTIFIER.len() as u64);
+  assert!(copy_decompress(&mut &stream[..stream.len() - 1], &mut io::sink(), DecoderOptions::new()).is_err());
+}
+
+#[test]
+fn parallel_copy_keeps_decoding_options() {
+  let input: Vec<u8> = (0..200_000u32).map(|i| (i / 50) as u8).collect();
+  let copy = |stream: &[u8], options: DecoderOptions| {
+    let mut output = Vec::new();
+    copy_decompress(&mut &stream[..], &mut output, options.parallel(2)).map(|_| output)
+  };
+
+  let unchecked = snappy::encode_all(&input, EncoderOptions::new().checksum(false)).unwrap();
+  assert!(copy(&unchecked, DecoderOptions::new()).is_err());
+  assert_eq!(copy(&unchecked, DecoderOptions::new().verify_checksum(false)).unwrap(), input);
+
+  let stream = snappy::encode_all(&input, EncoderOptions::new()).unwrap();
+  let concatenated = [&stream[..], &snappy::encode_all(b"second", EncoderOptions::new()).unwrap()].concat();
+  assert_eq!(copy(&concatenated, DecoderOptions::new().multi_stream(false)).unwrap(), input);
+
+  let mut corrupted = stream.clone();
+  corrupted[STREAM_IDENTIFIER.len() + 4] ^= 0xff;
+  assert!(copy(&corrupted, DecoderOptions::new()).is_err());
+  assert_eq!(copy(&corrupted, DecoderOptions::new().lenient(true)).unwrap(), &input[65536..]);
+}
+
+#[test]
+fn options_pick_format_and_threads() {
+  use snappy::{decode_all, detect_format, encode_all, Format};
+
+  let input: Vec<u8> = (0..100_000u32).map(|i| (i % 97) as u8).collect();
+  for format in [Format::Raw, Format::Framed, Format::Hadoop, Format::Xerial] {
+    for threads in [1, 3] {
+      let stream = encode_all(&input, EncoderOptions::new().block_size(8192).format(format).parallel(threads)).unwrap();
+      assert_eq!(detect_format(&stream), Some(format));
+      let options = DecoderOptions::new().format(format).parallel(threads);
+      assert_eq!(decode_all(&stream, options).unwrap(), input);
+
+      let error = decode_all(&stream, options.max_output_size(50_000)).unwrap_err();
+      assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+  }
+}
+
+#[test]
+fn reader_reports_corruption() {
+  let mut writer = SnappyWriter::new(Vec::new());
+  writer.write_all(b"some bytes").unwrap();
+  let mut stream = writer.finish().unwrap();
+  let last = stream.len() - 1;
+  stream[last] ^= 0xff;
+
+  let error = SnappyReader::new(&stream[..]).read_to_end(&mut Vec::new()).unwrap_err();
+  assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn snappy_errors_convert_to_io_errors() {
+  fn decompress(input: &[u8]) -> io::Result<Vec<u8>> { Ok(snappy::decompress(input)?) }
+
+  let error = decompress(b"\xff\xff\xff").unwrap_err();
+  assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+  assert_eq!(*error.into_inner().unwrap().downcast::<snappy::SnappyError>().unwrap(), snappy::SnappyError::InvalidInput);
+
+  let error = io::Error::from(snappy::SnappyError::BadBlockSize(1));
+  assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+  assert_eq!(error.get_ref().unwrap().downcast_ref(), Some(&snappy::SnappyError::BadBlockSize(1)));
+
+  let mut stream = SnappyWriter::new(Vec::new()).finish().unwrap();
+  stream.extend_from_slice(b"\x00\x01");
+  let error = io::copy(&mut SnappyReader::new(&stream[..]), &mut io::sink()).unwrap_err();
+  assert_eq!(error.get_ref().unwrap().downcast_ref(), Some(&snappy::SnappyError::TruncatedStream { missing: 2 }));
+}
+
+#[test]
+fn metadata_round_trips() {
+  let mut writer = SnappyWriter::new(Vec::new());
+  writer.write_metadata(0x80, b"name=data.txt").unwrap();
+  writer.write_all(b"file body").unwrap();
+  writer.write_metadata(0x81, b"sha=abc").unwrap();
+  assert!(writer.write_metadata(0xfe, b"padding is not metadata").is_err());
+  let stream = writer.finish().unwrap();
+
+  assert_eq!(decode(&stream), b"file body");
+
+  let mut reader = SnappyReader::with_options(&stream[..], DecoderOptions::new().read_metadata(true));
+  let mut body = Vec::new();
+  reader.read_to_end(&mut body).unwrap();
+  let metadata: Vec<Metadata> = reader.metadata().collect();
+
+  assert_eq!(body, b"file body");
+  assert_eq!(metadata, vec![
+    Metadata { tag: 0x80, data: b"name=data.txt".to_vec() },
+    Metadata { tag: 0x81, data: b"sha=abc".to_vec() },
+  ]);
+}
+
+#[test]
+fn sync_flush_makes_written_data_decodable() {
+  let mut writer = SnappyWriter::new(Vec::new());
+  writer.sync_flush().unwrap();
+  assert_eq!(writer.get_ref(), STREAM_IDENTIFIER);
+
+  writer.write_all(b"ping").unwrap();
+  writer.sync_flush().unwrap();
+  let mut reader = SnappyReader::new(&writer.get_ref()[..]);
+  let mut request = [0u8; 4];
+  reader.read_exact(&mut request).unwrap();
+  assert_eq!(&request, b"ping");
+
+  writer.write_all(b"pong").unwrap();
+  writer.sync_flush().unwrap();
+  assert_eq!(decode(&writer.finish().unwrap()), b"pingpong");
+}
+
+#[test]
+fn hadoop_format_round_trips() {
+  let input: Vec<u8> = (0..500_000u32).map(|i| (i % 17) as u8).collect();
+  let mut writer = HadoopWriter::new(Vec::new());
+  io::copy(&mut &input[..], &mut writer).unwrap();
+  let stream = writer.finish().unwrap();
+  assert_eq!(stream[..4], (hadoop::DEFAULT_BLOCK_SIZE as u32).to_be_bytes());
+
+  let mut output = Vec::new();
+  HadoopReader::new(&stream[..]).read_to_end(&mut output).unwrap();
+  assert_eq!(output, input);
+}
+
+#[test]
+fn hadoop_reader_joins_subchunks() {
+  let mut stream = 11u32.to_be_bytes().to_vec();
+  for part in [&b"hello "[..], b"world"] {
+    let compressed = snappy::compress(part);
+    stream.extend_from_slice(&(compressed.len() as u32).to_be_bytes());
+    stream.extend_from_slice(&compressed);
+  }
+  let mut output = Vec::new();
+  HadoopReader::new(&stream[..]).read_to_end(&mut output).unwrap();
+  assert_eq!(output, b"hello world");
+
+  stream[3] = 10;
+  assert!(HadoopReader::new(&stream[..]).read_to_end(&mut Vec::new()).is_err());
+  stream[3] = 12;
+  assert!(HadoopReader::new(&stream[..]).read_to_end(&mut Vec::new()).is_err());
+}
+
+#[test]
+fn hadoop_reader_bounds_lengths_before_allocating() {
+  let bad_header = |stream: &[u8], at: u64| {
+    let e = HadoopReader::new(stream).read_to_end(&mut Vec::new()).unwrap_err();
+    e.get_ref().and_then(|e| e.downcast_ref()) == Some(&snappy::SnappyError::BadChunkHeader { offset: at })
+  };
+  // subchunk of 4 GiB compressed
+  assert!(bad_header(b"\x00\x00\x00\x10\xff\xff\xff\xff", 4));
+  // block of 4 GiB of data
+  assert!(bad_header(b"\xff\xff\xff\xff\x00\x00\x00\x05\xff\xff\xff\xff\x0f", 0));
+
+  let mut writer = HadoopWriter::new(Vec::new());
+  writer.write_all(&[7; 100_000]).unwrap();
+  let stream = writer.finish().unwrap();
+  assert!(HadoopReader::new(&stream[..]).read_to_end(&mut Vec::new()).is_ok());
+  assert!(HadoopReader::new(&stream[..]).max_block_len(50_000).read_to_end(&mut Vec::new()).is_err());
+}
+
+#[test]
+fn xerial_format_round_trips() {
+  let input: Vec<u8> = (0..100_000u32).map(|i| (i % 29) as u8).collect();
+  let mut writer = XerialWriter::new(Vec::new());
+  io::copy(&mut &input[..], &mut writer).unwrap();
+  let mut stream = writer.finish().unwrap();
+  assert!(stream.starts_with(xerial::HEADER));
+
+  let mut output = Vec::new();
+  XerialReader::new(&stream[..]).read_to_end(&mut output).unwrap();
+  assert_eq!(output, input);
+
+  // concatenated, as Kafka batches are
+  stream.extend(XerialWriter::new(Vec::new()).finish().unwrap());
+  let mut tail = XerialWriter::new(Vec::new());
+  tail.write_all(b"tail").unwrap();
+  stream.extend(tail.finish().unwrap());
+  let mut output = Vec::new();
+  XerialReader::new(&stream[..]).read_to_end(&mut output).unwrap();
+  assert_eq!(output, [&input[..], b"tail"].concat());
+
+  assert!(XerialReader::new(&b"\x82SNAPPX\x00\x00\x00\x00\x01\x00\x00\x00\x01"[..]).read_to_end(&mut Vec::new()).is_err());
+  assert!(XerialReader::new(&b""[..]).read_to_end(&mut Vec::new()).is_err());
+}
+
+#[test]
+fn xerial_reader_bounds_block_lengths_before_allocating() {
+  let bad_header = |stream: &[u8]| {
+    let e = XerialReader::new(stream).read_to_end(&mut Vec::new()).unwrap_err();
+    matches!(e.get_ref().and_then(|e| e.downcast_ref()), Some(snappy::SnappyError::BadChunkHeader { offset: 16 }))
+  };
+  // a 5 byte block claiming 4 GiB of data
+  assert!(bad_header(&[xerial::HEADER, b"\x00\x00\x00\x05\xff\xff\xff\xff\x0f"].concat()));
+  // 2 GiB of compressed data claimed
+  assert!(bad_header(&[xerial::HEADER, b"\x7f\xff\xff\xff\x00"].concat()));
+
+  let mut writer = XerialWriter::with_block_size(Vec::new(), 100_000);
+  writer.write_all(&[7; 100_000]).unwrap();
+  let stream = writer.finish().unwrap();
+  assert!(XerialReader::new(&stream[..]).read_to_end(&mut Vec::new()).is_ok());
+  let mut reader = XerialReader::new(&stream[..]).max_block_len(50_000);
+  assert_eq!(reader.read_to_end(&mut Vec::new()).unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn kafka_payloads_of_both_formats_decode() {
+  use snappy::kafka;
+
+  let records = b"kafka record ".repeat(5000);
+  let payload = kafka::compress(&records);
+  assert!(payload.starts_with(xerial::HEADER));
+  assert_eq!(kafka::decompress(&payload).unwrap(), records);
+  assert_eq!(kafka::decompress(&snappy::compress(&records)).unwrap(), records);
+  assert!(kafka::decompress(&payload[..payload.len() - 1]).is_err());
+}
+
+#[test]
+fn formats_are_detected_and_decompressed() {
+  use snappy::{decompress_auto, detect_format, Format};
+
+  let input = b"any snappy flavor, any snappy flavor, any snappy flavor";
+  let framed = { let mut w = SnappyWriter::new(Vec::new()); w.write_all(input).unwrap(); w.finish().unwrap() };
+  let hadoop = { let mut w = HadoopWriter::new(Vec::new()); w.write_all(input).unwrap(); w.finish().unwrap() };
+  let xerial = { let mut w = XerialWriter::new(Vec::new()); w.write_all(input).unwrap(); w.finish().unwrap() };
+  let raw = snappy::compress(input);
+
+  for (stream, format) in [(raw, Format::Raw), (framed, Format::Framed), (hadoop, Format::Hadoop), (xerial, Format::Xerial)] {
+    assert_eq!(detect_format(&stream), Some(format));
+    assert_eq!(decompress_auto(&stream).unwrap(), input);
+  }
+  assert_eq!(detect_format(b"\xff\xff\xff\xff plain text"), None);
+  assert_eq!(decompress_auto(b""), Err(snappy::SnappyError::InvalidInput));
+}
+
+#[test]
+fn messages_round_trip() {
+  use snappy::{MessageReader, MessageWriter};
+
+  let long = vec![b'x'; 1000];
+  let mut writer = MessageWriter::new(Vec::new());
+  for message in [&b"ping"[..], b"", &long] { writer.write_message(message).unwrap() }
+  let stream = writer.into_inner();
+  assert_eq!(stream[0] as usize, snappy::compress(b"ping").len());
+
+  let messages: Vec<Vec<u8>> = MessageReader::new(&stream[..]).collect::<io::Result<_>>().unwrap();
+  assert_eq!(messages, [&b"ping"[..], b"", &long]);
+
+  let mut reader = MessageReader::with_max_len(&stream[..], 8);
+  assert_eq!(reader.read_message().unwrap().unwrap(), b"ping");
+  reader.read_message().unwrap();
+  assert!(reader.read_message().is_err());
+  assert!(MessageReader::new(&stream[..stream.len() - 1]).nth(2).unwrap().is_err());
+
+  // 6 bytes claiming 4 GiB
+  let hostile = [5, 0xff, 0xff, 0xff, 0xff, 0x0f];
+  let error = MessageReader::new(&hostile[..]).read_message().unwrap_err();
+  assert_eq!(error.into_inner().unwrap().downcast::<snappy::SnappyError>().unwrap(), Box::new(snappy::SnappyError::OutputLimitExceeded));
+}
+
+#[test]
+fn parallel_encoder_matches_frame_encoder() {
+  use snappy::frame::{EncoderOptions, FrameEncoder};
+  use snappy::ParallelFrameEncoder;
+
+  let mut seed = 5u32;
+  let input: Vec<u8> = (0..200_000).map(|i| if i % 3000 < 1000 { seed ^= seed << 13; seed ^= seed >> 17; seed ^= seed << 5; seed as u8 } else { i as u8 }).collect();
+  let options = EncoderOptions::new().block_size(4096);
+  let mut expected = FrameEncoder::with_options(Vec::new(), options).unwrap();
+  expected.write_data(&input).unwrap();
+  let expected_stats = expected.stats();
+  let expected = expected.into_inner().unwrap();
+
+  let mut encoder = ParallelFrameEncoder::with_options(Vec::new(), options.parallel(4)).unwrap();
+  for piece in input.chunks(1000) { encoder.write_all(piece).unwrap() }
+  encoder.sync_flush().unwrap();
+  assert_eq!(encoder.stats(), expected_stats);
+  assert_eq!(encoder.finish().unwrap(), expected);
+
+  let empty = ParallelFrameEncoder::with_options(Vec::new(), options.parallel(2)).unwrap().finish().unwrap();
+  assert_eq!(empty, STREAM_IDENTIFIER);
+}
+
+#[test]
+fn parallel_decoder_matches_frame_decoder() {
+  use snappy::ParallelFrameDecoder;
+
+  let input: Vec<u8> = (0..300_000u32).map(|i| (i % 251 * (i % 7)) as u8).collect();
+  let mut writer = SnappyWriter::new(Vec::new());
+  writer.write_all(&input[..100_000]).unwrap();
+  writer.write_metadata(0x80, b"skipped").unwrap();
+  writer.write_all(&input[100_000..]).unwrap();
+  let stream = writer.finish().unwrap();
+
+  let mut output = Vec::new();
+  ParallelFrameDecoder::with_options(&stream[..], DecoderOptions::new().parallel(3)).read_to_end(&mut output).unwrap();
+  assert_eq!(output, input);
+
+  // data before a corrupted chunk comes first
+  let mut corrupted = stream.clone();
+  let last = corrupted.len() - 1;
+  corrupted[last] ^= 1;
+  let mut decoder = ParallelFrameDecoder::with_options(&corrupted[..], DecoderOptions::new().parallel(3));
+  let mut output = vec![0; 200_000];
+  decoder.read_exact(&mut output).unwrap();
+  assert_eq!(output, &input[..200_000]);
+  assert_eq!(decoder.read_to_end(&mut Vec::new()).unwrap_err().kind(), io::ErrorKind::InvalidData);
+  assert!(decoder.read(&mut [0]).is_err());
+}
+
+#[test]
+fn output_limit_is_checked_on_claimed_lengths() {
+  use snappy::Format;
+
+  let limit_exceeded = |stream: &[u8], options: DecoderOptions| {
+    let e = copy_decompress(&mut &stream[..], &mut Vec::new(), options.max_output_size(1000)).unwrap_err();
+    e.get_ref().and_then(|e| e.downcast_ref()) == Some(&snappy::SnappyError::OutputLimitExceeded)
+  };
+  // each claims 1 MiB of data in a few bytes, which are no valid block
+  let hadoop = b"\x00\x10\x00\x00\x00\x00\x00\x05\x80\x80\x40\xff\xff";
+  assert!(limit_exceeded(hadoop, DecoderOptions::new().format(Format::Hadoop)));
+  let xerial = [xerial::HEADER, b"\x00\x00\x00\x05\x80\x80\x40\xff\xff"].concat();
+  assert!(limit_exceeded(&xerial, DecoderOptions::new().format(Format::Xerial)));
+  // 64 KiB, as much as a chunk can hold
+  let framed = [STREAM_IDENTIFIER, b"\x00\x09\x00\x00\x00\x00\x00\x00\x80\x80\x04\xff\xff"].concat();
+  assert!(limit_exceeded(&framed, DecoderOptions::new().parallel(2)));
+}
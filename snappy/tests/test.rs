@@ -0,0 +1,452 @@
+extern crate snappy;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// Global allocator counting bytes alive and allocations made on the current thread
+struct CountingAlloc;
+
+thread_local! { static LIVE_BYTES: Cell<isize> = const { Cell::new(0) } }
+thread_local! { static ALLOCATIONS: Cell<usize> = const { Cell::new(0) } }
+
+unsafe impl GlobalAlloc for CountingAlloc {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    LIVE_BYTES.with(|n| n.set(n.get() + layout.size() as isize));
+    ALLOCATIONS.with(|n| n.set(n.get() + 1));
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    LIVE_BYTES.with(|n| n.set(n.get() - layout.size() as isize));
+    System.dealloc(ptr, layout)
+  }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+#[test]
+fn it_works() {
+  println!("It works!");
+}
+
+#[test]
+fn compress_shrinks_repetitive_input() {
+  let input = [b'a'; 1024];
+  let output = snappy::compress(&input);
+
+  assert!(output.len() < input.len());
+  assert!(unsafe { snappy::validate(output.as_ptr(), output.len()) });
+}
+
+#[test]
+fn decompress_round_trips() {
+  let input = b"snappy snappy snappy snappy frontend";
+  let output = snappy::decompress(&snappy::compress(input)).unwrap();
+
+  assert_eq!(&output[..], &input[..]);
+}
+
+#[test]
+fn decompress_rejects_garbage() {
+  assert_eq!(snappy::decompress(b"\xff\xff\xff\xff\xff"), Err(snappy::SnappyError::InvalidInput));
+}
+
+#[test]
+fn compress_into_reports_written_length() {
+  let input = [b'z'; 512];
+  let mut output = [0u8; 1024];
+  let written = snappy::compress_into(&input, &mut output).unwrap();
+
+  assert_eq!(&output[..written], &snappy::compress(&input)[..]);
+  assert_eq!(snappy::compress_into(&input, &mut output[..16]), Err(snappy::SnappyError::InsufficientBuffer));
+}
+
+#[test]
+fn decompress_into_checks_buffer_length() {
+  let compressed = snappy::compress(b"0123456789abcdef");
+  let mut output = [0u8; 16];
+
+  assert_eq!(snappy::decompress_into(&compressed, &mut output), Ok(16));
+  assert_eq!(&output, b"0123456789abcdef");
+  assert_eq!(snappy::decompress_into(&compressed, &mut output[..15]), Err(snappy::SnappyError::InsufficientBuffer));
+}
+
+#[test]
+fn errors_propagate_with_question_mark() {
+  fn round_trip(input: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Ok(snappy::decompress(&snappy::compress(input))?)
+  }
+
+  assert_eq!(round_trip(b"question mark").unwrap(), b"question mark");
+  assert_eq!(snappy::SnappyError::InvalidInput.to_string(), "Invalid Input");
+  assert_eq!(snappy::SnappyResult::InsufficientBuffer.into_result(), Err(snappy::SnappyError::InsufficientBuffer));
+}
+
+#[test]
+fn inflate_reports_true_length() {
+  let compressed = snappy::compress(b"inflate me");
+  let mut output = [0u8; 32];
+  let mut output_len = output.len();
+
+  let result = unsafe { snappy::inflate(compressed.as_ptr(), compressed.len(), output.as_mut_ptr(), &mut output_len) };
+  assert!(result.is_ok());
+  assert_eq!(&output[..output_len], b"inflate me");
+
+  output_len = 4;
+  let result = unsafe { snappy::inflate(compressed.as_ptr(), compressed.len(), output.as_mut_ptr(), &mut output_len) };
+  assert!(result.insuff_buf());
+}
+
+#[test]
+fn deflate_writes_into_caller_buffer() {
+  let input = [7u8; 256];
+  let mut output = vec![0u8; unsafe { snappy::snappy_max_compressed_length(input.len()) }];
+  let mut output_len = output.len();
+
+  let result = unsafe { snappy::deflate(input.as_ptr(), input.len(), output.as_mut_ptr(), &mut output_len) };
+  assert!(result.is_ok());
+  assert_eq!(&output[..output_len], &snappy::compress(&input)[..]);
+}
+
+#[test]
+fn buffers_use_global_allocator() {
+  let live = || LIVE_BYTES.with(|n| n.get());
+  let before = live();
+
+  {
+    let compressed = snappy::compress(&[1u8; 4096]);
+    assert!(live() - before >= compressed.capacity() as isize);
+
+    let output = snappy::decompress(&compressed).unwrap();
+    assert!(live() - before >= (compressed.capacity() + output.capacity()) as isize);
+  }
+
+  assert_eq!(live(), before);
+}
+
+#[test]
+fn unknown_status_codes_are_errors() {
+  use std::convert::TryFrom;
+
+  assert!(snappy::SnappyResult::try_from(2).unwrap().insuff_buf());
+  assert_eq!(snappy::SnappyResult::try_from(42).err(), Some(snappy::SnappyError::Unknown(42)));
+}
+
+#[test]
+fn compressors_reuse_their_buffers() {
+  let (small, large) = (b"small ".repeat(10), b"large ".repeat(1000));
+  let mut compressor = snappy::Compressor::new();
+  let mut decompressor = snappy::Decompressor::new();
+  let compressed_large = compressor.compress(&large).to_vec();
+  assert_eq!(decompressor.decompress(&compressed_large).unwrap(), &large[..]);
+
+  let allocations = || ALLOCATIONS.with(|n| n.get());
+  let before = allocations();
+  for input in [&small, &large, &small] {
+    let compressed = compressor.compress(input);
+    assert_eq!(compressed, &snappy::compress(input)[..]);
+  }
+  assert_eq!(decompressor.decompress(&compressed_large).unwrap(), &large[..]);
+  // one per reference `snappy::compress` call only
+  assert_eq!(allocations() - before, 3);
+
+  assert_eq!(decompressor.decompress(b"\xff\xff\xff"), Err(snappy::SnappyError::InvalidInput));
+  assert!(snappy::Compressor::with_capacity(1000).capacity() >= 1000);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn avro_blocks_end_with_crc32() {
+  use snappy::avro::crc32;
+  use snappy::{avro_compress_block, avro_decompress_block, SnappyError};
+
+  assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+  let input = b"avro record ".repeat(100);
+  let block = avro_compress_block(&input);
+  assert_eq!(block[block.len() - 4..], crc32(&input).to_be_bytes());
+  assert_eq!(avro_decompress_block(&block).unwrap(), input);
+
+  let mut corrupted = block.clone();
+  *corrupted.last_mut().unwrap() ^= 1;
+  assert!(matches!(avro_decompress_block(&corrupted), Err(SnappyError::ChecksumMismatch { .. })));
+  assert!(matches!(avro_decompress_block(&block[..2]), Err(SnappyError::TruncatedStream { missing: 2 })));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn leveldb_blocks_have_trailers() {
+  use snappy::crc32c::{crc32c, crc32c_extend};
+  use snappy::leveldb::{compress_block, decompress_block, split_trailer, NO_COMPRESSION, SNAPPY_COMPRESSION};
+
+  // vectors of LevelDB crc32c_test.cc
+  assert_eq!(crc32c(&[0; 32]), 0x8a91_36aa);
+  assert_eq!(crc32c(&[0xff; 32]), 0x62a8_ab43);
+  assert_eq!(crc32c(&(0..32).collect::<Vec<u8>>()), 0x46dd_794e);
+  assert_eq!(crc32c_extend(crc32c(b"hello "), b"world"), crc32c(b"hello world"));
+
+  let contents = b"key0001value".repeat(100);
+  let block = compress_block(&contents);
+  let (stored, kind) = split_trailer(&block).unwrap();
+  assert_eq!((stored, kind), (&snappy::compress(&contents)[..], SNAPPY_COMPRESSION));
+  assert_eq!(decompress_block(&block).unwrap(), &contents[..]);
+
+  // stored as is: "abc", type 0, masked CRC-32C of "abc\0"
+  let block = compress_block(b"abc");
+  assert_eq!(block, [b'a', b'b', b'c', NO_COMPRESSION, 0x00, 0x54, 0x1b, 0xbf]);
+  assert!(matches!(decompress_block(&block).unwrap(), std::borrow::Cow::Borrowed(b"abc")));
+
+  let mut corrupted = block.clone();
+  corrupted[0] ^= 1;
+  assert!(split_trailer(&corrupted).is_err());
+  assert!(split_trailer(&block[..4]).is_err());
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn cql_frames_are_compressed_and_decompressed() {
+  use snappy::cql::{compress_frame, decompress_body, decompress_frame};
+
+  // v4 RESULT of kind Void on stream 0, as Cassandra sends it with snappy agreed, then plain
+  let captured = [0x84, 0x01, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x06, 0x04, 0x0c, 0x00, 0x00, 0x00, 0x01];
+  let plain = [0x84, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01];
+  assert_eq!(decompress_body(&captured[9..]).unwrap(), [0, 0, 0, 1]);
+  assert_eq!(decompress_frame(&captured).unwrap(), plain);
+  assert_eq!(compress_frame(&plain).unwrap(), captured);
+  assert_eq!(decompress_frame(&plain).unwrap(), plain);
+
+  // v4 QUERY "SELECT * FROM system.local" at consistency ONE, stream 1, next frame following
+  let query = b"SELECT * FROM system.local";
+  let mut body = (query.len() as u32).to_be_bytes().to_vec();
+  body.extend_from_slice(query);
+  body.extend_from_slice(&[0x00, 0x01, 0x00]);
+  let mut frame = vec![0x04, 0x00, 0x00, 0x01, 0x07];
+  frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+  frame.extend_from_slice(&body);
+  let mut compressed = compress_frame(&frame).unwrap();
+  compressed.extend_from_slice(&plain);
+  assert_eq!(compressed[1], 0x01);
+  assert_eq!(decompress_frame(&compressed).unwrap(), frame);
+  assert!(decompress_frame(&compressed[..12]).is_err());
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn pooled_buffers_are_reused() {
+  use snappy::pooled;
+
+  let input = b"pooled ".repeat(1000);
+  let compressed = pooled::compress(&input);
+  assert_eq!(&compressed[..], &snappy::compress(&input)[..]);
+  assert_eq!(&pooled::decompress(&compressed).unwrap()[..], &input[..]);
+  drop(compressed);
+
+  let allocations = || ALLOCATIONS.with(|n| n.get());
+  let before = allocations();
+  for _ in 0..10 {
+    let compressed = pooled::compress(&input);
+    assert_eq!(&pooled::decompress(&compressed).unwrap()[..], &input[..]);
+  }
+  assert_eq!(allocations(), before);
+  assert!(pooled::decompress(b"\xff\xff\xff").is_err());
+
+  // nothing kept over the limits
+  pooled::set_limits(0, 0);
+  drop(pooled::compress(&input));
+  let before = allocations();
+  drop(pooled::compress(&input));
+  assert_eq!(allocations(), before + 1);
+  pooled::clear();
+}
+
+#[test]
+fn uninit_and_spare_outputs_append_in_place() {
+  use std::mem::MaybeUninit;
+
+  let input = b"spare capacity ".repeat(100);
+  let mut output = b"header".to_vec();
+  let len = snappy::compress_to_spare(&input, &mut output);
+  assert_eq!(&output[..6], b"header");
+  assert_eq!(&output[6..], &snappy::compress(&input)[..]);
+  assert_eq!(len, output.len() - 6);
+
+  let mut decompressed = Vec::new();
+  assert_eq!(snappy::decompress_to_spare(&output[6..], &mut decompressed), Ok(input.len()));
+  assert_eq!(decompressed, input);
+  assert!(snappy::decompress_to_spare(b"\xff\xff\xff", &mut decompressed).is_err());
+
+  let mut buffer = [MaybeUninit::<u8>::uninit(); 4096];
+  let compressed = snappy::compress_uninit(&input, &mut buffer).unwrap().to_vec();
+  assert_eq!(snappy::decompress_uninit(&compressed, &mut buffer).unwrap(), &input[..]);
+  assert_eq!(snappy::decompress_uninit(&compressed, &mut buffer[..10]).err(), Some(snappy::SnappyError::InsufficientBuffer));
+}
+
+#[test]
+fn default_backend_matches_crate_functions() {
+  let backend = snappy::default_backend();
+  let input = b"backend backend backend".repeat(50);
+  let compressed = backend.compress_vec(&input);
+  assert_eq!(compressed, snappy::compress(&input));
+  assert!(backend.validate(&compressed) && !backend.validate(b"\xff\xff\xff"));
+  assert_eq!(backend.uncompressed_len(&compressed), Ok(input.len()));
+  assert_eq!(backend.decompress_vec(&compressed).unwrap(), input);
+
+  let mut output = [std::mem::MaybeUninit::uninit(); 10];
+  assert_eq!(backend.decompress(&compressed, &mut output), Err(snappy::SnappyError::InsufficientBuffer));
+}
+
+#[test]
+fn compression_options_round_trip_at_every_level() {
+  let input = b"level one, level two; level one, level two! ".repeat(200);
+  for level in 0..4 {
+    let options = snappy::CompressionOptions::new().level(level);
+    let compressed = snappy::compress_with_options(&input, options);
+    assert_eq!(snappy::decompress(&compressed).unwrap(), input);
+
+    let mut output = vec![0; unsafe { snappy::snappy_max_compressed_length(input.len()) }];
+    assert_eq!(snappy::compress_into_with_options(&input, &mut output, options), Ok(compressed.len()));
+    assert_eq!(&output[..compressed.len()], &compressed[..]);
+    assert_eq!(snappy::compress_into_with_options(&input, &mut output[..10], options), Err(snappy::SnappyError::InsufficientBuffer));
+  }
+  assert_eq!(snappy::compress_with_options(&input, snappy::CompressionOptions::default()), snappy::compress(&input));
+}
+
+#[test]
+fn lengths_are_known_before_allocating() {
+  let input = b"length ".repeat(300);
+  let compressed = snappy::compress(&input);
+  assert!(snappy::max_compress_len(input.len()) >= compressed.len());
+  assert_eq!(snappy::max_compress_len(input.len()), unsafe { snappy::snappy_max_compressed_length(input.len()) });
+  assert_eq!(snappy::decompress_len(&compressed), Ok(input.len()));
+
+  // a header claiming 2^32 - 1 bytes, with nothing after it
+  assert_eq!(snappy::decompress_len(b"\xff\xff\xff\xff\x0f"), Ok(u32::MAX as usize));
+  assert_eq!(snappy::decompress_len(b"\xff\xff"), Err(snappy::SnappyError::InvalidInput));
+}
+
+#[test]
+fn incompressible_input_is_borrowed() {
+  use std::borrow::Cow;
+
+  let text = b"borrow or compress ".repeat(20);
+  let result = snappy::compress_or_borrow(&text);
+  assert!(result.compressed && matches!(result.data, Cow::Owned(_)));
+  assert_eq!(snappy::decompress_or_borrow(&result.data, result.compressed).unwrap(), &text[..]);
+
+  let noise: Vec<u8> = (0..200u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+  let result = snappy::compress_or_borrow(&noise);
+  assert!(!result.compressed && matches!(result.data, Cow::Borrowed(data) if data.as_ptr() == noise.as_ptr()));
+  assert!(matches!(snappy::decompress_or_borrow(&result.data, false), Ok(Cow::Borrowed(_))));
+  assert!(snappy::decompress_or_borrow(b"\xff\xff\xff", true).is_err());
+}
+
+#[test]
+fn ratio_is_estimated_from_samples() {
+  let text = b"estimate the ratio of this text ".repeat(10_000);
+  let mut state = 1u32;
+  let noise: Vec<u8> = (0..320_000).map(|_| { state ^= state << 13; state ^= state >> 17; state ^= state << 5; state as u8 }).collect();
+
+  let text_ratio = snappy::estimate_ratio(&text, 8192);
+  let actual = snappy::compress(&text).len() as f32 / text.len() as f32;
+  assert!(text_ratio < 0.2 && (text_ratio - actual).abs() < 0.05, "{} vs {}", text_ratio, actual);
+  assert!(snappy::estimate_ratio(&noise, 8192) > 0.99);
+  assert!(snappy::estimate_ratio(&noise, 100) > 0.99);
+
+  let mixed = [&text[..160_000], &noise[..160_000]].concat();
+  let mixed_ratio = snappy::estimate_ratio(&mixed, 8192);
+  assert!(mixed_ratio > text_ratio && mixed_ratio < 0.99);
+  assert_eq!(snappy::estimate_ratio(b"", 8192), 1.0);
+  assert_eq!(snappy::estimate_ratio(b"short", 8192), snappy::compress(b"short").len() as f32 / 5.0);
+}
+
+#[test]
+fn extension_methods_on_slices_and_vecs() {
+  use snappy::SnappyExt;
+
+  let input = b"extension extension extension".to_vec();
+  let compressed = input.snappy_compress();
+  assert_eq!(compressed, snappy::compress(&input));
+  assert!(compressed.snappy_valid() && !input.snappy_valid());
+  assert_eq!(compressed.snappy_decompress().unwrap(), input);
+  assert_eq!(compressed[..].snappy_decompress().unwrap(), input);
+  assert_eq!(b"\xff\xff\xff".snappy_decompress(), Err(snappy::SnappyError::InvalidInput));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn compressed_buf_reads_at_any_offset() {
+  use snappy::store::BLOCK_SIZE;
+  use std::io::Write;
+
+  let data: Vec<u8> = (0..300_000u32).map(|i| (i / 100 % 251) as u8).collect();
+  let mut buf = snappy::CompressedBuf::new();
+  buf.extend_from_slice(&data[..1000]);
+  buf.extend_from_slice(&data[1000..200_000]);
+  buf.write_all(&data[200_000..]).unwrap();
+  assert_eq!(buf.len(), data.len());
+  assert!(buf.compressed_len() < data.len() / 4);
+
+  for &(offset, len) in &[(0, 10), (BLOCK_SIZE - 5, 10), (1000, 3 * BLOCK_SIZE), (299_000, 1000)] {
+    assert_eq!(buf.read_at(offset, len), &data[offset..offset + len]);
+  }
+  assert_eq!(buf.read_at(299_990, 100), &data[299_990..]);
+  assert!(buf.read_at(400_000, 5).is_empty());
+  let mut out = [0u8; 64];
+  assert_eq!(buf.read_into(299_950, &mut out), 50);
+  assert_eq!(out[..50], data[299_950..]);
+
+  buf.clear();
+  assert!(buf.is_empty() && buf.read_at(0, 10).is_empty());
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn compressed_lru_evicts_by_compressed_size() {
+  let value = |i: u8| vec![i; 10_000];
+  let physical = snappy::compress(&value(0)).len();
+  let mut cache = snappy::CompressedLru::new(3 * physical);
+  for i in 0..3 { cache.insert(i.to_string(), &value(i)) }
+  assert_eq!((cache.len(), cache.logical_size(), cache.physical_size()), (3, 30_000, 3 * physical));
+
+  assert_eq!(cache.get("0"), Some(value(0)));
+  cache.insert("3".to_string(), &value(3));
+  assert!(cache.contains_key("0") && !cache.contains_key("1"));
+  assert_eq!(cache.get("1"), None);
+
+  cache.insert("0".to_string(), &value(9));
+  assert_eq!((cache.len(), cache.get("0")), (3, Some(value(9))));
+  assert_eq!(cache.remove("2"), Some(value(2)));
+  assert_eq!(cache.physical_size(), 2 * physical);
+
+  let noise: Vec<u8> = (0..100_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8).collect();
+  cache.insert("big".to_string(), &noise);
+  assert!(!cache.contains_key("big") && cache.len() == 2);
+  cache.set_budget(physical);
+  assert_eq!((cache.len(), cache.logical_size()), (1, 10_000));
+  assert!(cache.contains_key("0"));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn compressed_channel_carries_payloads_between_threads() {
+  use std::sync::mpsc::TrySendError;
+
+  let (sender, receiver) = snappy::compressed_channel(2);
+  let producers: Vec<_> = (0..3u8).map(|i| {
+    let sender = sender.clone();
+    std::thread::spawn(move || for n in 0..10 { sender.send(&vec![i; 1000 * n]).unwrap() })
+  }).collect();
+  drop(sender);
+  let mut received: Vec<Vec<u8>> = receiver.into_iter().collect();
+  producers.into_iter().for_each(|producer| producer.join().unwrap());
+  received.sort();
+  assert_eq!(received.len(), 30);
+  assert!(received.iter().all(|payload| payload.iter().all(|&b| b == payload.first().copied().unwrap_or(0))));
+
+  let (sender, receiver) = snappy::compressed_channel(1);
+  sender.try_send(b"first").unwrap();
+  assert_eq!(sender.try_send(b"second"), Err(TrySendError::Full(())));
+  assert_eq!(receiver.recv().unwrap(), b"first");
+  drop(receiver);
+  assert!(sender.send(b"third").is_err());
+}
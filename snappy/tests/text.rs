@@ -0,0 +1,15 @@
+#![cfg(feature = "base64")]
+
+use snappy::{compress_to_base64, decompress_from_base64, SnappyError};
+
+#[test]
+fn base64_text_round_trips() {
+  let payload = br#"{"trace":"span","tags":["a","a","a","a","a","a"]}"#.repeat(20);
+  let text = compress_to_base64(&payload);
+  assert!(text.bytes().all(|b| b.is_ascii_alphanumeric() || b"+/=".contains(&b)));
+  assert!(text.len() < payload.len());
+  assert_eq!(decompress_from_base64(&format!("{}\n", text)).unwrap(), payload);
+
+  assert_eq!(compress_to_base64(b""), "AA==");
+  assert!(matches!(decompress_from_base64("not base64!"), Err(SnappyError::InvalidInput)));
+}
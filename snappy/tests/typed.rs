@@ -0,0 +1,18 @@
+#![cfg(feature = "bytemuck")]
+
+use snappy::{compress_slice, decompress_slice, SnappyError};
+
+#[test]
+fn numeric_slices_round_trip() {
+  let readings: Vec<f64> = (0..10_000).map(|i| (i / 100) as f64 * 0.5).collect();
+  let compressed = compress_slice(&readings);
+  assert!(compressed.len() < readings.len() * 8 / 4);
+  assert_eq!(decompress_slice::<f64>(&compressed).unwrap(), readings);
+
+  let counters: Vec<u32> = (0..1000).collect();
+  assert_eq!(decompress_slice::<u32>(&compress_slice(&counters)).unwrap(), counters);
+  assert_eq!(decompress_slice::<[u16; 2]>(&compress_slice(&counters)).unwrap().len(), 1000);
+
+  assert!(matches!(decompress_slice::<u32>(&snappy::compress(&[1, 2, 3])), Err(SnappyError::InvalidInput)));
+  assert!(decompress_slice::<u64>(&[]).is_err());
+}
@@ -1,4 +0,0 @@
-#[test]
-fn it_works() {
-  println!("It works!");
-}